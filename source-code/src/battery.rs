@@ -0,0 +1,83 @@
+// ─────────────────────────────────────────────────────────────────────────────
+// Stan baterii dla placeholdera {battery}
+//
+// Czytany z /sys/class/power_supply/BAT*/{capacity,status}. Na komputerach
+// bez baterii segment jest pusty. Wynik trzymany kilka sekund, żeby nie
+// czytać sysfs przy każdym prompcie.
+// ─────────────────────────────────────────────────────────────────────────────
+
+use std::fs;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+use std::time::{Duration, Instant};
+
+const POWER_SUPPLY: &str = "/sys/class/power_supply";
+const CACHE_TTL: Duration = Duration::from_secs(5);
+
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BatteryStatus {
+    Charging,
+    Discharging,
+    Full,
+    Unknown,
+}
+
+#[derive(Clone, Debug)]
+pub struct BatteryInfo {
+    pub capacity: u8,
+    pub status:   BatteryStatus,
+}
+
+impl BatteryInfo {
+    /// `⚡87%` / `🔋42%` — na czerwono poniżej `warn_below` (gdy nie ładuje)
+    pub fn format(&self, warn_below: u8) -> String {
+        let icon = match self.status {
+            BatteryStatus::Charging => "⚡",
+            BatteryStatus::Full     => "🔌",
+            _                       => "🔋",
+        };
+        let color = if self.capacity < warn_below && self.status != BatteryStatus::Charging {
+            "\x1b[38;5;203m"
+        } else {
+            "\x1b[38;5;114m"
+        };
+        format!("{}{}{}%\x1b[0m", color, icon, self.capacity)
+    }
+}
+
+static CACHE: Mutex<Option<(Instant, Option<BatteryInfo>)>> = Mutex::new(None);
+
+/// Stan pierwszej baterii (BAT0, potem kolejne BAT*) — z pamięci podręcznej
+pub fn battery_info() -> Option<BatteryInfo> {
+    let mut cache = CACHE.lock().ok()?;
+    if let Some((at, info)) = cache.as_ref() {
+        if at.elapsed() < CACHE_TTL {
+            return info.clone();
+        }
+    }
+    let info = find_battery().and_then(|dir| read_battery(&dir));
+    *cache = Some((Instant::now(), info.clone()));
+    info
+}
+
+fn find_battery() -> Option<PathBuf> {
+    let mut batteries: Vec<PathBuf> = fs::read_dir(POWER_SUPPLY)
+        .ok()?
+        .flatten()
+        .filter(|e| e.file_name().to_string_lossy().starts_with("BAT"))
+        .map(|e| e.path())
+        .collect();
+    batteries.sort();
+    batteries.into_iter().next()
+}
+
+fn read_battery(dir: &Path) -> Option<BatteryInfo> {
+    let capacity = fs::read_to_string(dir.join("capacity")).ok()?.trim().parse::<u8>().ok()?;
+    let status = match fs::read_to_string(dir.join("status")).unwrap_or_default().trim() {
+        "Charging"    => BatteryStatus::Charging,
+        "Discharging" => BatteryStatus::Discharging,
+        "Full"        => BatteryStatus::Full,
+        _             => BatteryStatus::Unknown,
+    };
+    Some(BatteryInfo { capacity: capacity.min(100), status })
+}
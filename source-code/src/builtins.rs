@@ -1,51 +1,35 @@
 use std::collections::HashMap;
 use std::env;
+use std::fs;
+use std::io::{self, Write};
 use std::path::{Path, PathBuf};
 use rustyline::Editor;
 
+use crate::execute::ShellCtx;
+use crate::expand::expand_tilde;
 use crate::helper::ShellHelper;
-use crate::history::ShellHistory;
-use crate::jobs::JobTable;
+use crate::jobs::JobStatus;
 use crate::vars::ShellVars;
 
-fn expand_tilde(s: &str) -> String {
-    if s.starts_with('~') {
-        if let Ok(home) = env::var("HOME") {
-            format!("{}{}", home, &s[1..])
-        } else {
-            s.to_string()
-        }
-    } else {
-        s.to_string()
-    }
-}
-
-fn parse_job_id(s: &str) -> Option<usize> {
-    if s.starts_with('%') {
-        s[1..].parse().ok()
-    } else if s == "%" {
-        Some(1)
-    } else {
-        s.parse().ok()
-    }
-}
-
 pub fn handle_builtin(
     cmd:            &str,
-    _rl:            &mut Editor<ShellHelper, rustyline::history::FileHistory>,
+    ctx:            &mut ShellCtx,
+    rl:             &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:       &mut Option<PathBuf>,
-    jobs:           &mut JobTable,
-    shell_history:  &ShellHistory,
-    aliases:        &HashMap<String, String>,
     dry_run:        bool,
-    vars:           &mut ShellVars,
     _heredoc_bodies: &HashMap<String, String>,
 ) -> Option<i32> {
     let trimmed = cmd.trim();
 
     // ── cd ───────────────────────────────────────────────────────────────────
     if trimmed == "cd" || trimmed.starts_with("cd ") {
-        let dir_str = trimmed.strip_prefix("cd").unwrap_or("").trim();
+        let mut dir_str = trimmed.strip_prefix("cd").unwrap_or("").trim();
+        // -L (domyślnie): `..` względem logicznego $PWD; -P: fizycznie
+        let mut physical = false;
+        while let Some(flag) = dir_str.split_whitespace().next().filter(|f| *f == "-L" || *f == "-P") {
+            physical = flag == "-P";
+            dir_str = dir_str[flag.len()..].trim_start();
+        }
         let target_dir = if dir_str.is_empty() {
             env::var("HOME").unwrap_or_else(|_| "/".to_string())
         } else if dir_str == "-" {
@@ -58,23 +42,223 @@ pub fn handle_builtin(
                 return Some(1);
             }
         } else {
-            expand_tilde(dir_str)
+            // `cd "$1"` — cudzysłowy po rozwinięciu zmiennych zdejmujemy tutaj
+            let dir = crate::tokenize::split(dir_str)
+                .and_then(|w| w.into_iter().next())
+                .unwrap_or_else(|| dir_str.to_string());
+            // `cd @nazwa[/podkatalog]` — zakładka z ~/.hsh_bookmarks
+            let dir = match dir.strip_prefix('@') {
+                Some(rest) if !ctx.vars.posix => {
+                    let (name, sub) = rest.split_once('/').unwrap_or((rest, ""));
+                    match crate::config::load_bookmarks().get(name) {
+                        Some(base) if sub.is_empty() => base.clone(),
+                        Some(base) => format!("{}/{}", base, sub),
+                        None => {
+                            eprintln!("cd: @{}: no such bookmark", name);
+                            return Some(1);
+                        }
+                    }
+                }
+                _ => dir,
+            };
+            // $CDPATH — tylko dla względnego celu, którego nie ma w bieżącym katalogu
+            match search_cdpath(&dir) {
+                Some(found) => {
+                    println!("{}", found);
+                    found
+                }
+                None => expand_tilde(&dir),
+            }
         };
         if dry_run {
             println!("[dry-run] cd {}", target_dir);
             return Some(0);
         }
         let current = env::current_dir().unwrap_or(PathBuf::from("/"));
-        if env::set_current_dir(&target_dir).is_ok() {
+        // Ścieżka logiczna może nie istnieć fizycznie (np. `..` za dowiązaniem
+        // do usuniętego rodzica) — wtedy jak bash próbujemy dosłownego celu
+        let logical = (!physical).then(|| ctx.vars.logical_path(&target_dir));
+        if logical.as_ref().map(|l| env::set_current_dir(l).is_ok()).unwrap_or(false)
+            || env::set_current_dir(&target_dir).is_ok()
+            || (ctx.vars.cd_mkdir && !ctx.vars.posix && offer_mkdir(&target_dir) && env::set_current_dir(&target_dir).is_ok())
+        {
             *prev_dir = Some(current);
-            vars.set_pwd();
+            match &logical {
+                Some(l) => ctx.vars.set_pwd_logical(l),
+                None    => ctx.vars.set_pwd(),
+            }
+            // Baza frecency dla `j` — tylko interaktywne `cd`, w trybie
+            // bezpiecznym bez zapisu
+            if ctx.vars.interactive && !ctx.vars.safe_mode {
+                crate::frecency::record(&env::var("PWD").unwrap_or_default());
+            }
             Some(0)
+        } else if ctx.vars.posix {
+            let err = env::set_current_dir(&target_dir).err()
+                .map(|e| io_error_text(&e))
+                .unwrap_or_default();
+            eprintln!("hsh: cd: {}: {}", target_dir, err);
+            Some(1)
         } else {
             eprintln!("cd: no such file or directory: {}", target_dir);
             Some(1)
         }
     }
 
+    // ── bookmark — zakładki katalogów (~/.hsh_bookmarks) ────────────────────
+    // add NAZWA [KATALOG] / go NAZWA / rm NAZWA / list; `cd @nazwa` też działa
+    else if trimmed == "bookmark" || trimmed.starts_with("bookmark ") {
+        let args = match crate::tokenize::split_args(&trimmed[8..]) {
+            Ok(a)  => a,
+            Err(e) => { eprintln!("bookmark: {}", e); return Some(1); }
+        };
+        let mut bookmarks = crate::config::load_bookmarks();
+        let name = args.get(1).map(String::as_str).unwrap_or("");
+        let valid = !name.is_empty() && !name.contains(['/', '\t', '\n']);
+
+        match args.first().map(String::as_str) {
+            None | Some("list") | Some("ls") => {
+                for (name, path) in &bookmarks {
+                    if Path::new(path).is_dir() {
+                        println!("  {:<12} {}", name, path);
+                    } else {
+                        println!("\x1b[31m! {:<12} {} (missing)\x1b[0m", name, path);
+                    }
+                }
+                return Some(0);
+            }
+            Some("go") => {
+                let Some(path) = bookmarks.get(name) else {
+                    eprintln!("bookmark: {}: no such bookmark", name);
+                    return Some(1);
+                };
+                if !Path::new(path).is_dir() {
+                    eprintln!("bookmark: {}: {} no longer exists (bookmark rm {})", name, path, name);
+                    return Some(1);
+                }
+                let cd = format!("cd {}", shlex::try_quote(path).unwrap_or_default());
+                return handle_builtin(&cd, ctx, rl, prev_dir, dry_run, _heredoc_bodies);
+            }
+            Some("add") if valid => {
+                let dir = match args.get(2) {
+                    Some(d) => fs::canonicalize(expand_tilde(d)),
+                    None    => env::current_dir(),
+                };
+                let dir = match dir {
+                    Ok(d) if d.is_dir() => d.to_string_lossy().to_string(),
+                    _ => {
+                        eprintln!("bookmark: {}: no such directory", args.get(2).map(String::as_str).unwrap_or("."));
+                        return Some(1);
+                    }
+                };
+                println!("@{} → {}", name, dir);
+                bookmarks.insert(name.to_string(), dir);
+            }
+            Some("rm") | Some("remove") if valid => {
+                if bookmarks.remove(name).is_none() {
+                    eprintln!("bookmark: {}: no such bookmark", name);
+                    return Some(1);
+                }
+            }
+            Some("add") if !name.is_empty() => {
+                eprintln!("bookmark: {}: invalid bookmark name", name);
+                return Some(2);
+            }
+            _ => {
+                eprintln!("bookmark: usage: bookmark [list | add NAME [DIR] | go NAME | rm NAME]");
+                return Some(2);
+            }
+        }
+        if dry_run || ctx.vars.safe_mode {
+            println!("[{}] bookmark: ~/.hsh_bookmarks not written", if dry_run { "dry-run" } else { "safe-mode" });
+            return Some(0);
+        }
+        match crate::config::save_bookmarks(&bookmarks) {
+            Ok(_)  => Some(0),
+            Err(e) => { eprintln!("bookmark: {}", e); Some(1) }
+        }
+    }
+
+    // ── j — skok do najczęściej/ostatnio odwiedzanego katalogu (frecency) ────
+    // `j proj` → cd; `j -l [fraza]` — kandydaci z wynikami
+    else if trimmed == "j" || trimmed.starts_with("j ") {
+        let mut args = match crate::tokenize::split_args(&trimmed[1..]) {
+            Ok(a)  => a,
+            Err(e) => { eprintln!("j: {}", e); return Some(1); }
+        };
+        let list = args.first().map(|a| a == "-l").unwrap_or(false);
+        if list { args.remove(0); }
+        let found = crate::frecency::matches(&args);
+
+        if list || args.is_empty() {
+            for (score, path) in &found {
+                println!("{:>8.1}  {}", score, path);
+            }
+            return Some(0);
+        }
+        let Some((_, path)) = found.first() else {
+            eprintln!("j: no match for '{}'", args.join(" "));
+            return Some(1);
+        };
+        let cd = format!("cd {}", shlex::try_quote(path).unwrap_or_default());
+        handle_builtin(&cd, ctx, rl, prev_dir, dry_run, _heredoc_bodies)
+    }
+
+    // ── status — kod wyjścia poprzedniej komendy ─────────────────────────────
+    else if trimmed == "status" {
+        let code = ctx.vars.last_exit;
+        match crate::execute::signal_name(code) {
+            Some(sig) => println!("{} ({})", code, sig),
+            None      => println!("{}", code),
+        }
+        Some(0)
+    }
+
+    // ── clear / cls ──────────────────────────────────────────────────────────
+    // Ekran i bufor przewijania (\x1b[3J); `clear -x` zostawia przewijanie.
+    // Kursor w lewym górnym rogu — prompt rysuje się od pierwszej linii.
+    else if matches!(trimmed, "clear" | "cls" | "clear -x" | "cls -x") {
+        let keep_scrollback = trimmed.ends_with(" -x");
+        print!("\x1b[H\x1b[2J{}", if keep_scrollback { "" } else { "\x1b[3J" });
+        io::stdout().flush().ok();
+        Some(0)
+    }
+
+    // ── pwd ──────────────────────────────────────────────────────────────────
+    // Tylko same flagi — `pwd | …` i `pwd > plik` idą dalej do natywnej komendy
+    else if trimmed == "pwd"
+        || (trimmed.starts_with("pwd ")
+            && trimmed[4..].split_whitespace().all(|a| a.starts_with('-')))
+    {
+        let mut physical = false;
+        for arg in trimmed[3..].split_whitespace() {
+            match arg {
+                "-L" => physical = false,
+                "-P" => physical = true,
+                _    => {
+                    eprintln!("pwd: {}: invalid option", arg);
+                    eprintln!("pwd: usage: pwd [-L|-P]");
+                    return Some(2);
+                }
+            }
+        }
+        let cwd = match env::current_dir() {
+            Ok(p)  => p,
+            Err(e) => { eprintln!("pwd: {}", e); return Some(1); }
+        };
+        let dir = if physical {
+            match fs::canonicalize(&cwd) {
+                Ok(p)  => p,
+                Err(e) => { eprintln!("pwd: {}", e); return Some(1); }
+            }
+        } else {
+            // $PWD — logiczna ścieżka, o ile nadal wskazuje na bieżący katalog
+            ctx.vars.get("PWD").map(PathBuf::from).unwrap_or(cwd)
+        };
+        println!("{}", dir.display());
+        Some(0)
+    }
+
     // ── exit ─────────────────────────────────────────────────────────────────
     else if trimmed == "exit" || trimmed.starts_with("exit ") {
         let code: i32 = trimmed
@@ -82,90 +266,176 @@ pub fn handle_builtin(
             .unwrap_or("")
             .trim()
             .parse()
-            .unwrap_or(vars.last_exit);
+            .unwrap_or(ctx.vars.last_exit);
+        if ctx.vars.remember_dir {
+            crate::config::save_last_dir();
+        }
         std::process::exit(code);
     }
 
     // ── history ──────────────────────────────────────────────────────────────
     else if trimmed == "history" || trimmed.starts_with("history ") {
         let arg = trimmed.strip_prefix("history").unwrap_or("").trim();
+        // Wzorzec może być w cudzysłowach: history -g "git *"
+        let unquote = |s: &str| crate::tokenize::split(s).map(|w| w.join(" ")).unwrap_or_else(|| s.to_string());
+        if let Err(e @ crate::tokenize::SplitError::Operator(_)) = crate::tokenize::split_args(arg) {
+            eprintln!("history: {}", e);
+            return Some(1);
+        }
+        // Numery wpisów są stałe (pozycja w historii), więc działają z !N
         if arg.is_empty() {
-            shell_history.print_all();
+            ctx.history.print_all();
         } else if arg == "-c" {
-            // Wyczyść historię (nie modyfikujemy shell_history bezpośrednio)
-            eprintln!("hsh: history -c: wyczyść historię przez usunięcie pliku ~/.hsh-history");
-        } else {
-            let results = shell_history.fuzzy_search(arg);
+            ctx.history.clear();
+            let _ = rl.clear_history();
+        } else if let Ok(n) = arg.parse::<usize>() {
+            let skip = ctx.history.len().saturating_sub(n);
+            ctx.history.print_matching(|idx, _| idx >= skip);
+        } else if let Some(pat) = arg.strip_prefix("-g ") {
+            match glob::Pattern::new(&unquote(pat)) {
+                Ok(p)  => ctx.history.print_matching(|_, cmd| p.matches(cmd)),
+                Err(e) => { eprintln!("history: -g: {}", e); return Some(1); }
+            }
+        } else if let Some(query) = arg.strip_prefix("-f ") {
+            // Dawne zachowanie: wyszukiwanie rozmyte (bez numerów)
+            let results = ctx.history.fuzzy_search(&unquote(query));
             for entry in results.iter().take(20) {
                 println!(
                     "  \x1b[38;5;242m{}\x1b[0m  {}",
-                    entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                    entry.time_text(),
                     entry.command
                 );
             }
+        } else {
+            let needle = unquote(arg);
+            ctx.history.print_matching(|_, cmd| cmd.contains(&needle));
         }
         Some(0)
     }
 
     // ── which / type ─────────────────────────────────────────────────────────
+    // type: builtin → alias → PATH; which: tylko PATH. Kod 1, gdy któraś
+    // z nazw nie została znaleziona
     else if trimmed.starts_with("which ") || trimmed.starts_with("type ") {
-        let name = if trimmed.starts_with("which ") {
-            trimmed.strip_prefix("which ").unwrap_or("").trim()
-        } else {
-            trimmed.strip_prefix("type ").unwrap_or("").trim()
-        };
-        resolve_type(name, aliases, vars);
-        Some(0)
+        let path_only = trimmed.starts_with("which ");
+        let mut code = 0;
+        for name in trimmed.split_whitespace().skip(1) {
+            let found = if path_only {
+                match crate::helper::find_in_path(name) {
+                    Some(p) => { println!("{}", p.display()); true }
+                    None    => { eprintln!("which: no {} in PATH", name); false }
+                }
+            } else {
+                resolve_type(name, &ctx.aliases, &ctx.vars)
+            };
+            if !found { code = 1; }
+        }
+        Some(code)
     }
 
     // ── jobs ─────────────────────────────────────────────────────────────────
-    else if trimmed == "jobs" {
-        jobs.list();
+    else if trimmed == "jobs" || trimmed.starts_with("jobs ") {
+        // Najpierw zbierz zakończone — lista pokazuje tylko żywe zadania
+        ctx.jobs.check_finished();
+        let (mut long, mut pids_only) = (false, false);
+        let mut status = None;
+        let mut ids = Vec::new();
+        for arg in trimmed.split_whitespace().skip(1) {
+            match arg.strip_prefix('-') {
+                Some(flags) if !flags.is_empty() => for f in flags.chars() {
+                    match f {
+                        'l' => long = true,
+                        'p' => pids_only = true,
+                        'r' => status = Some(JobStatus::Running),
+                        's' => status = Some(JobStatus::Stopped),
+                        _ => {
+                            eprintln!("jobs: -{}: invalid option", f);
+                            eprintln!("jobs: usage: jobs [-lprs] [%job ...]");
+                            return Some(2);
+                        }
+                    }
+                },
+                _ => match ctx.jobs.resolve_spec(arg) {
+                    Ok(id)  => ids.push(id),
+                    Err(e) => { eprintln!("jobs: {}", e); return Some(1); }
+                },
+            }
+        }
+        if ids.is_empty() {
+            ids = ctx.jobs.ids(status);
+        }
+        if pids_only {
+            for pid in ids.iter().filter_map(|&id| ctx.jobs.pid_of(id)) { println!("{}", pid); }
+        } else {
+            ctx.jobs.list(&ids, long);
+        }
         Some(0)
     }
 
     // ── fg ───────────────────────────────────────────────────────────────────
     else if trimmed == "fg" || trimmed.starts_with("fg ") {
-        let id_str = trimmed.strip_prefix("fg").unwrap_or("").trim();
-        let id = if id_str.is_empty() { 1 } else { parse_job_id(id_str).unwrap_or(1) };
-        if let Some(pid) = jobs.fg(id) {
-            use nix::sys::wait::waitpid;
-            use nix::unistd::Pid;
-            let _ = waitpid(Pid::from_raw(pid as i32), None);
-            jobs.mark_done(id);
-            Some(0)
-        } else {
-            eprintln!("fg: job {} not found", id);
-            Some(1)
+        let spec = trimmed.strip_prefix("fg").unwrap_or("").trim();
+        let id = match ctx.jobs.resolve_spec(spec) {
+            Ok(id) => id,
+            Err(e) => { eprintln!("fg: {}", e); return Some(1); }
+        };
+        // Jak bash: pokaż wznawianą komendę
+        if let Some(job) = ctx.jobs.get(id) { println!("{}", job.command); }
+        match ctx.jobs.wait_job(id, true) {
+            Some(code) => { ctx.vars.last_exit = code; Some(code) }
+            None => {
+                eprintln!("fg: %{}: no such job", id);
+                Some(1)
+            }
         }
     }
 
     // ── bg ───────────────────────────────────────────────────────────────────
     else if trimmed == "bg" || trimmed.starts_with("bg ") {
-        let id_str = trimmed.strip_prefix("bg").unwrap_or("").trim();
-        let id = if id_str.is_empty() { 1 } else { parse_job_id(id_str).unwrap_or(1) };
-        if jobs.bg(id) {
-            println!("[{}] continued", id);
+        let spec = trimmed.strip_prefix("bg").unwrap_or("").trim();
+        let id = match ctx.jobs.resolve_spec(spec) {
+            Ok(id) => id,
+            Err(e) => { eprintln!("bg: {}", e); return Some(1); }
+        };
+        if ctx.jobs.get(id).map(|j| j.status == JobStatus::Running).unwrap_or(false) {
+            eprintln!("bg: job {} already in background", id);
+            return Some(0);
+        }
+        if ctx.jobs.bg(id) {
+            let cmd = ctx.jobs.get(id).map(|j| j.command.clone()).unwrap_or_default();
+            println!("[{}]+ {} &", id, cmd);
             Some(0)
         } else {
-            eprintln!("bg: job {} not found", id);
+            eprintln!("bg: %{}: no such job", id);
             Some(1)
         }
     }
 
     // ── stop ─────────────────────────────────────────────────────────────────
     else if trimmed == "stop" || trimmed.starts_with("stop ") {
-        let id_str = trimmed.strip_prefix("stop").unwrap_or("").trim();
-        let id = if id_str.is_empty() { 1 } else { parse_job_id(id_str).unwrap_or(1) };
-        if jobs.stop(id) {
+        let spec = trimmed.strip_prefix("stop").unwrap_or("").trim();
+        let id = match ctx.jobs.resolve_spec(spec) {
+            Ok(id) => id,
+            Err(e) => { eprintln!("stop: {}", e); return Some(1); }
+        };
+        if ctx.jobs.stop(id) {
             println!("[{}] stopped", id);
             Some(0)
         } else {
-            eprintln!("stop: job {} not found", id);
+            eprintln!("stop: %{}: no such job", id);
             Some(1)
         }
     }
 
+    // ── disown ───────────────────────────────────────────────────────────────
+    else if trimmed == "disown" || trimmed.starts_with("disown ") {
+        let spec = trimmed.strip_prefix("disown").unwrap_or("").trim();
+        match ctx.jobs.resolve_spec(spec) {
+            Ok(id) => { ctx.jobs.remove(id); Some(0) }
+            Err(e) => { eprintln!("disown: {}", e); Some(1) }
+        }
+    }
+
     // ── kill ─────────────────────────────────────────────────────────────────
     else if trimmed.starts_with("kill ") {
         let args: Vec<&str> = trimmed
@@ -213,59 +483,97 @@ pub fn handle_builtin(
             }
         }
 
-        let id = parse_job_id(id_str).unwrap_or(1);
-        if jobs.send_signal(id, signal) {
+        let id = match ctx.jobs.resolve_spec(id_str) {
+            Ok(id) => id,
+            Err(e) => { eprintln!("kill: {}", e); return Some(1); }
+        };
+        if ctx.jobs.send_signal(id, signal) {
             Some(0)
         } else {
-            eprintln!("kill: job {} not found", id);
+            eprintln!("kill: %{}: no such job", id);
             Some(1)
         }
     }
 
     // ── wait ─────────────────────────────────────────────────────────────────
     else if trimmed == "wait" || trimmed.starts_with("wait ") {
-        let id_str = trimmed.strip_prefix("wait").unwrap_or("").trim();
-        if id_str.is_empty() {
-            // wait bez argumentu — czekaj na wszystkie dzieci
-            use nix::sys::wait::{waitpid, WaitPidFlag};
-            use nix::unistd::Pid;
-            loop {
-                match waitpid(Pid::from_raw(-1), Some(WaitPidFlag::WNOHANG)) {
-                    Ok(nix::sys::wait::WaitStatus::StillAlive) | Err(_) => break,
-                    _ => {}
-                }
-            }
+        let specs: Vec<&str> = trimmed.split_whitespace().skip(1).collect();
+        if specs.is_empty() {
+            // wait bez argumentu — czekaj na wszystkie zadania w tle
+            ctx.jobs.wait_all();
             return Some(0);
         }
-        let id = parse_job_id(id_str).unwrap_or(1);
-        if let Some(pid) = jobs.fg(id) {
-            use nix::sys::wait::waitpid;
-            use nix::unistd::Pid;
-            let status = waitpid(Pid::from_raw(pid as i32), None);
-            jobs.mark_done(id);
-            match status {
-                Ok(nix::sys::wait::WaitStatus::Exited(_, code)) => Some(code),
-                Ok(nix::sys::wait::WaitStatus::Signaled(_, sig, _)) => {
-                    eprintln!("wait: job terminated by signal {}", sig);
-                    Some(128 + (sig as i32))
+        // wait %1 %2 … — kod wyjścia ostatniego, jak w bash
+        let mut last = 0;
+        for spec in specs {
+            let id = match ctx.jobs.resolve_spec(spec) {
+                Ok(id) => id,
+                Err(e) => { eprintln!("wait: {}", e); last = 127; continue; }
+            };
+            last = match ctx.jobs.wait_job(id, false) {
+                Some(code) => {
+                    if code > 128 {
+                        eprintln!("wait: job terminated by signal {}", code - 128);
+                    }
+                    code
+                }
+                None => {
+                    eprintln!("wait: %{}: no such job", id);
+                    127
+                }
+            };
+        }
+        Some(last)
+    }
+
+    // ── tz ───────────────────────────────────────────────────────────────────
+    else if trimmed == "tz" || trimmed.starts_with("tz ") {
+        let arg = trimmed.strip_prefix("tz").unwrap_or("").trim();
+        match arg {
+            "" => {
+                let name = ctx.vars.prompt_tz.as_deref().unwrap_or("local");
+                let off  = ctx.vars.prompt_tz.as_deref()
+                    .and_then(crate::prompt::zone_offset)
+                    .unwrap_or_else(|| *chrono::Local::now().offset());
+                let now  = chrono::Utc::now().with_timezone(&off);
+                println!("{} (UTC{}) {}", name, now.format("%:z"), now.format("%H:%M:%S"));
+                Some(0)
+            }
+            "-" | "local" => { ctx.vars.prompt_tz = None; Some(0) }
+            name => {
+                if crate::prompt::zone_offset(name).is_some() {
+                    ctx.vars.prompt_tz = Some(name.to_string());
+                    Some(0)
+                } else {
+                    eprintln!("tz: {}: unknown time zone", name);
+                    Some(1)
                 }
-                _ => Some(1),
             }
-        } else {
-            eprintln!("wait: job {} not found", id);
-            Some(127)
         }
     }
 
+    // ── times ────────────────────────────────────────────────────────────────
+    else if trimmed == "times" {
+        let (self_user, self_sys)   = rusage_times(libc::RUSAGE_SELF);
+        let (child_user, child_sys) = rusage_times(libc::RUSAGE_CHILDREN);
+        println!("{} {}", format_cpu_time(self_user), format_cpu_time(self_sys));
+        println!("{} {}", format_cpu_time(child_user), format_cpu_time(child_sys));
+        Some(0)
+    }
+
     // ── export ───────────────────────────────────────────────────────────────
     else if trimmed == "export" || trimmed.starts_with("export ") {
         let export_str = trimmed.strip_prefix("export").unwrap_or("").trim();
         if export_str.is_empty() {
-            // export bez argumentów = lista
+            // export bez argumentów = lista NAME=value (POSIX: jak export -p)
             let mut env_vars: Vec<(String, String)> = env::vars().collect();
             env_vars.sort_by(|a, b| a.0.cmp(&b.0));
             for (k, v) in env_vars {
-                println!("export {}=\"{}\"", k, v);
+                if ctx.vars.posix {
+                    println!("export {}={}", k, quote_alias(&v));
+                } else {
+                    println!("{}={}", k, v);
+                }
             }
             return Some(0);
         }
@@ -277,25 +585,33 @@ pub fn handle_builtin(
             }
             return Some(0);
         }
-        // Obsługa wielu zmiennych: export A=1 B=2
-        for part in export_str.split_whitespace() {
-            if let Some(eq_pos) = part.find('=') {
-                let name  = part[..eq_pos].trim();
-                let value = part[eq_pos + 1..].trim().trim_matches('"').trim_matches('\'');
-                if dry_run {
-                    println!("[dry-run] export {}={}", name, value);
-                } else {
+        // Obsługa wielu zmiennych: export A=1 B="x y"
+        let parts = crate::tokenize::split(export_str)
+            .unwrap_or_else(|| export_str.split_whitespace().map(String::from).collect());
+        let mut code = 0;
+        for part in &parts {
+            let (name, value) = match part.split_once('=') {
+                Some((n, v)) => (n, Some(v)),
+                None         => (part.as_str(), None),
+            };
+            if !is_valid_name(name) {
+                eprintln!("export: `{}': not a valid identifier", part);
+                code = 1;
+                continue;
+            }
+            match value {
+                Some(value) if dry_run => println!("[dry-run] export {}={}", name, value),
+                Some(value) => {
                     env::set_var(name, value);
-                    vars.set(name, value);
+                    ctx.vars.set(name, value);
                 }
-            } else {
                 // export VARNAME — eksportuj istniejącą zmienną
-                if let Some(val) = vars.get(part) {
-                    env::set_var(part, &val);
-                }
+                None => if let Some(val) = ctx.vars.local.get(name).cloned() {
+                    if !dry_run { env::set_var(name, val); }
+                },
             }
         }
-        Some(0)
+        Some(code)
     }
 
     // ── local ─────────────────────────────────────────────────────────────────
@@ -305,11 +621,11 @@ pub fn handle_builtin(
             if let Some(eq_pos) = part.find('=') {
                 let name  = &part[..eq_pos];
                 let value = &part[eq_pos + 1..];
-                vars.set(name, value);
+                ctx.vars.set(name, value);
             } else {
                 // local bez wartości — ustaw pusty string jeśli nie istnieje
-                if vars.get(part).is_none() {
-                    vars.set(part, "");
+                if ctx.vars.get(part).is_none() {
+                    ctx.vars.set(part, "");
                 }
             }
         }
@@ -323,11 +639,11 @@ pub fn handle_builtin(
             if let Some(eq_pos) = part.find('=') {
                 let name  = &part[..eq_pos];
                 let value = &part[eq_pos + 1..];
-                vars.set(name, value);
+                ctx.vars.set(name, value);
                 env::set_var(name, value);
             } else {
                 // readonly istniejącej zmiennej
-                if let Some(val) = vars.get(part) {
+                if let Some(val) = ctx.vars.get(part) {
                     env::set_var(part, &val);
                 }
             }
@@ -378,12 +694,12 @@ pub fn handle_builtin(
             if let Some(eq_pos) = part.find('=') {
                 let name  = &part[..eq_pos];
                 let value = &part[eq_pos + 1..].trim_matches('"').trim_matches('\'');
-                vars.set(name, value);
+                ctx.vars.set(name, value);
                 if flag_export {
                     env::set_var(name, value);
                 }
             } else if flag_export {
-                if let Some(val) = vars.get(part) {
+                if let Some(val) = ctx.vars.get(part) {
                     env::set_var(part, &val);
                 }
             }
@@ -392,10 +708,12 @@ pub fn handle_builtin(
     }
 
     // ── unset ─────────────────────────────────────────────────────────────────
-    else if trimmed.starts_with("unset ") {
-        let names = trimmed.strip_prefix("unset ").unwrap_or("").trim();
-        for name in names.split_whitespace() {
-            vars.local.remove(name);
+    // Nieistniejąca zmienna to no-op (kod 0), jak w bash
+    else if trimmed == "unset" || trimmed.starts_with("unset ") {
+        let names = trimmed.strip_prefix("unset").unwrap_or("").trim();
+        for name in names.split_whitespace().filter(|n| !n.starts_with('-')) {
+            if dry_run { println!("[dry-run] unset {}", name); continue; }
+            ctx.vars.local.remove(name);
             env::remove_var(name);
         }
         Some(0)
@@ -403,57 +721,74 @@ pub fn handle_builtin(
 
     // ── alias ─────────────────────────────────────────────────────────────────
     else if trimmed == "alias" {
-        let mut sorted: Vec<(&String, &String)> = aliases.iter().collect();
+        let mut sorted: Vec<(&String, &String)> = ctx.aliases.iter().collect();
         sorted.sort_by_key(|(k, _)| k.as_str());
         for (k, v) in sorted {
-            println!("alias {}='{}'", k, v);
+            println!("alias {}={}", k, quote_alias(v));
         }
         Some(0)
     }
     else if trimmed.starts_with("alias ") {
-        // alias name='value' — tylko wypisz co by było (nie możemy modyfikować)
+        // alias ll='ls -la' gs="git status" — definicja; alias name — wypisz
         let rest = trimmed.strip_prefix("alias ").unwrap_or("").trim();
-        if let Some(eq_pos) = rest.find('=') {
-            let name = &rest[..eq_pos];
-            eprintln!(
-                "hsh: alias {} ustawiony tylko w .hshrc (restart lub source ~/.hshrc)",
-                name
-            );
-        } else {
-            // alias name — wypisz wartość
-            if let Some(val) = aliases.get(rest) {
-                println!("alias {}='{}'", rest, val);
-            } else {
-                eprintln!("alias: {} nie zdefiniowany", rest);
-                return Some(1);
+        let words = match crate::tokenize::split_args(rest) {
+            Ok(w)  => w,
+            Err(e) => { eprintln!("alias: {}", e); return Some(1); }
+        };
+        let mut code = 0;
+        for word in words {
+            match word.split_once('=') {
+                Some((name, value)) if !name.is_empty() => {
+                    if dry_run {
+                        println!("[dry-run] alias {}={}", name, quote_alias(value));
+                    } else {
+                        ctx.aliases.insert(name.to_string(), value.to_string());
+                        if ctx.vars.safe_mode {
+                            println!("[safe-mode] alias {}: this session only, ~/.hshrc not written", name);
+                        }
+                    }
+                }
+                _ => match ctx.aliases.get(&word) {
+                    Some(val) => println!("alias {}={}", word, quote_alias(val)),
+                    None => {
+                        eprintln!("alias: {} nie zdefiniowany", word);
+                        code = 1;
+                    }
+                },
             }
         }
-        Some(0)
+        Some(code)
     }
 
     // ── unalias ───────────────────────────────────────────────────────────────
-    else if trimmed.starts_with("unalias ") {
-        let name = trimmed.strip_prefix("unalias ").unwrap_or("").trim();
-        if name.is_empty() {
-            eprintln!("unalias: usage: unalias name");
-            return Some(1);
+    else if trimmed == "unalias" || trimmed.starts_with("unalias ") {
+        let names: Vec<&str> = trimmed.strip_prefix("unalias").unwrap_or("").split_whitespace().collect();
+        if names.is_empty() {
+            eprintln!("unalias: usage: unalias [-a] name [name ...]");
+            return Some(2);
         }
-        if aliases.contains_key(name) {
-            eprintln!(
-                "hsh: unalias {} — usuń wpis z ~/.hshrc [aliases] i wykonaj: source ~/.hshrc",
-                name
-            );
-        } else {
-            eprintln!("unalias: {}: nie znaleziono", name);
-            return Some(1);
+        if names.contains(&"-a") {
+            if dry_run { println!("[dry-run] unalias -a"); } else { ctx.aliases.clear(); }
+            return Some(0);
         }
-        Some(0)
+        let mut code = 0;
+        for name in names {
+            if !ctx.aliases.contains_key(name) {
+                eprintln!("unalias: {}: nie znaleziono", name);
+                code = 1;
+            } else if dry_run {
+                println!("[dry-run] unalias {}", name);
+            } else {
+                ctx.aliases.remove(name);
+            }
+        }
+        Some(code)
     }
 
     // ── set ───────────────────────────────────────────────────────────────────
     else if trimmed == "set" {
         // set bez argumentów — wypisz wszystkie zmienne
-        let mut all: Vec<(String, String)> = vars.all().into_iter().collect();
+        let mut all: Vec<(String, String)> = ctx.vars.all().into_iter().collect();
         all.sort_by(|a, b| a.0.cmp(&b.0));
         for (k, v) in all {
             println!("{}={}", k, v);
@@ -466,45 +801,160 @@ pub fn handle_builtin(
             .unwrap_or("")
             .split_whitespace()
             .collect();
-        for arg in args {
+        let mut args = args.into_iter();
+        while let Some(arg) = args.next() {
             match arg {
-                "-e" | "--errexit"  => vars.set_option("e", true),
-                "+e"                => vars.set_option("e", false),
-                "-x" | "--xtrace"  => vars.set_option("x", true),
-                "+x"                => vars.set_option("x", false),
-                "-u" | "--nounset" => vars.set_option("u", true),
-                "+u"                => vars.set_option("u", false),
-                "-o" | "+o"        => {} // obsługa -o option pominięta
-                _ => eprintln!("set: unknown option {}", arg),
+                "--errexit"        => ctx.vars.set_option("e", true),
+                "--xtrace"         => ctx.vars.set_option("x", true),
+                "--verbose"        => ctx.vars.set_option("v", true),
+                "--nounset"        => ctx.vars.set_option("u", true),
+                "-o" | "+o"        => match args.next() {
+                    Some(name @ ("errexit" | "xtrace" | "verbose" | "nounset" | "posix")) => {
+                        ctx.vars.set_option(name, arg == "-o");
+                    }
+                    Some(name) => eprintln!("set: {}: invalid option name", name),
+                    None => for (name, on) in [
+                        ("errexit", ctx.vars.errexit), ("nounset", ctx.vars.nounset), ("posix", ctx.vars.posix),
+                        ("verbose", ctx.vars.verbose), ("xtrace", ctx.vars.xtrace),
+                    ] {
+                        println!("{:<15} {}", name, if on { "on" } else { "off" });
+                    },
+                },
+                // -e, +x, łączone: -ex, +eu
+                _ if arg.len() > 1
+                    && (arg.starts_with('-') || arg.starts_with('+'))
+                    && arg[1..].chars().all(|c| "exvu".contains(c)) =>
+                {
+                    for c in arg[1..].chars() {
+                        ctx.vars.set_option(&c.to_string(), arg.starts_with('-'));
+                    }
+                }
+                _ => {
+                    eprintln!("set: unknown option {}", arg);
+                    return Some(2);
+                }
+            }
+        }
+        Some(0)
+    }
+
+    // ── path ──────────────────────────────────────────────────────────────────
+    else if trimmed == "path" || trimmed.starts_with("path ") {
+        let args = match crate::tokenize::split_args(&trimmed[4..]) {
+            Ok(a)  => a,
+            Err(e) => { eprintln!("path: {}", e); return Some(1); }
+        };
+        let mut entries: Vec<String> = env::var("PATH")
+            .unwrap_or_default()
+            .split(':')
+            .filter(|d| !d.is_empty())
+            .map(String::from)
+            .collect();
+
+        match args.first().map(String::as_str) {
+            None => {
+                for dir in &entries {
+                    if Path::new(dir).is_dir() {
+                        println!("  {}", dir);
+                    } else {
+                        println!("\x1b[31m! {}\x1b[0m", dir);
+                    }
+                }
+                return Some(0);
+            }
+            Some("add") => {
+                let append = args.get(1).map(|a| a == "-a").unwrap_or(false);
+                let dirs: Vec<String> = args[if append { 2 } else { 1 }..]
+                    .iter()
+                    .map(|d| normalize_path_entry(&expand_tilde(d)))
+                    .collect();
+                if dirs.is_empty() {
+                    eprintln!("path: usage: path add [-a] DIR...");
+                    return Some(2);
+                }
+                for dir in &dirs {
+                    if !Path::new(dir).is_dir() {
+                        eprintln!("path: warning: {}: no such directory", dir);
+                    }
+                }
+                // Dodany katalog przenosimy na początek/koniec zamiast dublować
+                entries.retain(|e| !dirs.contains(&normalize_path_entry(e)));
+                if append {
+                    entries.extend(dirs);
+                } else {
+                    entries.splice(0..0, dirs);
+                }
+            }
+            Some("remove") | Some("rm") => {
+                let dirs: Vec<String> = args[1..]
+                    .iter()
+                    .map(|d| normalize_path_entry(&expand_tilde(d)))
+                    .collect();
+                if dirs.is_empty() {
+                    eprintln!("path: usage: path remove DIR...");
+                    return Some(2);
+                }
+                let before = entries.len();
+                entries.retain(|e| !dirs.contains(&normalize_path_entry(e)));
+                if entries.len() == before {
+                    eprintln!("path: {}: not in PATH", dirs.join(" "));
+                    return Some(1);
+                }
+            }
+            Some(other) => {
+                eprintln!("path: unknown subcommand: {}", other);
+                eprintln!("path: usage: path [add [-a] DIR | remove DIR]");
+                return Some(2);
             }
         }
+
+        dedup_path(&mut entries);
+        let new_path = entries.join(":");
+        if dry_run {
+            println!("[dry-run] export PATH={}", new_path);
+            return Some(0);
+        }
+        env::set_var("PATH", new_path);
+        // Cache komend dla podświetlania/uzupełniania; PathCache sam wykryje
+        // zmianę hasha $PATH przed następnym promptem
+        if let Some(helper) = rl.helper_mut() {
+            helper.rehash();
+        }
+        Some(0)
+    }
+
+    // ── rehash — przeskanuj PATH od nowa (podświetlanie i uzupełnianie) ───────
+    else if trimmed == "rehash" {
+        if let Some(helper) = rl.helper_mut() {
+            helper.rehash();
+        }
         Some(0)
     }
 
     // ── pushd ─────────────────────────────────────────────────────────────────
     else if trimmed == "pushd" || trimmed.starts_with("pushd ") {
         let dir_str = trimmed.strip_prefix("pushd").unwrap_or("").trim();
+        let current = env::current_dir().unwrap_or_default();
+        // pushd bez argumentu — zamień dwa górne wpisy (jak bash)
         let target = if dir_str.is_empty() {
-            env::var("HOME").unwrap_or_else(|_| "/".to_string())
+            match ctx.vars.dir_stack.pop() {
+                Some(top) => top,
+                None => {
+                    eprintln!("pushd: no other directory");
+                    return Some(1);
+                }
+            }
         } else {
             expand_tilde(dir_str)
         };
-        let current = env::current_dir()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-        vars.dir_stack.push(current.clone());
         if env::set_current_dir(&target).is_ok() {
-            vars.set_pwd();
-            // Wypisz stack
-            print!("{}", target);
-            for d in vars.dir_stack.iter().rev() {
-                print!(" {}", d);
-            }
-            println!();
+            ctx.vars.dir_stack.push(current.to_string_lossy().to_string());
+            *prev_dir = Some(current);
+            ctx.vars.set_pwd();
+            print_dir_stack(&ctx.vars);
             Some(0)
         } else {
-            vars.dir_stack.pop();
+            if dir_str.is_empty() { ctx.vars.dir_stack.push(target.clone()); }
             eprintln!("pushd: {}: no such directory", target);
             Some(1)
         }
@@ -512,17 +962,17 @@ pub fn handle_builtin(
 
     // ── popd ──────────────────────────────────────────────────────────────────
     else if trimmed == "popd" {
-        if let Some(prev) = vars.dir_stack.pop() {
+        if let Some(prev) = ctx.vars.dir_stack.pop() {
+            let current = env::current_dir().unwrap_or_default();
             if env::set_current_dir(&prev).is_ok() {
-                vars.set_pwd();
-                print!("{}", prev);
-                for d in vars.dir_stack.iter().rev() {
-                    print!(" {}", d);
-                }
-                println!();
+                *prev_dir = Some(current);
+                ctx.vars.set_pwd();
+                print_dir_stack(&ctx.vars);
                 Some(0)
             } else {
-                eprintln!("popd: cannot change to {}", prev);
+                // Katalog zniknął — zostaw stos bez zmian
+                ctx.vars.dir_stack.push(prev.clone());
+                eprintln!("popd: {}: no such directory", prev);
                 Some(1)
             }
         } else {
@@ -533,15 +983,22 @@ pub fn handle_builtin(
 
     // ── dirs ──────────────────────────────────────────────────────────────────
     else if trimmed == "dirs" || trimmed.starts_with("dirs ") {
-        let current = env::current_dir()
-            .unwrap_or_default()
-            .to_string_lossy()
-            .to_string();
-        print!("{}", current);
-        for d in vars.dir_stack.iter().rev() {
-            print!(" {}", d);
+        match trimmed[4..].trim() {
+            ""   => print_dir_stack(&ctx.vars),
+            "-c" => ctx.vars.dir_stack.clear(),
+            "-v" => {
+                let current = env::current_dir().unwrap_or_default();
+                println!(" 0  {}", current.display());
+                for (i, d) in ctx.vars.dir_stack.iter().rev().enumerate() {
+                    println!("{:>2}  {}", i + 1, d);
+                }
+            }
+            other => {
+                eprintln!("dirs: {}: invalid option", other);
+                eprintln!("dirs: usage: dirs [-c|-v]");
+                return Some(2);
+            }
         }
-        println!();
         Some(0)
     }
 
@@ -605,13 +1062,13 @@ pub fn handle_builtin(
                 let line = line.trim_end_matches('\n').trim_end_matches('\r');
                 if varnames.is_empty() {
                     // Czytaj do REPLY
-                    vars.set("REPLY", line);
+                    ctx.vars.set("REPLY", line);
                 } else if varnames.len() == 1 {
-                    vars.set(varnames[0], line);
+                    ctx.vars.set(varnames[0], line);
                     env::set_var(varnames[0], line);
                 } else {
                     // Podziel wg IFS (domyślnie whitespace)
-                    let ifs = vars.get("IFS").unwrap_or_else(|| " \t\n".to_string());
+                    let ifs = ctx.vars.get("IFS").unwrap_or_else(|| " \t\n".to_string());
                     let sep: Vec<char> = ifs.chars().collect();
                     let mut parts: Vec<&str> = line
                         .splitn(varnames.len(), |c| sep.contains(&c))
@@ -621,7 +1078,7 @@ pub fn handle_builtin(
                         parts.push("");
                     }
                     for (name, val) in varnames.iter().zip(parts.iter()) {
-                        vars.set(name, val.trim());
+                        ctx.vars.set(name, val.trim());
                         env::set_var(name, val.trim());
                     }
                 }
@@ -676,7 +1133,18 @@ pub fn handle_builtin(
         let hshrc = format!("{}/.hshrc", home);
         if Path::new(&hshrc).exists() {
             println!("hsh: przeładowuję {} ...", hshrc);
-            println!("hsh: użyj 'source ~/.hshrc' żeby załadować aliasy do bieżącej sesji");
+            // Aliasy z pliku zastępują zdefiniowane w sesji
+            let config = crate::config::load_shell_config(false);
+            ctx.aliases = crate::config::get_aliases(&config);
+            println!("hsh: załadowano {} aliasów", ctx.aliases.len());
+            ctx.vars.danger_patterns = crate::config::get_dangerous_patterns(&config);
+            ctx.vars.confirm_dangerous = crate::config::get_confirm_dangerous(&config);
+            ctx.vars.sudo = crate::config::get_sudo_options(&config);
+            if let Some(helper) = rl.helper_mut() {
+                helper.danger_patterns = ctx.vars.danger_patterns.clone();
+                helper.theme = crate::theme::Theme::load()
+                    .with_overrides(&crate::config::get_highlight_colors(&config));
+            }
         } else {
             eprintln!("hsh: plik {} nie istnieje", hshrc);
             return Some(1);
@@ -691,65 +1159,183 @@ pub fn handle_builtin(
 
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn resolve_type(name: &str, aliases: &HashMap<String, String>, vars: &ShellVars) {
-    let builtins = [
-        "cd", "exit", "history", "which", "type", "jobs", "fg", "bg", "stop",
-        "kill", "wait", "export", "alias", "unalias", "set", "pushd", "popd",
-        "dirs", "source", "hsh-help", "help", "true", "false", ":", "read",
-        "local", "readonly", "declare", "typeset", "unset", "exec", "eval",
-        "hsh-version", "hsh-reload",
-    ];
+/// Czas użytkownika i systemowy (w sekundach) z getrusage.
+fn rusage_times(who: libc::c_int) -> (f64, f64) {
+    let mut usage: libc::rusage = unsafe { std::mem::zeroed() };
+    if unsafe { libc::getrusage(who, &mut usage) } != 0 {
+        return (0.0, 0.0);
+    }
+    let secs = |tv: libc::timeval| tv.tv_sec as f64 + tv.tv_usec as f64 / 1_000_000.0;
+    (secs(usage.ru_utime), secs(usage.ru_stime))
+}
+
+/// Format jak w bash: 0m1.234s
+fn format_cpu_time(secs: f64) -> String {
+    let minutes = (secs / 60.0).floor();
+    format!("{}m{:.3}s", minutes as u64, secs - minutes * 60.0)
+}
 
-    if builtins.contains(&name) {
+/// Katalog `dir` w kolejnych wpisach $CDPATH (jak bash). Pomijane: ścieżki
+/// bezwzględne, `~…`, `./…`, `../…` i cele istniejące w bieżącym katalogu.
+fn search_cdpath(dir: &str) -> Option<String> {
+    if dir.starts_with('/') || dir.starts_with('~') || dir == "." || dir == ".."
+        || dir.starts_with("./") || dir.starts_with("../") || Path::new(dir).is_dir()
+    {
+        return None;
+    }
+    let cdpath = env::var("CDPATH").ok()?;
+    cdpath
+        .split(':')
+        .filter(|base| !base.is_empty())
+        .map(|base| Path::new(&expand_tilde(base)).join(dir))
+        .find(|p| p.is_dir())
+        .map(|p| p.to_string_lossy().to_string())
+}
+
+/// Zapytaj, czy utworzyć brakujący katalog dla cd. Zwraca true jeśli utworzono.
+fn offer_mkdir(dir: &str) -> bool {
+    if Path::new(dir).exists() { return false; }
+    eprint!("\x1b[1;33mcd: {} does not exist. Create directory? [y/N] \x1b[0m", dir);
+    io::stderr().flush().ok();
+    let mut ans = String::new();
+    io::stdin().read_line(&mut ans).ok();
+    if !ans.trim().eq_ignore_ascii_case("y") { return false; }
+    match fs::create_dir_all(dir) {
+        Ok(_)  => true,
+        Err(e) => { eprintln!("cd: mkdir {}: {}", dir, e); false }
+    }
+}
+
+/// Wartość aliasu w pojedynczych cudzysłowach, `'` jako `'\''` (jak bash)
+fn quote_alias(value: &str) -> String {
+    format!("'{}'", value.replace('\'', "'\\''"))
+}
+
+/// Opis błędu I/O bez sufiksu ` (os error N)` — jak strerror w sh
+pub fn io_error_text(e: &io::Error) -> String {
+    let text = e.to_string();
+    match text.find(" (os error") {
+        Some(pos) => text[..pos].to_string(),
+        None      => text,
+    }
+}
+
+/// Poprawna nazwa zmiennej: litera lub `_`, dalej litery, cyfry, `_`
+fn is_valid_name(name: &str) -> bool {
+    let mut chars = name.chars();
+    matches!(chars.next(), Some(c) if c.is_ascii_alphabetic() || c == '_')
+        && chars.all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
+/// Bieżący katalog i stos pushd — od wierzchołka, jak `dirs` w bash
+fn print_dir_stack(vars: &ShellVars) {
+    let current = env::current_dir().unwrap_or_default();
+    print!("{}", current.display());
+    for d in vars.dir_stack.iter().rev() {
+        print!(" {}", d);
+    }
+    println!();
+}
+
+/// Wszystkie builtiny: (nazwa, działa na stanie powłoki). Jedna lista dla
+/// `type`, uzupełniania komend i blokady builtinów stanu w potoku — w osobnym
+/// procesie ich efekt by przepadł.
+pub const BUILTINS: &[(&str, bool)] = &[
+    ("cd", true), ("pushd", true), ("popd", true), ("dirs", false), ("pwd", false),
+    ("exit", true), ("export", true), ("alias", true), ("unalias", true),
+    ("set", true), ("unset", true), ("source", true), (".", true),
+    ("local", true), ("readonly", true), ("declare", true), ("typeset", true),
+    ("read", false), ("exec", true), ("eval", false),
+    ("jobs", false), ("fg", true), ("bg", true), ("stop", false), ("kill", false),
+    ("wait", true), ("disown", true), ("times", false), ("tz", true),
+    ("history", false), ("fc", true), ("repeat", true), ("status", false),
+    ("which", false), ("type", false), ("test", false), ("true", false),
+    ("false", false), (":", false), ("path", false), ("rehash", true),
+    ("bookmark", true), ("j", true), ("clear", false), ("cls", false),
+    ("help", false), ("hsh-help", false), ("hsh-version", false),
+    ("hsh-reload", true), ("hsh-settings", false), ("hsh-docs", false),
+];
+
+pub fn is_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|(n, _)| *n == name)
+}
+
+/// Builtin zmieniający stan samej powłoki (cd, export, alias…)
+pub fn is_shell_state_builtin(name: &str) -> bool {
+    BUILTINS.iter().any(|&(n, state)| state && n == name)
+}
+
+/// Wpis PATH bez końcowego `/` (poza samym `/`), żeby `/bin` == `/bin/`
+fn normalize_path_entry(dir: &str) -> String {
+    let trimmed = dir.trim_end_matches('/');
+    if trimmed.is_empty() { dir.to_string() } else { trimmed.to_string() }
+}
+
+/// Usuń powtórzone wpisy PATH — zostaje pierwsze wystąpienie (to wygrywa
+/// przy wyszukiwaniu komendy)
+fn dedup_path(entries: &mut Vec<String>) {
+    let mut seen = std::collections::HashSet::new();
+    entries.retain(|e| seen.insert(normalize_path_entry(e)));
+}
+
+fn resolve_type(name: &str, aliases: &HashMap<String, String>, vars: &ShellVars) -> bool {
+    if is_builtin(name) {
         println!("{} is a shell builtin", name);
-        return;
+        return true;
     }
 
     if let Some(val) = aliases.get(name) {
         println!("{} is aliased to '{}'", name, val);
-        return;
+        return true;
     }
 
     // Sprawdź funkcje (vars nie przechowuje funkcji — sprawdź przez FunctionTable)
     // Tutaj nie mamy dostępu do FunctionTable, więc pomijamy
 
-    if let Ok(path_var) = env::var("PATH") {
-        for dir in path_var.split(':') {
-            let full = Path::new(dir).join(name);
-            if full.exists() {
-                println!("{} is {}", name, full.display());
-                return;
-            }
-        }
+    if let Some(full) = crate::helper::find_in_path(name) {
+        println!("{} is {}", name, full.display());
+        return true;
     }
 
     // Sprawdź natywne komendy hsh
     let native_cmds = [
-        "echo", "pwd", "ls", "cat", "mkdir", "rm", "cp", "mv", "touch",
+        "echo", "ls", "cat", "mkdir", "rm", "cp", "mv", "touch",
         "env", "grep", "head", "tail", "wc", "uname", "find", "xargs", "printf",
     ];
     if native_cmds.contains(&name) {
         println!("{} is a native hsh command", name);
-        return;
+        return true;
     }
 
-    println!("{}: not found", name);
+    eprintln!("type: {}: not found", name);
+    false
 }
 
 fn print_help() {
     println!("\x1b[1;32mhsh\x1b[0m — HackerOS Shell v0.4.0");
     println!();
     println!("\x1b[1mBuilt-in commands:\x1b[0m");
-    println!("  cd [dir|-]           Change directory (- goes back)");
+    println!("  cd [-L|-P] [dir|-]   Change directory (- goes back; -P: physical ..; @name: bookmark)");
+    println!("  pwd [-L|-P]          Print working directory (-P: resolve symlinks)");
     println!("  exit [code]          Exit shell");
     println!("  history [query]      Show history; with query: fuzzy search");
-    println!("  which/type NAME      Show if alias, builtin, or binary");
-    println!("  jobs                 List background jobs");
-    println!("  fg [id]              Bring job to foreground");
-    println!("  bg [id]              Resume job in background");
-    println!("  stop [id]            Suspend job");
-    println!("  kill [-SIG] %id|pid  Send signal to job or PID");
-    println!("  wait [id]            Wait for job to finish");
+    println!("  type NAME...         Show if alias, builtin, or binary");
+    println!("  which NAME...        Show path of binary in PATH (1 if missing)");
+    println!("  jobs [-lprs] [%job]  List background jobs (-l: with PIDs, -r/-s: running/stopped)");
+    println!("  fg [%job]            Bring job to foreground");
+    println!("  bg [%job]            Resume job in background");
+    println!("  stop [%job]          Suspend job");
+    println!("  disown [%job]        Remove job from the job table");
+    println!("  kill [-SIG] %job|pid Send signal to job or PID");
+    println!("  wait [%job]          Wait for job to finish");
+    println!("                       %job: %N %+ %% %- %str %?str");
+    println!("  history [N|pat]      Show history (last N, or entries containing pat; -g glob, -f fuzzy, -c clear)");
+    println!("  fc [-l] [-e ED] [N [M]]  Edit history range in $EDITOR and run it (-l: list)");
+    println!("  status               Print the exit code of the previous command");
+    println!("  clear / cls [-x]     Clear the screen and scrollback (-x: keep scrollback)");
+    println!("  repeat N cmd         Run cmd N times (stops on first failure with set -e)");
+    println!("  times                Show shell and children CPU times");
+    println!("  tz [zone|local]      Show or set the prompt time zone (UTC, +05:30, Europe/Warsaw)");
     println!("  export [KEY=VAL]     Set/list environment variables");
     println!("  export -p            Print all exported variables");
     println!("  local KEY=VAL        Set local variable");
@@ -758,19 +1344,27 @@ fn print_help() {
     println!("  unset NAME           Remove variable");
     println!("  read [-p prompt] VAR Read line from stdin");
     println!("  exec CMD             Replace shell with command");
-    println!("  alias                List aliases");
-    println!("  set [-e] [-x] [-u]   Set shell options (or list all vars)");
-    println!("  pushd [dir]          Push directory onto stack");
+    println!("  alias [name=value]   List or define aliases for this session");
+    println!("  unalias [-a] NAME    Remove alias (-a: all)");
+    println!("  set [-e] [-x] [-u] [-v]  Set shell options (or list all vars)");
+    println!("  set -o [NAME]        Enable option by name (errexit, posix, …) or list");
+    println!("  pushd [dir]          Push directory onto stack (no dir: swap top two)");
     println!("  popd                 Pop directory from stack");
-    println!("  dirs                 Show directory stack");
+    println!("  bookmark [add|go|rm] Directory bookmarks in ~/.hsh_bookmarks (list without args)");
+    println!("  j [-l] <substring>   Jump to the most frecent matching directory (-l: list scores)");
+    println!("  dirs [-c|-v]         Show directory stack (-c: clear, -v: numbered)");
+    println!("  path                 List PATH entries (! marks missing dirs)");
+    println!("  path add [-a] DIR    Prepend DIR to PATH (-a: append)");
+    println!("  path remove DIR      Remove DIR from PATH");
+    println!("  rehash               Rescan PATH for highlighting/completion");
     println!("  source FILE          Execute file in current shell");
     println!("  true / false / :     Boolean/no-op builtins");
     println!("  help / hsh-help      Show this help");
     println!("  hsh-version          Show version");
-    println!("  hsh-reload           Reload config info");
+    println!("  hsh-reload           Reload aliases from ~/.hshrc");
     println!();
     println!("\x1b[1mNative commands (built into hsh):\x1b[0m");
-    println!("  echo  pwd  ls  cat  mkdir  rm  cp  mv  touch  env");
+    println!("  echo  ls  cat  mkdir  rm  cp  mv  touch  env");
     println!("  grep  head  tail  wc  uname  find  xargs  printf");
     println!();
     println!("\x1b[1mScript features:\x1b[0m");
@@ -781,7 +1375,7 @@ fn print_help() {
     println!("  break / continue     Loop control");
     println!("  return [code]        Return from function");
     println!("  hsh --check FILE     Validate script syntax");
-    println!("  hsh FILE.sh [args]   Run script directly");
+    println!("  hsh FILE [args]      Run script non-interactively (no history)");
     println!();
     println!("\x1b[1mFeatures:\x1b[0m");
     println!("  Syntax highlighting with dangerous-command detection");
@@ -792,5 +1386,10 @@ fn print_help() {
     println!("  Auto ~/.hshrc generation on first run");
     println!("  TUI theme selector: hsh-settings");
     println!("  Dry-run mode:     hsh --dry-run");
+    println!("  Safe mode:        hsh --safe (no auto-chmod/state saving/[env], confirm disk writes)");
+    println!("  POSIX mode:       hsh --posix (no auto-sudo/confirmations/.sh-.hl handling)");
     println!("  -c flag:          hsh -c 'command'");
+    println!("  -ic flags:        hsh -ic 'command' (then stay interactive)");
+    println!("  Startup profile:  ~/.hsh_profile + [startup] commands (skip: hsh --no-rc)");
+    println!("  Login shell:      hsh --login / -l (profile also for -c and scripts)");
 }
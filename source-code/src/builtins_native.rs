@@ -3,6 +3,7 @@ use std::fs::{self, read_dir, File};
 use std::io::{self, BufRead, BufReader, Write};
 use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
+use std::sync::atomic::{AtomicBool, Ordering};
 
 use chrono::{DateTime, Local};
 use walkdir::WalkDir;
@@ -11,39 +12,85 @@ use syntect::highlighting::{ThemeSet, Style};
 use syntect::parsing::SyntaxSet;
 use syntect::util::{as_24_bit_terminal_escaped, LinesWithEndings};
 
+/// Natywna komenda pisząca (mkdir, rm, cp, mv, touch) dostała odmowę dostępu —
+/// odpowiednik "Permission denied" na stderr komendy zewnętrznej ([sudo] auto_retry)
+static DENIED: AtomicBool = AtomicBool::new(false);
+
+type NativeFn = fn(&[String]) -> i32;
+
+/// Natywne komendy i ich implementacje — jedna lista dla `is_native`
+/// i `dispatch_native`
+const NATIVE: &[(&str, NativeFn)] = &[
+    ("echo",   native_echo),
+    ("pwd",    |_| native_pwd()),
+    ("ls",     native_ls),
+    ("cat",    native_cat_highlighted),
+    ("mkdir",  native_mkdir),
+    ("rm",     native_rm),
+    ("cp",     native_cp),
+    ("mv",     native_mv),
+    ("touch",  native_touch),
+    ("env",    native_env),
+    ("grep",   native_grep),
+    ("head",   native_head),
+    ("tail",   native_tail),
+    ("wc",     native_wc),
+    ("true",   |_| 0),
+    ("false",  |_| 1),
+    ("uname",  native_uname),
+    ("find",   native_find),
+    ("xargs",  native_xargs),
+    ("printf", native_printf),
+];
+
+/// Czy `cmd` obsługuje `dispatch_native`
+pub fn is_native(cmd: &str) -> bool {
+    NATIVE.iter().any(|(name, _)| *name == cmd)
+}
+
 pub fn dispatch_native(cmd: &str, args: &[String]) -> Option<i32> {
-    match cmd {
-        "echo"    => Some(native_echo(args)),
-        "pwd"     => Some(native_pwd()),
-        "ls"      => Some(native_ls(args)),
-        "cat"     => Some(native_cat_highlighted(args)),
-        "mkdir"   => Some(native_mkdir(args)),
-        "rm"      => Some(native_rm(args)),
-        "cp"      => Some(native_cp(args)),
-        "mv"      => Some(native_mv(args)),
-        "touch"   => Some(native_touch(args)),
-        "env"     => Some(native_env(args)),
-        "grep"    => Some(native_grep(args)),
-        "head"    => Some(native_head(args)),
-        "tail"    => Some(native_tail(args)),
-        "wc"      => Some(native_wc(args)),
-        "true"    => Some(0),
-        "false"   => Some(1),
-        "uname"   => Some(native_uname(args)),
-        "find"    => Some(native_find(args)),
-        "xargs"   => Some(native_xargs(args)),
-        "printf"  => Some(native_printf(args)),
-        _         => None,
+    DENIED.store(false, Ordering::Relaxed);
+    let (_, run) = NATIVE.iter().find(|(name, _)| *name == cmd)?;
+    Some(run(args))
+}
+
+/// Czy ostatnie wywołanie `dispatch_native` skończyło się odmową dostępu
+pub fn last_denied() -> bool {
+    DENIED.load(Ordering::Relaxed)
+}
+
+fn note_denied(e: &io::Error) {
+    if e.kind() == io::ErrorKind::PermissionDenied || e.raw_os_error() == Some(libc::EPERM) {
+        DENIED.store(true, Ordering::Relaxed);
     }
 }
 
 // ── echo ────────────────────────────────────────────────────────────────────
 
 fn native_echo(args: &[String]) -> i32 {
-    let no_newline = args.first().map(|a| a == "-n").unwrap_or(false);
-    let start = if no_newline { 1 } else { 0 };
-    let out = args[start..].join(" ");
-    let out = interpret_escapes(&out);
+    // Opcje jak w bash: -n, -e, -E (także łączone: -ne); pierwszy
+    // argument, który nie jest opcją, kończy ich parsowanie
+    let (mut no_newline, mut escapes) = (false, false);
+    let mut start = 0;
+    for arg in args {
+        let Some(flags) = arg.strip_prefix('-') else { break };
+        if flags.is_empty() || !flags.chars().all(|c| matches!(c, 'n' | 'e' | 'E')) { break; }
+        for c in flags.chars() {
+            match c {
+                'n' => no_newline = true,
+                'e' => escapes = true,
+                _   => escapes = false,
+            }
+        }
+        start += 1;
+    }
+    let mut out = args[start..].join(" ");
+    if escapes {
+        let (text, stop) = interpret_escapes(&out);
+        out = text;
+        // \c — przerwij wypisywanie (także bez końcowego znaku nowej linii)
+        if stop { no_newline = true; }
+    }
     if no_newline {
         print!("{}", out);
         io::stdout().flush().ok();
@@ -53,7 +100,8 @@ fn native_echo(args: &[String]) -> i32 {
     0
 }
 
-fn interpret_escapes(s: &str) -> String {
+/// Rozwiń sekwencje `echo -e`; drugi element — napotkano `\c`
+fn interpret_escapes(s: &str) -> (String, bool) {
     let mut result = String::new();
     let mut chars = s.chars().peekable();
     while let Some(c) = chars.next() {
@@ -62,7 +110,13 @@ fn interpret_escapes(s: &str) -> String {
                 Some('n')  => result.push('\n'),
                 Some('t')  => result.push('\t'),
                 Some('r')  => result.push('\r'),
+                Some('a')  => result.push('\x07'),
+                Some('b')  => result.push('\x08'),
+                Some('e')  => result.push('\x1b'),
+                Some('f')  => result.push('\x0c'),
+                Some('v')  => result.push('\x0b'),
                 Some('\\') => result.push('\\'),
+                Some('c')  => return (result, true),
                 Some(c)    => { result.push('\\'); result.push(c); }
                 None       => result.push('\\'),
             }
@@ -70,7 +124,7 @@ fn interpret_escapes(s: &str) -> String {
             result.push(c);
         }
     }
-    result
+    (result, false)
 }
 
 // ── pwd ─────────────────────────────────────────────────────────────────────
@@ -230,6 +284,7 @@ fn native_mkdir(args: &[String]) -> i32 {
             fs::create_dir(arg)
         };
         if let Err(e) = result {
+            note_denied(&e);
             eprintln!("mkdir: {}: {}", arg, e);
             code = 1;
         }
@@ -253,6 +308,7 @@ fn native_rm(args: &[String]) -> i32 {
             fs::remove_file(p)
         };
         if let Err(e) = result {
+            note_denied(&e);
             if !force { eprintln!("rm: {}: {}", arg, e); code = 1; }
         }
     }
@@ -280,7 +336,7 @@ fn native_cp(args: &[String]) -> i32 {
         } else {
             fs::copy(src_path, &target).map(|_| ())
         };
-        if let Err(e) = result { eprintln!("cp: {}: {}", src, e); code = 1; }
+        if let Err(e) = result { note_denied(&e); eprintln!("cp: {}: {}", src, e); code = 1; }
     }
     code
 }
@@ -314,9 +370,11 @@ fn native_mv(args: &[String]) -> i32 {
             // cross-device: copy + remove
             if e.kind() == io::ErrorKind::CrossesDevices || e.raw_os_error() == Some(18) {
                 if let Err(e2) = fs::copy(src_path, &target).and_then(|_| fs::remove_file(src_path)) {
+                    note_denied(&e2);
                     eprintln!("mv: {}: {}", src, e2); code = 1;
                 }
             } else {
+                note_denied(&e);
                 eprintln!("mv: {}: {}", src, e); code = 1;
             }
         }
@@ -334,7 +392,7 @@ fn native_touch(args: &[String]) -> i32 {
         } else {
             File::create(arg).map(|_| ())
         };
-        if let Err(e) = result { eprintln!("touch: {}: {}", arg, e); code = 1; }
+        if let Err(e) = result { note_denied(&e); eprintln!("touch: {}: {}", arg, e); code = 1; }
     }
     code
 }
@@ -362,7 +420,7 @@ fn native_env(args: &[String]) -> i32 {
                 cmd.env(&pair[..eq], &pair[eq+1..]);
             }
         }
-        return cmd.status().map(|s| s.code().unwrap_or(1)).unwrap_or(1);
+        return cmd.status().map(|s| crate::execute::exit_code(&s)).unwrap_or(1);
     }
     0
 }
@@ -624,7 +682,7 @@ fn native_find(args: &[String]) -> i32 {
         if let Some(pat) = &name_pattern {
             let name = e.file_name().to_string_lossy();
             // proste globowanie bez regex – używamy glob::Pattern zamiast regex
-            if let Ok(pattern) = glob::Pattern::new(&pat) {
+            if let Ok(pattern) = glob::Pattern::new(pat) {
                 if !pattern.matches(&name) {
                     return false;
                 }
@@ -1,11 +1,14 @@
 use std::collections::HashMap;
 use std::env;
 use std::fs;
+use std::os::unix::fs::PermissionsExt;
 use std::path::Path;
 
-use hk_parser::{load_hk_file, resolve_interpolations, HkConfig};
+use hk_parser::{load_hk_file, resolve_interpolations, HkConfig, HkValue};
 use indexmap::IndexMap;
 
+use crate::security::{danger_style_escape, DEFAULT_DANGER_STYLE};
+
 /// Domyślna zawartość pliku .hshrc w formacie hk
 const DEFAULT_HSHRC: &str = r#"# ─────────────────────────────────────────────────────────────────────────────
 # ~/.hshrc — HackerOS Shell konfiguracja (format: hk-parser 0.3.0)
@@ -16,6 +19,8 @@ const DEFAULT_HSHRC: &str = r#"# ───────────────
 version = "0.4.0"
 history_file   = "~/.hsh-history"
 history_limit  = 10000
+# Wspólna historia dla wielu terminali: zapis od razu, scalanie przed promptem
+shared_history = false
 hints_file     = "~/.hsh-hints.json"
 path_cache     = "~/.hsh-path-cache.json"
 theme_file     = "~/.config/hackeros/hsh/theme.json"
@@ -23,17 +28,104 @@ default_theme  = "default"
 # Opcje powłoki (jak set -e / set -x)
 errexit        = false
 xtrace         = false
+# Tryb zgodności POSIX (jak --posix): bez auto-sudo, potwierdzeń, obsługi .sh/.hl
+posix          = false
+# Edytor dla fc, gdy nie ustawiono $FCEDIT ani $EDITOR (domyślnie vi)
+editor         = ""
+# Pokaż komendę po rozwinięciu aliasów, zmiennych i globów (jak set -v)
+verbose        = false
 nounset        = false
+# cd do nieistniejącego katalogu: "error" (domyślnie) lub "ask" (zaproponuj mkdir -p)
+cd_mkdir       = "error"
+# Wpisanie samej ścieżki katalogu (../src, ~/projekty) robi do niej cd (jak AUTO_CD w zsh);
+# działa też jako [options] auto_cd
+auto_cd        = false
+# Zapamiętaj katalog przy wyjściu i wróć do niego przy następnym starcie
+restore_last_dir = false
+# Koloruj stderr komend (jawne 2>… / 2>&1 / &> ma pierwszeństwo)
+color_stderr   = false
+# Tryb bezpieczny (jak hsh --safe): bez auto-chmod, bez zapisu historii/stanu, bez [env],
+# potwierdzenie przed komendami zapisującymi na dysk
+safe_mode      = false
+# Komenda uruchamiana po Enter na pustej linii, np. "ls" lub "git status" (pusta — nic)
+on_empty       = ""
+# Ctrl+T: fuzzy wyszukiwanie w historii (fzf jeśli zainstalowany, inaczej wbudowane)
+fuzzy_finder   = false
+# Tryb edycji linii: "emacs" albo "vi" (Esc — tryb NORMAL, i/a — INSERT;
+# {vi_mode} w [prompt] format pokazuje bieżący tryb)
+edit_mode      = "emacs"
+# Uzupełnianie Tab: "list" (wypisz pasujące) albo "circular" (kolejne Tab
+# podstawia kolejnych kandydatów, Shift+Tab — wstecz)
+completion     = "list"
+# Jak menu-complete w readline — Tab przełącza kandydatów (= completion "circular")
+menu_complete  = false
+# Literówka w nazwie komendy (gti status) — zapytaj "Did you mean 'git'? [Y/n]"
+correct        = false
+# Powłoka używana do podstawiania $(…) (np. "/bin/bash"); pusta — samo hsh
+fallback_shell = ""
+# Komendy otwierające podpowłokę — hsh wypisze "(entering subshell via …)"
+# subshell_commands = "ssh, sudo -s, sudo -i, su"
+subshell_commands = ""
 
 [prompt]
 # Kolejność segmentów promptu (oddzielone przecinkiem)
 segment_order  = "time, dir, git, mem_cpu"
 # Znak promptu można nadpisać tutaj (jeśli pusty — używa motywu)
 # prompt_char  = "❯"
-# Czy pokazywać czas trwania komendy (w ms) gdy > 2000ms
+# Czy pokazywać czas trwania komendy dłuższej niż cmd_duration_min_ms
 show_duration  = true
+cmd_duration_min_ms = 2000
 # Czy pokazywać exit code gdy != 0
 show_exit_code = true
+# Strefa czasowa czasu w prompcie: "UTC", "+05:30", "Europe/Warsaw" (pusta — lokalna)
+timezone       = ""
+# Dopisz strefę do czasu: "offset" (+02:00), "name" (nazwa strefy) lub "none"
+show_tz        = "none"
+# Limit czasu zapytań git w ms (wolne NFS) — po przekroczeniu segment git znika
+git_timeout_ms = 150
+# Stan repozytorium przy gałęzi: ±zmienione pliki, ↑/↓ względem upstream
+# (wolniejsze — false pokazuje samą gałąź)
+git_status     = true
+# Co ile ms odświeżać segment git w tle (cd i komendy git odświeżają od razu)
+git_refresh_ms = 2000
+# Wyświetlanie katalogu: "full", "basename" (ostatni składnik)
+# lub "truncate" (rodzice skróceni do pierwszej litery: ~/p/hsh)
+path_style     = "full"
+# user@host w prompcie tylko w sesji SSH ($SSH_CONNECTION); wtedy też
+# {host} w format jest pusty poza SSH
+show_host      = false
+# {battery} na czerwono poniżej tego poziomu naładowania (%)
+battery_warn_below = 20
+# {venv} pokazuje też wersję z .nvmrc / .python-version w bieżącym katalogu
+venv_versions  = false
+# Symbol wyświetlany poza repozytorium git (pusty — segment znika)
+no_git_symbol  = ""
+# Kolor katalogu poza repozytorium (numer palety 256, pusty — kolor motywu)
+# no_git_dir_color = "244"
+# Własny układ promptu — placeholdery: {time} {cwd} {git} {exit_symbol}
+# {exit_code} {root_symbol} {duration} {cmd_duration} {battery} {depth} {sys}
+# {prompt_char} {user} {host} {vi_mode} {venv}; \n — nowa linia (pusty — domyślny układ)
+# format         = "{user}@{host} {cwd} {git}\n{exit_symbol}{prompt_char} "
+# Prawy prompt — placeholdery: {mem} {mem_pct} {cpu} {time} {dir} {branch}
+# {exit_code} {duration} {cmd_duration} {battery} {venv}
+# (pusty — brak prawego promptu)
+# rprompt        = "mem: {mem}  cpu: {cpu}"
+
+[colors]
+# Kolory podświetlania linii poleceń (nadpisują motyw): nazwa (green,
+# bright_red, gray), numer palety 256 ("110") lub parametry SGR ("1;32")
+# command_ok      = "green"
+# command_missing = "red"
+# string          = "150"
+# variable        = "cyan"
+# operator        = "gray"
+# option          = "yellow"
+# path            = "73"
+# comment         = "242"
+
+[functions]
+# Funkcje powłoki: nazwa = treść; argumenty jako $1, $2, $@
+# mkcd = "mkdir -p \"$1\" && cd \"$1\""
 
 [aliases]
 # Skróty komend
@@ -65,7 +157,14 @@ ccl   = "cargo clippy"
 cls   = "clear"
 q     = "exit"
 h     = "history"
-j     = "jobs"
+
+# Sekcje warunkowe nadpisują bazową tylko na pasującej maszynie:
+# host=… (nazwa hosta, także glob: work*), os=… (linux, macos, freebsd…),
+# wiele warunków po przecinku, != neguje
+# [aliases:host=workstation]
+# ll = "ls -la --group-directories-first"
+# [prompt:os=macos,host!=laptop]
+# symbol = ">"
 
 [env]
 # Dodatkowe zmienne środowiskowe ładowane przy starcie
@@ -75,6 +174,21 @@ j     = "jobs"
 # Przykład:
 # MY_VAR = "wartość"
 
+[history]
+# "session" — każda sesja nadpisuje plik przy wyjściu (ostatnia wygrywa)
+# "shared"  — wpisy dopisywane od razu i scalane między terminalami
+#             (jak shared_history w [shell])
+# mode          = "session"
+# Maksymalna liczba wpisów (domyślnie history_limit z [shell])
+# max_entries   = 10000
+# Nie zapisuj komendy identycznej z poprzednią
+ignore_dups     = true
+# Wzorce glob komend, które nigdy nie trafiają do historii
+# (" *" — komendy zaczynające się od spacji)
+# ignore_patterns = [" *", "*password*"]
+# Kolumna z datą i godziną w `history` (wpisy ze starych plików — bez czasu)
+timestamps      = true
+
 [completion]
 # Czy uzupełniać pliki ukryte (zaczynające się od .)
 show_hidden    = false
@@ -83,8 +197,32 @@ max_completions = 20
 
 [safety]
 # Lista wzorców uznawanych za niebezpieczne (poza wbudowanymi)
-# extra_dangerous = ["sudo rm -rf"]
+# dangerous_patterns = ["shutdown", "sudo rm -rf"]
+# Pytaj "Are you sure?" przed komendą pasującą do wzorca
 confirm_dangerous = true
+# Styl podświetlenia niebezpiecznych komend: blink | bg_red | bold_red
+danger_style   = "blink"
+
+[sudo]
+# Edytory, dla których hsh proponuje sudo przy plikach systemowych
+editors         = ["vi", "vim", "nano", "emacs"]
+# Katalogi zawsze wymagające roota (poza plikami bez prawa zapisu)
+protected_paths = ["/etc/", "/usr/", "/var/", "/boot/"]
+# Dopisuj sudo bez pytania
+always          = false
+# Po odmowie dostępu (EACCES, "Permission denied" na stderr) zaproponuj
+# ponowienie komendy przez sudo; stderr komend przechodzi wtedy przez
+# pseudoterminal (komendy nadal widzą terminal)
+auto_retry      = false
+
+[startup]
+# Program powitalny uruchamiany przy starcie (pusty — wyłączony);
+# pomijany, jeśli plik nie istnieje lub nie jest wykonywalny
+motd       = "/usr/share/HackerOS/Archived/MOTD/hackeros-motd"
+# Nie czekaj na MOTD przed pierwszym promptem
+motd_async = false
+# Komendy wykonywane przy starcie (po ~/.hsh_profile); pomija je hsh --no-rc
+# commands  = ["export PATH=\"$HOME/bin:$PATH\"", "source ~/.hsh/functions.sh"]
 
 [scripts]
 # Katalogi przeszukiwane przy source / . (oprócz PATH)
@@ -92,20 +230,76 @@ confirm_dangerous = true
 auto_chmod = true
 "#;
 
-pub fn load_shell_config() -> HkConfig {
+/// `generate` — utwórz domyślny .hshrc, jeśli brak (wyłączone w trybie --safe)
+pub fn load_shell_config(generate: bool) -> HkConfig {
     let home = env::var("HOME").unwrap_or_default();
     let config_path = format!("{}/.hshrc", home);
 
     // Wygeneruj domyślny .hshrc jeśli nie istnieje
-    if !Path::new(&config_path).exists() {
+    if generate && !Path::new(&config_path).exists() {
         generate_default_hshrc(&config_path);
     }
 
     let mut config = load_hk_file(&config_path).unwrap_or_else(|_| IndexMap::new());
     resolve_interpolations(&mut config).ok();
+    apply_conditional_sections(&mut config);
     config
 }
 
+/// Scal sekcje warunkowe `[nazwa:warunek,…]` z sekcją bazową `[nazwa]`.
+/// Pasujące nadpisują klucze bazowe (w kolejności z pliku), niepasujące są
+/// pomijane; żadna sekcja warunkowa nie zostaje w konfiguracji.
+fn apply_conditional_sections(config: &mut HkConfig) {
+    let conditional: Vec<String> = config.keys().filter(|k| k.contains(':')).cloned().collect();
+    if conditional.is_empty() { return; }
+    let host = hostname();
+
+    for key in conditional {
+        let Some(value) = config.shift_remove(&key) else { continue };
+        let (base, cond) = key.split_once(':').unwrap_or((&key, ""));
+        if !conditions_match(cond, &host) { continue; }
+        let HkValue::Map(overrides) = value else { continue };
+        match config.entry(base.trim().to_string()).or_insert_with(|| HkValue::Map(IndexMap::new())) {
+            HkValue::Map(section) => section.extend(overrides),
+            other                 => *other = HkValue::Map(overrides),
+        }
+    }
+}
+
+/// `host=workstation,os=linux` — wszystkie warunki muszą być spełnione
+fn conditions_match(cond: &str, host: &str) -> bool {
+    cond.split(',').map(str::trim).filter(|c| !c.is_empty()).all(|c| {
+        let (name, expected, negate) = match c.split_once("!=") {
+            Some((n, v)) => (n, v, true),
+            None => match c.split_once('=') {
+                Some((n, v)) => (n, v, false),
+                None         => return false,
+            },
+        };
+        let actual = match name.trim() {
+            "host" | "hostname" => host,
+            "os"                => env::consts::OS,
+            other => {
+                eprintln!("hsh: .hshrc: nieznany warunek sekcji: {}", other);
+                return false;
+            }
+        };
+        let expected = expected.trim().trim_matches('"');
+        let hit = glob::Pattern::new(expected)
+            .map(|p| p.matches(actual))
+            .unwrap_or(expected == actual);
+        hit != negate
+    })
+}
+
+pub fn hostname() -> String {
+    let mut buf = [0u8; 256];
+    let ok = unsafe { libc::gethostname(buf.as_mut_ptr() as *mut libc::c_char, buf.len()) } == 0;
+    if !ok { return String::new(); }
+    let end = buf.iter().position(|&b| b == 0).unwrap_or(buf.len());
+    String::from_utf8_lossy(&buf[..end]).to_string()
+}
+
 /// Generuje domyślny plik .hshrc
 fn generate_default_hshrc(path: &str) {
     if let Some(parent) = Path::new(path).parent() {
@@ -216,3 +410,298 @@ pub fn get_history_limit(config: &HkConfig) -> usize {
         .and_then(|s| s.parse().ok())
         .unwrap_or(10000)
 }
+
+/// Opcje historii z sekcji [history]
+pub struct HistoryOptions {
+    pub max_entries:     usize,
+    /// max_entries podane wprost w [history], nie przejęte z history_limit
+    pub max_entries_set: bool,
+    pub ignore_dups:     bool,
+    pub ignore_patterns: Vec<String>,
+    /// Tryb "shared" — historia wspólna dla wszystkich sesji
+    pub shared:          bool,
+    /// Czas wpisów w `history` ([history] timestamps, domyślnie tak)
+    pub timestamps:      bool,
+}
+
+/// Pobierz [history]: max_entries (domyślnie [shell] history_limit),
+/// ignore_dups (domyślnie tak), listę ignore_patterns, mode
+/// ("session" | "shared", domyślnie [shell] shared_history) i timestamps
+pub fn get_history_options(config: &HkConfig) -> HistoryOptions {
+    let section = config.get("history").and_then(|v| v.as_map().ok());
+    let get = |key: &str| section.and_then(|m| m.get(key));
+    HistoryOptions {
+        max_entries: get("max_entries")
+            .and_then(|v| v.as_string().ok())
+            .and_then(|s| s.parse().ok())
+            .unwrap_or_else(|| get_history_limit(config)),
+        max_entries_set: get("max_entries")
+            .and_then(|v| v.as_string().ok())
+            .map(|s| s.parse::<usize>().is_ok())
+            .unwrap_or(false),
+        ignore_dups: get("ignore_dups")
+            .and_then(|v| v.as_string().ok())
+            .map(|s| s != "false")
+            .unwrap_or(true),
+        ignore_patterns: get("ignore_patterns").map(string_list).unwrap_or_default(),
+        shared: match get("mode").and_then(|v| v.as_string().ok()).as_deref() {
+            Some("shared")  => true,
+            Some("session") => false,
+            _ => get_shell_options(config)
+                .get("shared_history")
+                .map(|v| v == "true")
+                .unwrap_or(false),
+        },
+        timestamps: get("timestamps")
+            .and_then(|v| v.as_string().ok())
+            .map(|s| s != "false")
+            .unwrap_or(true),
+    }
+}
+
+/// Lista napisów z wartości hk — tablica albo pojedynczy napis
+fn string_list(value: &HkValue) -> Vec<String> {
+    match value {
+        HkValue::Array(items) => items.iter().filter_map(|i| i.as_string().ok()).collect(),
+        other => other.as_string().ok().into_iter().collect(),
+    }
+}
+
+/// Pobierz styl podświetlenia niebezpiecznych komend z [safety]
+pub fn get_danger_style(config: &HkConfig) -> String {
+    config
+        .get("safety")
+        .and_then(|v| v.as_map().ok())
+        .and_then(|m| m.get("danger_style"))
+        .and_then(|v| v.as_string().ok())
+        .map(|s| danger_style_escape(&s).to_string())
+        .unwrap_or_else(|| DEFAULT_DANGER_STYLE.to_string())
+}
+
+/// Dodatkowe wzorce niebezpiecznych komend z [safety] — lista lub
+/// pojedynczy napis; `extra_dangerous` to starsza nazwa klucza
+pub fn get_dangerous_patterns(config: &HkConfig) -> Vec<String> {
+    let Some(safety) = config.get("safety").and_then(|v| v.as_map().ok()) else {
+        return Vec::new();
+    };
+    ["dangerous_patterns", "extra_dangerous"]
+        .iter()
+        .filter_map(|key| safety.get(*key))
+        .flat_map(string_list)
+        .filter(|p| !p.trim().is_empty())
+        .collect()
+}
+
+/// Czy pytać przed niebezpieczną komendą ([safety] confirm_dangerous, domyślnie tak)
+pub fn get_confirm_dangerous(config: &HkConfig) -> bool {
+    config
+        .get("safety")
+        .and_then(|v| v.as_map().ok())
+        .and_then(|m| m.get("confirm_dangerous"))
+        .map(|v| match v {
+            HkValue::Bool(b) => *b,
+            other => other.as_string().map(|s| s != "false").unwrap_or(true),
+        })
+        .unwrap_or(true)
+}
+
+/// auto_cd z [options] (jak w zsh) albo z [shell]; [options] ma pierwszeństwo
+pub fn get_auto_cd(config: &HkConfig) -> bool {
+    ["options", "shell"]
+        .iter()
+        .find_map(|section| {
+            config
+                .get(*section)
+                .and_then(|v| v.as_map().ok())
+                .and_then(|m| m.get("auto_cd"))
+        })
+        .map(|v| match v {
+            HkValue::Bool(b) => *b,
+            other => other.as_string().map(|s| s == "true").unwrap_or(false),
+        })
+        .unwrap_or(false)
+}
+
+/// Ścieżka MOTD z [startup] motd (domyślnie program HackerOS) i motd_async.
+/// None — wyłączone albo plik nie istnieje / nie jest wykonywalny.
+pub fn get_motd(config: &HkConfig) -> (Option<String>, bool) {
+    let section = config.get("startup").and_then(|v| v.as_map().ok());
+    let path = section
+        .and_then(|m| m.get("motd"))
+        .and_then(|v| v.as_string().ok())
+        .unwrap_or_else(|| DEFAULT_MOTD.to_string());
+    let path = match path.strip_prefix('~') {
+        Some(rest) => format!("{}{}", env::var("HOME").unwrap_or_default(), rest),
+        None       => path,
+    };
+    let runnable = !path.is_empty()
+        && fs::metadata(&path)
+            .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+            .unwrap_or(false);
+    let is_async = section
+        .and_then(|m| m.get("motd_async"))
+        .map(|v| match v {
+            HkValue::Bool(b) => *b,
+            other => other.as_string().map(|s| s == "true").unwrap_or(false),
+        })
+        .unwrap_or(false);
+    (runnable.then_some(path), is_async)
+}
+
+/// Role kolorów podświetlania z [colors] (command_ok, string, path…)
+pub fn get_highlight_colors(config: &HkConfig) -> HashMap<String, String> {
+    config
+        .get("colors")
+        .and_then(|v| v.as_map().ok())
+        .map(|m| {
+            m.iter()
+                .filter_map(|(k, v)| v.as_string().ok().map(|s| (k.clone(), s)))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Funkcje z [functions] jako definicje `nazwa() { treść; }`, posortowane po nazwie
+pub fn get_functions(config: &HkConfig) -> Vec<String> {
+    let mut defs: Vec<String> = config
+        .get("functions")
+        .and_then(|v| v.as_map().ok())
+        .map(|m| {
+            m.iter()
+                .filter(|(name, _)| is_function_name(name))
+                .filter_map(|(name, v)| v.as_string().ok().map(|body| format!("{}() {{ {}; }}", name, body.trim().trim_end_matches(';'))))
+                .collect()
+        })
+        .unwrap_or_default();
+    defs.sort();
+    defs
+}
+
+fn is_function_name(name: &str) -> bool {
+    !name.is_empty()
+        && !name.starts_with(|c: char| c.is_ascii_digit())
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_' || c == '-')
+}
+
+/// Auto-sudo przy edycji plików systemowych ([sudo])
+pub struct SudoOptions {
+    pub editors:         Vec<String>,
+    pub protected_paths: Vec<String>,
+    /// Dopisz sudo bez pytania y/n
+    pub always:          bool,
+    /// Po odmowie dostępu zaproponuj ponowienie przez sudo
+    pub auto_retry:      bool,
+}
+
+impl Default for SudoOptions {
+    fn default() -> Self {
+        SudoOptions {
+            editors:         ["vi", "vim", "nano", "emacs"].map(String::from).to_vec(),
+            protected_paths: ["/etc/", "/usr/", "/var/", "/boot/"].map(String::from).to_vec(),
+            always:          false,
+            auto_retry:      false,
+        }
+    }
+}
+
+/// Pobierz [sudo]: editors, protected_paths (tablice lub napisy), always
+/// i auto_retry; brakujące klucze — wartości domyślne
+pub fn get_sudo_options(config: &HkConfig) -> SudoOptions {
+    let mut opts = SudoOptions::default();
+    let Some(section) = config.get("sudo").and_then(|v| v.as_map().ok()) else { return opts };
+    if let Some(v) = section.get("editors") {
+        opts.editors = string_list(v);
+    }
+    if let Some(v) = section.get("protected_paths") {
+        opts.protected_paths = string_list(v);
+    }
+    let flag = |key: &str| section
+        .get(key)
+        .map(|v| match v {
+            HkValue::Bool(b) => *b,
+            other => other.as_string().map(|s| s == "true").unwrap_or(false),
+        })
+        .unwrap_or(false);
+    opts.always = flag("always");
+    opts.auto_retry = flag("auto_retry");
+    opts
+}
+
+/// Komendy z [startup] commands — tablica albo pojedynczy napis
+pub fn get_startup_commands(config: &HkConfig) -> Vec<String> {
+    config
+        .get("startup")
+        .and_then(|v| v.as_map().ok())
+        .and_then(|m| m.get("commands"))
+        .map(string_list)
+        .unwrap_or_default()
+}
+
+const DEFAULT_MOTD: &str = "/usr/share/HackerOS/Archived/MOTD/hackeros-motd";
+
+/// Plik stanu z ostatnim katalogiem roboczym (restore_last_dir)
+fn last_dir_path() -> String {
+    let home = env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+    format!("{}/.hsh-last-dir", home)
+}
+
+/// Zapisz bieżący katalog roboczy do pliku stanu
+pub fn save_last_dir() {
+    if let Ok(cwd) = env::current_dir() {
+        let _ = fs::write(last_dir_path(), cwd.to_string_lossy().as_bytes());
+    }
+}
+
+fn allowlist_path() -> String {
+    let home = env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+    format!("{}/.hsh-allow", home)
+}
+
+/// Potwierdzenia zapamiętane odpowiedzią "A" (`rodzaj<TAB>komenda` w linii)
+pub fn load_allowlist() -> std::collections::HashSet<String> {
+    fs::read_to_string(allowlist_path())
+        .map(|s| s.lines().filter(|l| !l.trim().is_empty()).map(String::from).collect())
+        .unwrap_or_default()
+}
+
+/// Dopisz potwierdzenie do ~/.hsh-allow
+pub fn append_allowlist(key: &str) {
+    use std::io::Write;
+    let file = fs::OpenOptions::new().create(true).append(true).open(allowlist_path());
+    if let Ok(mut f) = file {
+        let _ = writeln!(f, "{}", key);
+    }
+}
+
+fn bookmarks_path() -> String {
+    let home = env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+    format!("{}/.hsh_bookmarks", home)
+}
+
+/// Zakładki katalogów (`bookmark`, `cd @nazwa`) — linie `nazwa<TAB>ścieżka`
+pub fn load_bookmarks() -> std::collections::BTreeMap<String, String> {
+    fs::read_to_string(bookmarks_path())
+        .map(|s| {
+            s.lines()
+                .filter_map(|l| l.split_once('\t'))
+                .map(|(name, path)| (name.to_string(), path.to_string()))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Zapisz zakładki do ~/.hsh_bookmarks
+pub fn save_bookmarks(bookmarks: &std::collections::BTreeMap<String, String>) -> std::io::Result<()> {
+    let body: String = bookmarks
+        .iter()
+        .map(|(name, path)| format!("{}\t{}\n", name, path))
+        .collect();
+    fs::write(bookmarks_path(), body)
+}
+
+/// Odczytaj zapisany katalog, jeśli nadal istnieje
+pub fn load_last_dir() -> Option<String> {
+    let dir = fs::read_to_string(last_dir_path()).ok()?;
+    let dir = dir.trim();
+    if Path::new(dir).is_dir() { Some(dir.to_string()) } else { None }
+}
@@ -47,7 +47,7 @@ fn docs_index() {
     println!();
 
     let topics = [
-        ("redirections", "Przekierowania I/O: > >> < 2>&1 &> <<"),
+        ("redirections", "Przekierowania I/O: > >> < 2>&1 &> &>> <<"),
         ("pipes",        "Natywne pipelines: cmd1 | cmd2 | cmd3"),
         ("vars",         "Zmienne: $VAR ${VAR:-def} $? $$ $# $@"),
         ("scripting",    "Skrypty: if/for/while/case/funkcje"),
@@ -80,6 +80,7 @@ fn docs_redirections() {
     code("cmd 2> err.log               # stderr do pliku");
     code("cmd 2>&1                     # stderr → stdout");
     code("cmd &> wszystko.log          # stdout+stderr do pliku");
+    code("cmd &>> wszystko.log         # stdout+stderr dopisz do pliku");
     println!();
 
     section("Heredoc:");
@@ -342,6 +343,7 @@ fn docs_shortcuts() {
         ("Alt+F",        "Akceptuj jedno słowo hintu"),
         ("Tab",          "Uzupełnij komendę / plik"),
         ("Ctrl+R",       "Szukaj w historii (reverse search)"),
+        ("Ctrl+S",       "Szukaj w historii w przód (forward search)"),
         ("Ctrl+L",       "Wyczyść ekran"),
         ("Ctrl+C",       "Anuluj bieżącą linię"),
         ("Ctrl+D",       "Wyjdź z hsh (EOF)"),
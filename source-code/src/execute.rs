@@ -1,27 +1,35 @@
 use std::collections::HashMap;
 use std::env;
-use std::fs::read_to_string;
-use std::io::{self, Write};
-use std::os::unix::fs::PermissionsExt;
+use std::fs::{read_to_string, File};
+use std::io::{self, Read, Write};
+use std::os::unix::fs::{MetadataExt, PermissionsExt};
+use std::os::unix::io::{FromRawFd, OwnedFd};
 use std::os::unix::process::CommandExt;
 use std::path::{Path, PathBuf};
 use std::process::Stdio;
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::Arc;
 
 use rustyline::Editor;
 
 use crate::arithmetic::expand_arithmetic;
-use crate::builtins::handle_builtin;
-use crate::builtins_native::dispatch_native;
-use crate::helper::ShellHelper;
+use crate::builtins::{handle_builtin, io_error_text};
+use crate::builtins_native::{dispatch_native, is_native};
+use crate::expand::{expand_alias, expand_tilde, expand_words, glob_match};
+use crate::helper::{is_complete_input, ShellHelper};
 use crate::history::ShellHistory;
 use crate::jobs::JobTable;
 use crate::path_cache::PathCache;
-use crate::redirect::{apply_redirections, parse_redirections, Redirect};
+use crate::redirect::{
+    apply_in_shell, apply_redirections, failing_target, parse_redirections, redirects_stderr, Redirect, RedirectKind,
+    RedirectTarget,
+};
 use crate::script::{
     builtin_test, print_syntax_errors, validate_script, FunctionTable, Node, Parser,
 };
-use crate::security::confirm_dangerous;
+use crate::security::{ask_confirm, confirm_dangerous};
 use crate::smarthints::SmartHints;
+use crate::tokenize;
 use crate::vars::{parse_inline_env, ShellVars};
 
 // ─────────────────────────────────────────────────────────────────────────────
@@ -35,27 +43,47 @@ pub enum FlowSignal {
     Return(i32),
 }
 
+// ─────────────────────────────────────────────────────────────────────────────
+// Stan sesji powłoki
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Stan, który komendy mogą zmieniać: aliasy, funkcje, zadania, historia i
+/// zmienne. Przekazywany jako całość zamiast pięciu osobnych `&mut`.
+pub struct ShellCtx {
+    pub aliases:   HashMap<String, String>,
+    pub functions: FunctionTable,
+    pub jobs:      JobTable,
+    pub history:   ShellHistory,
+    pub vars:      ShellVars,
+}
+
+impl ShellCtx {
+    pub fn new(aliases: HashMap<String, String>, history: ShellHistory) -> Self {
+        Self {
+            aliases,
+            functions: FunctionTable::new(),
+            jobs:      JobTable::new(),
+            history,
+            vars:      ShellVars::new(),
+        }
+    }
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Publiczny entry point
 // ─────────────────────────────────────────────────────────────────────────────
 
 pub async fn execute_command(
     input:        &str,
-    aliases:      &HashMap<String, String>,
+    ctx:          &mut ShellCtx,
     rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:     &mut Option<PathBuf>,
-    jobs:         &mut JobTable,
-    vars:         &mut ShellVars,
     smart_hints:  &mut SmartHints,
-    shell_history: &mut ShellHistory,
     path_cache:   &PathCache,
     dry_run:      bool,
 ) -> io::Result<i32> {
-    let mut functions = FunctionTable::new();
     run_line(
-        input, aliases, rl, prev_dir, jobs, vars,
-        smart_hints, shell_history, path_cache,
-        &mut functions, dry_run,
+        input, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
     )
     .await
 }
@@ -108,6 +136,9 @@ pub fn extract_heredocs(input: &str, vars: &ShellVars) -> (String, HashMap<Strin
                 continue;
             }
 
+            // Sama linia komendy zostaje — `<<DELIM` zamieni się w przekierowanie
+            result.push_str(line);
+            result.push('\n');
             let mut body = String::new();
             i += 1;
             while i < lines.len() {
@@ -142,41 +173,43 @@ pub fn extract_heredocs(input: &str, vars: &ShellVars) -> (String, HashMap<Strin
 
 async fn run_line(
     input:        &str,
-    aliases:      &HashMap<String, String>,
+    ctx:          &mut ShellCtx,
     rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:     &mut Option<PathBuf>,
-    jobs:         &mut JobTable,
-    vars:         &mut ShellVars,
     smart_hints:  &mut SmartHints,
-    shell_history: &mut ShellHistory,
     path_cache:   &PathCache,
-    functions:    &mut FunctionTable,
     dry_run:      bool,
 ) -> io::Result<i32> {
-    let trimmed = input.trim();
-    if trimmed.is_empty() || trimmed.starts_with('#') {
+    let without_comments = strip_comments(input);
+    let trimmed = without_comments.trim();
+    if trimmed.is_empty() {
         return Ok(0);
     }
-    if vars.xtrace {
-        eprintln!("+ {}", trimmed);
-    }
 
     if is_script_construct(trimmed) {
         return run_script_node(
-            trimmed, aliases, rl, prev_dir, jobs, vars,
-            smart_hints, shell_history, path_cache, functions, dry_run,
+            trimmed, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
         )
         .await;
     }
 
     let stmts = split_compound(trimmed);
+    // `echo start; for x in a b; do …; done` — pętla dalej w linii też idzie do parsera
+    // (tylko po `;` — parser składa `a && b` w jedną komendę, która wraca tutaj)
+    if stmts.windows(2).any(|w| w[0].1.as_deref() == Some(";") && starts_script_keyword(&w[1].0)) {
+        return run_script_node(
+            trimmed, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
+        )
+        .await;
+    }
+    // $? po każdej komendzie — także po builtinach, które same go nie ustawiają
     if stmts.len() == 1 {
         let code = run_single(
-            trimmed, aliases, rl, prev_dir, jobs, vars,
-            smart_hints, shell_history, path_cache, functions, dry_run,
+            trimmed, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
         )
         .await?;
-        if vars.errexit && code != 0 { return Ok(code); }
+        ctx.vars.last_exit = code;
+        if ctx.vars.errexit && code != 0 { return Ok(code); }
         return Ok(code);
     }
 
@@ -185,11 +218,11 @@ async fn run_line(
         let stmt = stmt.trim().to_string();
         if stmt.is_empty() { continue; }
         last_code = Box::pin(run_single(
-            &stmt, aliases, rl, prev_dir, jobs, vars,
-            smart_hints, shell_history, path_cache, functions, dry_run,
+            &stmt, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
         ))
         .await?;
-        if vars.errexit && last_code != 0 { return Ok(last_code); }
+        ctx.vars.last_exit = last_code;
+        if ctx.vars.errexit && last_code != 0 { return Ok(last_code); }
         match op.as_deref() {
             Some("&&") if last_code != 0 => break,
             Some("||") if last_code == 0 => break,
@@ -205,15 +238,11 @@ async fn run_line(
 
 async fn run_script_node(
     input:        &str,
-    aliases:      &HashMap<String, String>,
+    ctx:          &mut ShellCtx,
     rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:     &mut Option<PathBuf>,
-    jobs:         &mut JobTable,
-    vars:         &mut ShellVars,
     smart_hints:  &mut SmartHints,
-    shell_history: &mut ShellHistory,
     path_cache:   &PathCache,
-    functions:    &mut FunctionTable,
     dry_run:      bool,
 ) -> io::Result<i32> {
     let mut parser = Parser::new(input);
@@ -221,12 +250,11 @@ async fn run_script_node(
     let mut last = 0i32;
     for node in nodes {
         match Box::pin(exec_node(
-            &node, aliases, rl, prev_dir, jobs, vars,
-            smart_hints, shell_history, path_cache, functions, dry_run,
+            &node, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
         ))
         .await?
         {
-            ExecResult::Code(c)  => { last = c; if vars.errexit && c != 0 { break; } }
+            ExecResult::Code(c)  => { last = c; if ctx.vars.errexit && c != 0 { break; } }
             ExecResult::Break    => break,
             ExecResult::Continue => continue,
             ExecResult::Return(c) => { last = c; break; }
@@ -252,48 +280,43 @@ enum ExecResult {
 
 async fn exec_node(
     node:         &Node,
-    aliases:      &HashMap<String, String>,
+    ctx:          &mut ShellCtx,
     rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:     &mut Option<PathBuf>,
-    jobs:         &mut JobTable,
-    vars:         &mut ShellVars,
     smart_hints:  &mut SmartHints,
-    shell_history: &mut ShellHistory,
     path_cache:   &PathCache,
-    functions:    &mut FunctionTable,
     dry_run:      bool,
 ) -> io::Result<ExecResult> {
     match node {
+        // Przez run_line — `mkdir -p "$1" && cd "$1"` w ciele funkcji to nadal && / ||
         Node::Command(cmd) => {
-            let code = Box::pin(run_single(
-                cmd, aliases, rl, prev_dir, jobs, vars,
-                smart_hints, shell_history, path_cache, functions, dry_run,
+            let code = Box::pin(run_line(
+                cmd, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
             ))
             .await?;
             Ok(ExecResult::Code(code))
         }
 
         Node::Assign { name, value } => {
-            let expanded = vars.expand(value);
-            vars.set(name, &expanded);
+            let expanded = expand_arithmetic(&ctx.vars.expand(value), &ctx.vars.all());
+            ctx.vars.set(name, &expanded);
             env::set_var(name, &expanded);
             Ok(ExecResult::Code(0))
         }
 
         Node::Break    => Ok(ExecResult::Break),
         Node::Continue => Ok(ExecResult::Continue),
-        Node::Return(code) => Ok(ExecResult::Return(code.unwrap_or(vars.last_exit))),
+        Node::Return(code) => Ok(ExecResult::Return(code.unwrap_or(ctx.vars.last_exit))),
 
         Node::Sequence(nodes) => {
             let mut last = 0i32;
             for n in nodes {
                 match Box::pin(exec_node(
-                    n, aliases, rl, prev_dir, jobs, vars,
-                    smart_hints, shell_history, path_cache, functions, dry_run,
+                    n, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
                 ))
                 .await?
                 {
-                    ExecResult::Code(c)   => { last = c; if vars.errexit && c != 0 { break; } }
+                    ExecResult::Code(c)   => { last = c; if ctx.vars.errexit && c != 0 { break; } }
                     other                 => return Ok(other),
                 }
             }
@@ -302,8 +325,7 @@ async fn exec_node(
 
         Node::If { condition, then_body, elif_branches, else_body } => {
             let cond = Box::pin(exec_node(
-                condition, aliases, rl, prev_dir, jobs, vars,
-                smart_hints, shell_history, path_cache, functions, dry_run,
+                condition, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
             ))
             .await?;
 
@@ -313,13 +335,11 @@ async fn exec_node(
             };
 
             if cond_code == 0 {
-                run_nodes_er(then_body, aliases, rl, prev_dir, jobs, vars,
-                             smart_hints, shell_history, path_cache, functions, dry_run).await
+                run_nodes_er(then_body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await
             } else {
                 for (elif_cond, elif_body) in elif_branches {
                     let ec = match Box::pin(exec_node(
-                        elif_cond, aliases, rl, prev_dir, jobs, vars,
-                        smart_hints, shell_history, path_cache, functions, dry_run,
+                        elif_cond, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
                     ))
                     .await?
                     {
@@ -327,13 +347,11 @@ async fn exec_node(
                         other               => return Ok(other),
                     };
                     if ec == 0 {
-                        return run_nodes_er(elif_body, aliases, rl, prev_dir, jobs, vars,
-                                            smart_hints, shell_history, path_cache, functions, dry_run).await;
+                        return run_nodes_er(elif_body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await;
                     }
                 }
                 if let Some(eb) = else_body {
-                    run_nodes_er(eb, aliases, rl, prev_dir, jobs, vars,
-                                 smart_hints, shell_history, path_cache, functions, dry_run).await
+                    run_nodes_er(eb, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await
                 } else {
                     Ok(ExecResult::Code(0))
                 }
@@ -344,8 +362,7 @@ async fn exec_node(
             let mut last = 0i32;
             loop {
                 let cond = match Box::pin(exec_node(
-                    condition, aliases, rl, prev_dir, jobs, vars,
-                    smart_hints, shell_history, path_cache, functions, dry_run,
+                    condition, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
                 ))
                 .await?
                 {
@@ -353,15 +370,14 @@ async fn exec_node(
                     other               => return Ok(other),
                 };
                 if cond != 0 { break; }
-                match run_nodes_er(body, aliases, rl, prev_dir, jobs, vars,
-                                   smart_hints, shell_history, path_cache, functions, dry_run).await?
+                match run_nodes_er(body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await?
                 {
                     ExecResult::Break       => break,
                     ExecResult::Continue    => continue,
                     ExecResult::Return(c)   => return Ok(ExecResult::Return(c)),
                     ExecResult::Code(c)     => {
                         last = c;
-                        if vars.errexit && c != 0 { break; }
+                        if ctx.vars.errexit && c != 0 { break; }
                     }
                 }
             }
@@ -372,8 +388,7 @@ async fn exec_node(
             let mut last = 0i32;
             loop {
                 let cond = match Box::pin(exec_node(
-                    condition, aliases, rl, prev_dir, jobs, vars,
-                    smart_hints, shell_history, path_cache, functions, dry_run,
+                    condition, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
                 ))
                 .await?
                 {
@@ -381,13 +396,12 @@ async fn exec_node(
                     other               => return Ok(other),
                 };
                 if cond == 0 { break; } // until: wykonuj dopóki warunek FAŁSZYWY
-                match run_nodes_er(body, aliases, rl, prev_dir, jobs, vars,
-                                   smart_hints, shell_history, path_cache, functions, dry_run).await?
+                match run_nodes_er(body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await?
                 {
                     ExecResult::Break     => break,
                     ExecResult::Continue  => continue,
                     ExecResult::Return(c) => return Ok(ExecResult::Return(c)),
-                    ExecResult::Code(c)   => { last = c; if vars.errexit && c != 0 { break; } }
+                    ExecResult::Code(c)   => { last = c; if ctx.vars.errexit && c != 0 { break; } }
                 }
             }
             Ok(ExecResult::Code(last))
@@ -396,13 +410,13 @@ async fn exec_node(
         Node::ForArith { init, condition, update, body } => {
             // Wykonaj init (np. i=0)
             if !init.trim().is_empty() {
-                let all_vars = vars.all();
+                let all_vars = ctx.vars.all();
                 let val_str = expand_arithmetic(init, &all_vars);
                 // Spróbuj sparsować jako assign
                 if let Some(eq) = init.find('=') {
                     let name = init[..eq].trim();
                     let val  = expand_arithmetic(&init[eq+1..], &all_vars);
-                    vars.set(name, &val);
+                    ctx.vars.set(name, &val);
                     env::set_var(name, &val);
                 }
             }
@@ -411,7 +425,7 @@ async fn exec_node(
             loop {
                 // Sprawdź warunek
                 if !condition.trim().is_empty() {
-                    let all_vars = vars.all();
+                    let all_vars = ctx.vars.all();
                     let cond_val = expand_arithmetic(condition, &all_vars);
                     if cond_val == "0" || cond_val.trim() == "0" { break; }
                     // Jeśli cond_val == "" lub "0" → wyjdź
@@ -422,22 +436,21 @@ async fn exec_node(
                     }
                 }
 
-                match run_nodes_er(body, aliases, rl, prev_dir, jobs, vars,
-                                   smart_hints, shell_history, path_cache, functions, dry_run).await?
+                match run_nodes_er(body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await?
                 {
                     ExecResult::Break     => break,
                     ExecResult::Continue  => {}
                     ExecResult::Return(c) => return Ok(ExecResult::Return(c)),
-                    ExecResult::Code(c)   => { last = c; if vars.errexit && c != 0 { break; } }
+                    ExecResult::Code(c)   => { last = c; if ctx.vars.errexit && c != 0 { break; } }
                 }
 
                 // Update
                 if !update.trim().is_empty() {
-                    let all_vars = vars.all();
+                    let all_vars = ctx.vars.all();
                     if let Some(eq) = update.find('=') {
                         let name = update[..eq].trim();
                         let val  = expand_arithmetic(&update[eq+1..], &all_vars);
-                        vars.set(name, &val);
+                        ctx.vars.set(name, &val);
                         env::set_var(name, &val);
                     } else {
                         // Wyrażenie arytmetyczne (np. i++)
@@ -450,29 +463,27 @@ async fn exec_node(
 
         Node::For { var, items, body } => {
             let mut last = 0i32;
-            let expanded_items = expand_for_items(items, vars);
+            let expanded_items = expand_for_items(items, &ctx.vars);
             for item in &expanded_items {
-                vars.set(var, item);
+                ctx.vars.set(var, item);
                 env::set_var(var, item);
-                match run_nodes_er(body, aliases, rl, prev_dir, jobs, vars,
-                                   smart_hints, shell_history, path_cache, functions, dry_run).await?
+                match run_nodes_er(body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await?
                 {
                     ExecResult::Break     => break,
                     ExecResult::Continue  => continue,
                     ExecResult::Return(c) => return Ok(ExecResult::Return(c)),
-                    ExecResult::Code(c)   => { last = c; if vars.errexit && c != 0 { break; } }
+                    ExecResult::Code(c)   => { last = c; if ctx.vars.errexit && c != 0 { break; } }
                 }
             }
             Ok(ExecResult::Code(last))
         }
 
         Node::Case { word, arms } => {
-            let word = vars.expand(word);
+            let word = ctx.vars.expand(word);
             for (patterns, body) in arms {
                 for pat in patterns {
                     if glob_match(pat, &word) {
-                        return run_nodes_er(body, aliases, rl, prev_dir, jobs, vars,
-                                            smart_hints, shell_history, path_cache, functions, dry_run).await;
+                        return run_nodes_er(body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await;
                     }
                 }
             }
@@ -480,7 +491,7 @@ async fn exec_node(
         }
 
         Node::FunctionDef { name, body } => {
-            functions.define(name, body.clone());
+            ctx.functions.define(name, body.clone());
             Ok(ExecResult::Code(0))
         }
     }
@@ -488,28 +499,23 @@ async fn exec_node(
 
 async fn run_nodes_er(
     nodes:        &[Node],
-    aliases:      &HashMap<String, String>,
+    ctx:          &mut ShellCtx,
     rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:     &mut Option<PathBuf>,
-    jobs:         &mut JobTable,
-    vars:         &mut ShellVars,
     smart_hints:  &mut SmartHints,
-    shell_history: &mut ShellHistory,
     path_cache:   &PathCache,
-    functions:    &mut FunctionTable,
     dry_run:      bool,
 ) -> io::Result<ExecResult> {
     let mut last = 0i32;
     for node in nodes {
         match Box::pin(exec_node(
-            node, aliases, rl, prev_dir, jobs, vars,
-            smart_hints, shell_history, path_cache, functions, dry_run,
+            node, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
         ))
         .await?
         {
             ExecResult::Code(c) => {
                 last = c;
-                if vars.errexit && c != 0 { return Ok(ExecResult::Code(c)); }
+                if ctx.vars.errexit && c != 0 { return Ok(ExecResult::Code(c)); }
             }
             other => return Ok(other),
         }
@@ -520,19 +526,14 @@ async fn run_nodes_er(
 // Zachowana kompatybilność — wrapper dla starych wywołań
 async fn run_nodes(
     nodes:        &[Node],
-    aliases:      &HashMap<String, String>,
+    ctx:          &mut ShellCtx,
     rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:     &mut Option<PathBuf>,
-    jobs:         &mut JobTable,
-    vars:         &mut ShellVars,
     smart_hints:  &mut SmartHints,
-    shell_history: &mut ShellHistory,
     path_cache:   &PathCache,
-    functions:    &mut FunctionTable,
     dry_run:      bool,
 ) -> io::Result<i32> {
-    match run_nodes_er(nodes, aliases, rl, prev_dir, jobs, vars,
-                       smart_hints, shell_history, path_cache, functions, dry_run).await?
+    match run_nodes_er(nodes, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await?
     {
         ExecResult::Code(c)  => Ok(c),
         ExecResult::Break    => Ok(0),
@@ -547,47 +548,90 @@ async fn run_nodes(
 
 async fn run_single(
     input:        &str,
-    aliases:      &HashMap<String, String>,
+    ctx:          &mut ShellCtx,
     rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
     prev_dir:     &mut Option<PathBuf>,
-    jobs:         &mut JobTable,
-    vars:         &mut ShellVars,
     smart_hints:  &mut SmartHints,
-    shell_history: &mut ShellHistory,
     path_cache:   &PathCache,
-    functions:    &mut FunctionTable,
     dry_run:      bool,
 ) -> io::Result<i32> {
 
+    // 0. repeat N komenda — przed rozwinięciem: run_line rozwija komendę przy
+    // każdym przebiegu, więc rozwinięcie tutaj wykonałoby wynik $x jako kod
+    if input.trim_start().starts_with("repeat ") {
+        return run_repeat(
+            input.trim_start(), ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
+        ).await;
+    }
+
     // 1. Variable expansion + arithmetic $((…))
-    let expanded = vars.expand(input);
-    let all_vars = vars.all();
+    ctx.vars.subst_status.set(None);
+    let expanded = ctx.vars.expand(input);
+    // Kod nieudanego $(…) widoczny w $? (np. `x=$(false)` zwraca 1)
+    let subst_code = ctx.vars.subst_status.take();
+    if let Some(code) = subst_code { ctx.vars.last_exit = code; }
+    let all_vars = ctx.vars.all();
     let expanded = expand_arithmetic(&expanded, &all_vars);
     let input    = expanded.as_str();
 
     // 2. Heredoc extraction
-    let (input_without_heredoc, heredoc_bodies) = extract_heredocs(input, vars);
+    let (input_without_heredoc, heredoc_bodies) = extract_heredocs(input, &ctx.vars);
+
+    // set -x: komenda po rozwinięciu, przed wykonaniem
+    if ctx.vars.xtrace {
+        eprintln!("+ {}", input_without_heredoc);
+    }
+
+    // 2b. Builtiny zmieniające stan powłoki nie mają sensu jako etap potoku
+    if let Some(name) = pipeline_builtin(&input_without_heredoc) {
+        eprintln!("hsh: {}: shell builtin cannot be used in a pipeline", name);
+        ctx.vars.last_exit = 1;
+        return Ok(1);
+    }
 
     // 3. source / .
     if let Some(path) = strip_source_prefix(&input_without_heredoc) {
         let path = expand_tilde(&path);
         if dry_run { println!("[dry-run] source {}", path); return Ok(0); }
         return run_source(
-            &path, aliases, rl, prev_dir, jobs, vars,
-            smart_hints, shell_history, path_cache, functions, dry_run,
+            &path, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
+        ).await;
+    }
+
+    // 3b. fc — edycja i ponowne wykonanie historii
+    if input_without_heredoc == "fc" || input_without_heredoc.starts_with("fc ") {
+        return run_fc(
+            &input_without_heredoc, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
         ).await;
     }
 
+    // 3c. Ukryte: statystyki completera (nie w hsh-help ani w podpowiedziach)
+    if input_without_heredoc.trim() == "hsh-debug completer" {
+        if let Some(helper) = rl.helper() {
+            helper.print_debug_stats();
+        }
+        println!("path_cache.size={}", path_cache.commands.len());
+        return Ok(0);
+    }
+
+    // 3d. auto_cd — sama ścieżka do katalogu działa jak `cd ścieżka` (nie w trybie POSIX)
+    let input_without_heredoc = if ctx.vars.auto_cd && !ctx.vars.posix && is_auto_cd(&input_without_heredoc, &ctx.aliases, &ctx.functions) {
+        format!("cd {}", input_without_heredoc.trim())
+    } else {
+        input_without_heredoc
+    };
+
     // 4. Shell builtins
     if let Some(code) = handle_builtin(
-        &input_without_heredoc, rl, prev_dir, jobs, shell_history, aliases, dry_run, vars, &heredoc_bodies,
+        &input_without_heredoc, ctx, rl, prev_dir, dry_run, &heredoc_bodies,
     ) {
+        ctx.vars.pipestatus = vec![code];
         return Ok(code);
     }
 
     // 5. test / [ ]
     {
-        let parts: Vec<String> = shlex::split(&input_without_heredoc).unwrap_or_default();
+        let parts: Vec<String> = tokenize::split(&input_without_heredoc).unwrap_or_default();
         if matches!(parts.first().map(|s| s.as_str()), Some("test") | Some("[")) {
             return Ok(builtin_test(&parts));
         }
@@ -595,25 +639,24 @@ async fn run_single(
 
     // 6. User-defined functions
     {
-        let parts: Vec<String> = shlex::split(&input_without_heredoc).unwrap_or_default();
+        let parts: Vec<String> = tokenize::split(&input_without_heredoc).unwrap_or_default();
         if let Some(fname) = parts.first() {
-            if functions.contains(fname) {
-                let body = functions.get(fname).cloned().unwrap_or_default();
+            if ctx.functions.contains(fname) {
+                let body = ctx.functions.get(fname).cloned().unwrap_or_default();
                 // Ustaw argumenty pozycyjne
-                vars.positional = parts[1..].to_vec();
+                ctx.vars.positional = parts[1..].to_vec();
                 for (i, arg) in parts[1..].iter().enumerate() {
-                    vars.set(&(i + 1).to_string(), arg);
+                    ctx.vars.set(&(i + 1).to_string(), arg);
                     env::set_var((i + 1).to_string(), arg);
                 }
-                let result = run_nodes_er(&body, aliases, rl, prev_dir, jobs, vars,
-                                         smart_hints, shell_history, path_cache, functions, dry_run).await?;
+                let result = run_nodes_er(&body, ctx, rl, prev_dir, smart_hints, path_cache, dry_run).await?;
                 let code = match result {
                     ExecResult::Code(c)  => c,
                     ExecResult::Return(c) => c,
                     ExecResult::Break     => 0,
                     ExecResult::Continue  => 0,
                 };
-                vars.last_exit = code;
+                ctx.vars.last_exit = code;
                 return Ok(code);
             }
         }
@@ -622,18 +665,21 @@ async fn run_single(
     // 7. Inline env assignments
     let (inline_env, rest) = parse_inline_env(&input_without_heredoc);
     if rest.is_empty() {
-        for (k, v) in &inline_env { vars.set(k, v); env::set_var(k, v); }
-        return Ok(0);
+        for (k, v) in &inline_env { ctx.vars.set(k, v); env::set_var(k, v); }
+        return Ok(subst_code.unwrap_or(0));
     }
 
     // 8. Alias expansion
-    let rest = expand_alias(&rest, aliases);
+    let rest = expand_alias(&rest, &ctx.aliases);
 
-    // 9. Auto-sudo
-    let rest = check_auto_sudo(&rest);
+    // 9. Auto-sudo (nie w trybie POSIX, nie dla roota)
+    let is_root = unsafe { libc::getuid() == 0 };
+    let rest = if ctx.vars.posix || is_root { rest } else { check_auto_sudo(&rest, &mut ctx.vars) };
 
     // 10. Dangerous command guard
-    if !dry_run && !confirm_dangerous(&rest) {
+    if !dry_run && !ctx.vars.posix && !confirm_dangerous(
+        &rest, &ctx.vars.danger_patterns, ctx.vars.confirm_dangerous, &mut ctx.vars.confirm_allow, !ctx.vars.safe_mode,
+    ) {
         println!("Command aborted.");
         return Ok(1);
     }
@@ -642,9 +688,13 @@ async fn run_single(
     let (background, rest) = strip_background_flag(&rest);
     let rest = rest.trim().to_string();
 
-    // 12. .sh chmod + walidacja składni
-    maybe_chmod_and_validate(&rest);
-    let rest = maybe_hl_run(rest);
+    // 12. .sh chmod + walidacja składni, uruchamianie .hl (nie w trybie POSIX)
+    let rest = if ctx.vars.posix {
+        rest
+    } else {
+        maybe_chmod_and_validate(&rest, ctx.vars.safe_mode);
+        maybe_hl_run(rest)
+    };
 
     // 13. Dry-run
     if dry_run {
@@ -653,14 +703,39 @@ async fn run_single(
     }
 
     // 14. Pipeline or simple
+    let subshell = if background || ctx.vars.posix { None } else { subshell_command(&rest, &ctx.vars.subshell_cmds) };
+    if let Some(via) = &subshell {
+        eprintln!("\x1b[38;5;244m(entering subshell via {})\x1b[0m", via);
+    }
     let stages = split_pipeline(&rest);
+    if ctx.vars.verbose {
+        eprintln!("\x1b[38;5;244m=> {}{}\x1b[0m", expanded_line(&stages), if background { " &" } else { "" });
+    }
+    if ctx.vars.safe_mode && stages.iter().any(|s| writes_to_disk(s)) && !confirm_safe_write(&rest) {
+        println!("Command aborted.");
+        return Ok(1);
+    }
     let code = if stages.len() == 1 {
-        run_simple(&rest, &inline_env, jobs, background, vars, &heredoc_bodies).await
+        run_simple(&rest, &inline_env, &mut ctx.jobs, background, &mut ctx.vars, &heredoc_bodies).await
     } else {
-        run_pipeline(&stages, &inline_env, jobs, background, vars, &heredoc_bodies).await
+        run_pipeline(&stages, &inline_env, &mut ctx.jobs, background, &mut ctx.vars, &heredoc_bodies).await
     }?;
 
-    vars.last_exit = code;
+    if subshell.is_some() {
+        // Podpowłoka mogła zostawić kolory / tryb kursora — wyczyść przed promptem
+        eprintln!("\x1b[0m\x1b[?25h\x1b[38;5;244m(back in hsh)\x1b[0m");
+    }
+
+    // 15. [sudo] auto_retry — po odmowie dostępu zaproponuj ponowienie przez sudo
+    let code = match sudo_retry(&rest, &stages, background, &mut ctx.vars) {
+        Some(retry) => {
+            let retry_stages = split_pipeline(&retry);
+            run_pipeline(&retry_stages, &inline_env, &mut ctx.jobs, false, &mut ctx.vars, &heredoc_bodies).await?
+        }
+        None => code,
+    };
+
+    ctx.vars.last_exit = code;
     Ok(code)
 }
 
@@ -677,15 +752,30 @@ async fn run_simple(
     heredoc_bodies: &HashMap<String, String>,
 ) -> io::Result<i32> {
     let (clean_cmd, redirects) = parse_redirections(cmd);
+    vars.permission_denied = false;
 
-    let raw: Vec<String> = shlex::split(&clean_cmd).unwrap_or_default();
-    if raw.is_empty() { return Ok(0); }
-    let parts   = expand_globs(raw.into_iter().map(|a| expand_tilde(&a)).collect());
+    let parts = expand_words(&clean_cmd);
+    if parts.is_empty() { return Ok(0); }
     let program = parts[0].clone();
     let argv: Vec<String> = parts[1..].to_vec();
 
-    if let Some(code) = dispatch_native(&program, &argv) {
-        vars.last_exit = code;
+    // Natywne komendy działają w procesie powłoki — przekierowania (> plik,
+    // heredoc) zakładamy na czas ich wykonania i potem przywracamy
+    if is_native(&program) {
+        let guard = match apply_in_shell(&redirects, heredoc_bodies) {
+            Ok(g)  => g,
+            Err(e) => {
+                vars.permission_denied = e.kind() == io::ErrorKind::PermissionDenied;
+                eprintln!("hsh: {}", e);
+                vars.last_exit = 1;
+                return Ok(1);
+            }
+        };
+        let code = dispatch_native(&program, &argv).unwrap_or(0);
+        drop(guard);
+        vars.permission_denied = code != 0 && crate::builtins_native::last_denied();
+        vars.last_exit  = code;
+        vars.pipestatus = vec![code];
         return Ok(code);
     }
 
@@ -693,6 +783,12 @@ async fn run_simple(
     builder.args(&argv);
     for (k, v) in inline_env { builder.env(k, v); }
 
+    // Jawne przekierowanie stderr wygrywa z color_stderr i auto_retry
+    let pty = tap_stderr(&mut builder, vars, background, &redirects);
+    let denied = Arc::new(AtomicBool::new(false));
+    // Zadanie w tle dostaje własną grupę procesów — Ctrl+C przy prompcie go nie zabije
+    if background { builder.process_group(0); }
+
     let redirects_for_child: Vec<Redirect> = redirects;
     let heredocs_for_child = heredoc_bodies.clone();
 
@@ -704,19 +800,37 @@ async fn run_simple(
         }
     }
 
-    match builder.spawn() {
+    // Builder trzyma kopię slave'a pseudoterminala — bez zamknięcia tee_pty
+    // nie dostałby końca strumienia
+    let spawned = builder.spawn();
+    drop(builder);
+    match spawned {
         Ok(mut child) => {
             if background {
-                let pid = child.id();
-                jobs.add(pid, &format!("{} {}", program, argv.join(" ")));
+                jobs.add_children(vec![child], &format!("{} {}", program, argv.join(" ")));
                 Ok(0)
             } else {
+                let painter = match pty {
+                    Some(master) => Some(tee_pty(master, &denied)),
+                    None         => paint_stderr(&mut child, vars.color_stderr.as_deref(), &denied),
+                };
                 let status = child.wait()?;
-                let code = status.code().unwrap_or(1);
-                vars.last_exit = code;
+                if let Some(p) = painter { let _ = p.join(); }
+                let code = exit_code(&status);
+                vars.permission_denied = code != 0 && denied.load(Ordering::Relaxed);
+                vars.last_exit  = code;
+                vars.pipestatus = vec![code];
                 Ok(code)
             }
         }
+        // Nieudane przekierowanie (`cmd < brak`) to nie brakująca komenda — kod 1
+        Err(e) if failing_target(&redirects_for_child).is_some() => {
+            vars.permission_denied = e.kind() == io::ErrorKind::PermissionDenied;
+            let target = failing_target(&redirects_for_child).unwrap_or_default();
+            eprintln!("hsh: {}: {}", target, io_error_text(&e));
+            vars.last_exit = 1;
+            Ok(1)
+        }
         Err(e) if e.kind() == io::ErrorKind::NotFound => {
             eprintln!("hsh: {}: command not found", program);
             vars.last_exit = 127;
@@ -747,8 +861,13 @@ async fn run_pipeline(
         return run_simple(&stages[0], inline_env, jobs, background, vars, heredoc_bodies).await;
     }
 
-    let mut children: Vec<std::process::Child> = Vec::with_capacity(stages.len());
+    // None — etap, którego nie udało się uruchomić (kod w `spawn_codes`)
+    let mut children: Vec<Option<std::process::Child>> = Vec::with_capacity(stages.len());
+    let mut spawn_codes: Vec<i32> = Vec::with_capacity(stages.len());
     let mut prev_stdout: Option<std::process::ChildStdout> = None;
+    let mut painters = Vec::new();
+    let denied = Arc::new(AtomicBool::new(false));
+    vars.permission_denied = false;
 
     for (i, stage) in stages.iter().enumerate() {
         let is_last  = i == stages.len() - 1;
@@ -756,14 +875,20 @@ async fn run_pipeline(
 
         let (clean_stage, redirects) = parse_redirections(stage);
 
-        let stdin_cfg: Stdio = prev_stdout.take()
-            .map(Stdio::from)
-            .unwrap_or_else(Stdio::inherit);
+        // Po nieudanym etapie następny czyta pusty strumień, nie terminal
+        let stdin_cfg: Stdio = match prev_stdout.take() {
+            Some(out)           => Stdio::from(out),
+            None if is_first    => Stdio::inherit(),
+            None                => Stdio::null(),
+        };
         let stdout_cfg: Stdio = if is_last { Stdio::inherit() } else { Stdio::piped() };
 
-        let raw: Vec<String> = shlex::split(&clean_stage).unwrap_or_default();
-        let parts = expand_globs(raw.into_iter().map(|a| expand_tilde(&a)).collect());
-        if parts.is_empty() { continue; }
+        let parts = expand_words(&clean_stage);
+        if parts.is_empty() {
+            children.push(None);
+            spawn_codes.push(0);
+            continue;
+        }
 
         let redirects_for_child: Vec<Redirect> = redirects;
         let heredocs_for_child  = heredoc_bodies.clone();
@@ -771,6 +896,12 @@ async fn run_pipeline(
         let mut cmd = std::process::Command::new(&parts[0]);
         cmd.args(&parts[1..]).stdin(stdin_cfg).stdout(stdout_cfg);
         if is_first { for (k, v) in inline_env { cmd.env(k, v); } }
+        // `cmd 2>&1 | grep` — jawne przekierowanie stderr wyłącza kolorowanie etapu
+        let pty = tap_stderr(&mut cmd, vars, background, &redirects_for_child);
+        // Potok w tle: wszystkie etapy w grupie procesów pierwszego
+        if background {
+            cmd.process_group(children.iter().flatten().next().map(|c| c.id() as i32).unwrap_or(0));
+        }
 
         if !redirects_for_child.is_empty() {
             let r = redirects_for_child.clone();
@@ -778,106 +909,281 @@ async fn run_pipeline(
             unsafe { cmd.pre_exec(move || apply_redirections(&r, &h)); }
         }
 
+        // Nieudany etap nie przerywa potoku — jak w bash pozostałe etapy
+        // działają dalej, a kod błędu trafia do PIPESTATUS
         let mut child = match cmd.spawn() {
             Ok(c) => c,
-            Err(e) if e.kind() == io::ErrorKind::NotFound => {
-                eprintln!("hsh: {}: command not found", &parts[0]);
-                vars.last_exit = 127;
-                return Ok(127);
-            }
             Err(e) => {
-                eprintln!("hsh: {}: {}", &parts[0], e);
-                vars.last_exit = 1;
-                return Ok(1);
+                let code = if let Some(target) = failing_target(&redirects_for_child) {
+                    if e.kind() == io::ErrorKind::PermissionDenied { denied.store(true, Ordering::Relaxed); }
+                    eprintln!("hsh: {}: {}", target, io_error_text(&e));
+                    1
+                } else if e.kind() == io::ErrorKind::NotFound {
+                    eprintln!("hsh: {}: command not found", &parts[0]);
+                    127
+                } else {
+                    eprintln!("hsh: {}: {}", &parts[0], e);
+                    126
+                };
+                children.push(None);
+                spawn_codes.push(code);
+                continue;
             }
         };
 
         if !is_last { prev_stdout = child.stdout.take(); }
-        children.push(child);
+        let painter = match pty {
+            Some(master) => Some(tee_pty(master, &denied)),
+            None         => paint_stderr(&mut child, vars.color_stderr.as_deref(), &denied),
+        };
+        painters.extend(painter);
+        children.push(Some(child));
+        spawn_codes.push(0);
     }
 
     if background {
-        if let Some(first) = children.first() {
-            jobs.add(first.id(), &stages.join(" | "));
+        let started: Vec<std::process::Child> = children.into_iter().flatten().collect();
+        if !started.is_empty() {
+            jobs.add_children(started, &stages.join(" | "));
         }
         return Ok(0);
     }
 
-    let mut last_code = 0i32;
-    for mut child in children {
+    let mut codes = Vec::with_capacity(children.len());
+    for (child, spawn_code) in children.into_iter().zip(spawn_codes) {
+        let Some(mut child) = child else { codes.push(spawn_code); continue };
         match child.wait() {
-            Ok(s)  => last_code = s.code().unwrap_or(1),
-            Err(e) => eprintln!("hsh: wait: {}", e),
+            Ok(s)  => codes.push(exit_code(&s)),
+            Err(e) => { eprintln!("hsh: wait: {}", e); codes.push(1); }
         }
     }
-    vars.last_exit = last_code;
+    for p in painters { let _ = p.join(); }
+    let last_code = codes.last().copied().unwrap_or(0);
+    vars.permission_denied = codes.iter().any(|&c| c != 0) && denied.load(Ordering::Relaxed);
+    vars.last_exit  = last_code;
+    vars.pipestatus = codes;
     Ok(last_code)
 }
 
+/// Kod wyjścia jak w bash: proces zabity sygnałem N daje 128+N (Ctrl+C → 130)
+pub fn exit_code(status: &std::process::ExitStatus) -> i32 {
+    use std::os::unix::process::ExitStatusExt;
+    status.code().unwrap_or_else(|| 128 + status.signal().unwrap_or(0))
+}
+
+/// Nazwa sygnału dla kodu 128+N (139 → SIGSEGV); None dla zwykłych kodów
+pub fn signal_name(code: i32) -> Option<&'static str> {
+    (code > 128)
+        .then(|| nix::sys::signal::Signal::try_from(code - 128).ok())
+        .flatten()
+        .map(|sig| sig.as_str())
+}
+
+/// Przechwyć stderr komendy, jeśli trzeba: color_stderr albo [sudo] auto_retry
+/// (wykrywanie odmowy dostępu). color_stderr — potok, linie są przepisywane
+/// w kolorze. Samo auto_retry przy stderr na terminalu — pseudoterminal:
+/// komenda dalej widzi tty (kolory, paski postępu), a bajty przechodzą bez
+/// zmian. Zwraca master pseudoterminala do odczytu w `tee_pty`.
+fn tap_stderr(
+    cmd:        &mut std::process::Command,
+    vars:       &ShellVars,
+    background: bool,
+    redirects:  &[Redirect],
+) -> Option<File> {
+    if background || redirects_stderr(redirects) { return None; }
+    if vars.color_stderr.is_none() {
+        // Ponowienie przez sudo jest możliwe tylko interaktywnie i nie dla roota
+        let retry = vars.sudo.auto_retry && !vars.posix
+            && unsafe { libc::getuid() != 0 && libc::isatty(libc::STDIN_FILENO) == 1 };
+        if !retry { return None; }
+        if unsafe { libc::isatty(libc::STDERR_FILENO) } == 1 {
+            if let Some((master, slave)) = open_pty() {
+                cmd.stderr(Stdio::from(slave));
+                return Some(master);
+            }
+        }
+    }
+    cmd.stderr(Stdio::piped());
+    None
+}
+
+/// Para master/slave pseudoterminala o rozmiarze terminala powłoki.
+/// Oba końce z CLOEXEC — slave trafia do dziecka tylko jako jego fd 2.
+fn open_pty() -> Option<(File, OwnedFd)> {
+    let (mut master, mut slave) = (-1, -1);
+    let mut size: libc::winsize = unsafe { std::mem::zeroed() };
+    unsafe {
+        libc::ioctl(libc::STDERR_FILENO, libc::TIOCGWINSZ, &mut size);
+        if libc::openpty(&mut master, &mut slave, std::ptr::null_mut(), std::ptr::null(), &size) != 0 {
+            return None;
+        }
+        libc::fcntl(master, libc::F_SETFD, libc::FD_CLOEXEC);
+        libc::fcntl(slave, libc::F_SETFD, libc::FD_CLOEXEC);
+        Some((File::from_raw_fd(master), OwnedFd::from_raw_fd(slave)))
+    }
+}
+
+/// Przepisuj stderr dziecka z pseudoterminala bez zmian (bez buforowania linii —
+/// paski postępu z `\r` działają) i zaznacz w `denied` komunikat o odmowie dostępu
+fn tee_pty(mut master: File, denied: &Arc<AtomicBool>) -> std::thread::JoinHandle<()> {
+    let denied = Arc::clone(denied);
+    std::thread::spawn(move || {
+        let mut out  = io::stderr();
+        let mut buf  = [0u8; 4096];
+        // Koniec poprzedniego kawałka — komunikat może być rozcięty między odczytami
+        let mut tail = Vec::new();
+        // Po zamknięciu ostatniej kopii slave'a read zwraca EIO
+        while let Ok(n) = master.read(&mut buf) {
+            if n == 0 { break; }
+            let _ = out.write_all(&buf[..n]);
+            let _ = out.flush();
+            tail.extend_from_slice(&buf[..n]);
+            let text = String::from_utf8_lossy(&tail);
+            if DENIED_MESSAGES.iter().any(|m| text.contains(m)) {
+                denied.store(true, Ordering::Relaxed);
+            }
+            let keep = tail.len().saturating_sub(64);
+            tail.drain(..keep);
+        }
+    })
+}
+
+/// Komunikaty, po których ponowienie przez sudo ma szansę pomóc
+const DENIED_MESSAGES: &[&str] = &[
+    "Permission denied",
+    "Operation not permitted",
+    "Access denied",
+    "Interactive authentication required",
+    "must be root",
+    "must be superuser",
+];
+
+/// Przepisuj przechwycone stderr dziecka — w kolorze `color` (error_color motywu,
+/// gdy color_stderr) — i zaznacz w `denied`, jeśli padł komunikat o odmowie dostępu.
+/// Wywoływane tylko gdy stderr jest `Stdio::piped()`, czyli komenda nie ma
+/// własnego przekierowania stderr — w przeciwnym razie `take()` zwraca None.
+fn paint_stderr(
+    child:  &mut std::process::Child,
+    color:  Option<&str>,
+    denied: &Arc<AtomicBool>,
+) -> Option<std::thread::JoinHandle<()>> {
+    let err = child.stderr.take()?;
+    let color = color.map(str::to_string);
+    let denied = Arc::clone(denied);
+    Some(std::thread::spawn(move || {
+        use std::io::BufRead;
+        let mut out = io::stderr();
+        for line in io::BufReader::new(err).split(b'\n').map_while(Result::ok) {
+            let text = String::from_utf8_lossy(&line);
+            if DENIED_MESSAGES.iter().any(|m| text.contains(m)) {
+                denied.store(true, Ordering::Relaxed);
+            }
+            if let Some(color) = &color {
+                let _ = out.write_all(color.as_bytes());
+                let _ = out.write_all(&line);
+                let _ = out.write_all(b"\x1b[0m\n");
+            } else {
+                let _ = out.write_all(&line);
+                let _ = out.write_all(b"\n");
+            }
+        }
+    }))
+}
+
+/// Oddziel linię shebang (`#!…`) od treści skryptu.
+/// Zwraca (słowa interpretera, reszta pliku); bez shebangu — (None, całość).
+pub fn split_shebang(contents: &str) -> (Option<Vec<String>>, &str) {
+    let Some(rest) = contents.strip_prefix("#!") else { return (None, contents) };
+    let (first, body) = rest.split_once('\n').unwrap_or((rest, ""));
+    let words: Vec<String> = first.split_whitespace().map(String::from).collect();
+    if words.is_empty() { (None, body) } else { (Some(words), body) }
+}
+
+/// Czy shebang wskazuje na hsh (`#!/usr/bin/hsh`, `#!/usr/bin/env hsh`)
+pub fn is_hsh_interpreter(words: &[String]) -> bool {
+    let base = |w: &str| Path::new(w).file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or_default();
+    match words.first().map(|w| base(w)) {
+        Some(ref b) if b == "env" => words.iter().skip(1)
+            .find(|w| !w.starts_with('-'))
+            .map(|w| base(w) == "hsh")
+            .unwrap_or(false),
+        Some(b) => b == "hsh",
+        None    => true,
+    }
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Source — obsługa plików .sh z walidacją składni
 // ─────────────────────────────────────────────────────────────────────────────
 
 async fn run_source(
     file_path:     &str,
-    aliases:       &HashMap<String, String>,
-    rl:            &mut Editor<ShellHelper, rustyline::history::FileHistory>,
-    prev_dir:      &mut Option<PathBuf>,
-    jobs:          &mut JobTable,
-    vars:          &mut ShellVars,
-    smart_hints:   &mut SmartHints,
-    shell_history:  &mut ShellHistory,
-    path_cache:    &PathCache,
-    functions:     &mut FunctionTable,
-    dry_run:       bool,
+    ctx:          &mut ShellCtx,
+    rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
+    prev_dir:     &mut Option<PathBuf>,
+    smart_hints:  &mut SmartHints,
+    path_cache:   &PathCache,
+    dry_run:      bool,
 ) -> io::Result<i32> {
     let contents = read_to_string(file_path).map_err(|e| {
         eprintln!("hsh: source: {}: {}", file_path, e);
         e
     })?;
+    // source ignoruje shebang — plik zawsze wykonuje bieżąca powłoka
+    let (_, contents) = split_shebang(&contents);
 
     // Walidacja składni dla plików .sh
     let is_sh = file_path.ends_with(".sh") || file_path.ends_with(".hsh");
     if is_sh {
-        let checks = validate_script(&contents);
+        let checks = validate_script(contents);
         let has_errors = print_syntax_errors(file_path, &checks);
-        if has_errors && vars.errexit {
+        if has_errors && ctx.vars.errexit {
             eprintln!("hsh: source: {} zawiera błędy składni, przerywam", file_path);
             return Ok(1);
         }
     }
 
-    // Wykonaj linię po linii, łącząc wieloliniowe konstrukty
+    // Wykonaj linię po linii, łącząc wieloliniowe konstrukty (if…fi,
+    // funkcje, pętle) tak samo jak REPL przy Enter
     let mut multiline_buf = String::new();
     let mut last_code     = 0i32;
+    let mut heredoc       = None::<String>;
 
     for raw_line in contents.lines() {
         let line = raw_line.trim_end();
 
-        // Kontynuacja linii z \
-        if line.ends_with('\\') {
+        // Treść heredoca trafia do bufora bez zmian, aż do delimitera
+        if let Some(delim) = &heredoc {
+            if line.trim() == delim { heredoc = None; }
+            multiline_buf.push_str(raw_line);
+            multiline_buf.push('\n');
+            if heredoc.is_some() { continue; }
+        } else if line.ends_with('\\') {
+            // Kontynuacja linii z \
             multiline_buf.push_str(&line[..line.len() - 1]);
             multiline_buf.push(' ');
             continue;
-        }
+        } else {
+            // Komentarze poza blokiem pomijamy od razu
+            if multiline_buf.trim().is_empty() && line.trim_start().starts_with('#') { continue; }
 
-        multiline_buf.push_str(line);
+            multiline_buf.push_str(line);
+            multiline_buf.push('\n');
+            heredoc = heredoc_delimiter(strip_line_comment(line));
+            if heredoc.is_some() { continue; }
+        }
+        if !is_complete_input(&strip_comments(&multiline_buf)) { continue; }
         let to_exec = std::mem::take(&mut multiline_buf);
 
         let tl = to_exec.trim();
-        if tl.is_empty() || tl.starts_with('#') { continue; }
-
-        if vars.xtrace {
-            eprintln!("+ {}", tl);
-        }
+        if tl.is_empty() { continue; }
 
         last_code = Box::pin(run_line(
-            &to_exec, aliases, rl, prev_dir, jobs, vars,
-            smart_hints, shell_history, path_cache, functions, dry_run,
+            &to_exec, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
         ))
         .await?;
 
-        if vars.errexit && last_code != 0 { break; }
+        if ctx.vars.errexit && last_code != 0 { break; }
     }
 
     // Jeśli zostały dane w buforze (np. brak końcowego newline)
@@ -885,8 +1191,7 @@ async fn run_source(
         let tl = multiline_buf.trim();
         if !tl.starts_with('#') {
             last_code = Box::pin(run_line(
-                &multiline_buf, aliases, rl, prev_dir, jobs, vars,
-                smart_hints, shell_history, path_cache, functions, dry_run,
+                &multiline_buf, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
             ))
             .await?;
         }
@@ -896,39 +1201,268 @@ async fn run_source(
 }
 
 // ─────────────────────────────────────────────────────────────────────────────
-// Utilities
+// fc — edycja historii w $EDITOR
 // ─────────────────────────────────────────────────────────────────────────────
 
-pub fn expand_tilde(s: &str) -> String {
-    if s.starts_with('~') {
-        if let Ok(home) = env::var("HOME") {
-            return format!("{}{}", home, &s[1..]);
+/// `fc [-e editor] [-lnr] [first [last]]` jak w bash: -l wypisuje zakres,
+/// bez -l otwiera go w edytorze i wykonuje wynik; `-e -` (lub -s) wykonuje
+/// bez edycji. first/last: numer, -N (N-ta od końca) lub prefiks komendy.
+async fn run_fc(
+    input:         &str,
+    ctx:          &mut ShellCtx,
+    rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
+    prev_dir:     &mut Option<PathBuf>,
+    smart_hints:  &mut SmartHints,
+    path_cache:   &PathCache,
+    dry_run:      bool,
+) -> io::Result<i32> {
+    let args = match tokenize::split_args(input) {
+        Ok(a)  => a,
+        Err(e) => { eprintln!("fc: {}", e); return Ok(1); }
+    };
+    let (mut list, mut numbers, mut reverse) = (false, true, false);
+    let mut editor: Option<String> = None;
+    let mut range: Vec<String> = Vec::new();
+    let mut it = args.into_iter().skip(1);
+    while let Some(arg) = it.next() {
+        match arg.as_str() {
+            "-e" => match it.next() {
+                Some(e) => editor = Some(e),
+                None    => { eprintln!("fc: -e: option requires an argument"); return Ok(2); }
+            },
+            "-s" => editor = Some("-".to_string()),
+            a if a.starts_with('-') && a.len() > 1 && a[1..].chars().all(|c| "lnr".contains(c)) => {
+                list    |= a.contains('l');
+                numbers &= !a.contains('n');
+                reverse |= a.contains('r');
+            }
+            _ => range.push(arg),
         }
     }
-    s.to_string()
+
+    // Bieżące `fc` jest już w historii — nie licz go jako ostatniej komendy
+    let mut last = ctx.history.len();
+    if last > 0 && ctx.history.nth(last).map(|c| c == "fc" || c.starts_with("fc ")).unwrap_or(false) {
+        last -= 1;
+    }
+    if last == 0 {
+        eprintln!("fc: history is empty");
+        return Ok(1);
+    }
+
+    let resolve = |spec: &str| -> Option<usize> {
+        match spec.parse::<i64>() {
+            Ok(n) if n < 0 => (last as i64 + 1 + n).try_into().ok().filter(|&i: &usize| i >= 1),
+            Ok(n)          => Some((n as usize).clamp(1, last)),
+            Err(_)         => (1..=last).rev()
+                .find(|&i| ctx.history.nth(i).map(|c| c.starts_with(spec)).unwrap_or(false)),
+        }
+    };
+    let default_first = if list { last.saturating_sub(15).max(1) } else { last };
+    let first = match range.first() {
+        Some(s) => match resolve(s) { Some(i) => i, None => { eprintln!("fc: {}: no such event", s); return Ok(1); } },
+        None    => default_first,
+    };
+    let end = match range.get(1) {
+        Some(s) => match resolve(s) { Some(i) => i, None => { eprintln!("fc: {}: no such event", s); return Ok(1); } },
+        None if list => last,
+        None    => first,
+    };
+    let mut indices: Vec<usize> = if first <= end { (first..=end).collect() } else { (end..=first).rev().collect() };
+    if reverse { indices.reverse(); }
+
+    if list {
+        for i in indices {
+            let cmd = ctx.history.nth(i).unwrap_or_default();
+            if numbers { println!("{}\t {}", i, cmd); } else { println!("\t {}", cmd); }
+        }
+        return Ok(0);
+    }
+
+    let commands: Vec<String> = indices.iter().filter_map(|&i| ctx.history.nth(i)).collect();
+    let editor = editor
+        .or_else(|| env::var("FCEDIT").ok())
+        .or_else(|| env::var("EDITOR").ok())
+        .or_else(|| ctx.vars.editor.clone())
+        .filter(|e| !e.is_empty())
+        .unwrap_or_else(|| "vi".to_string());
+
+    // Bez edycji (fc -s / -e -) — wykonaj od razu
+    if editor == "-" {
+        let script = commands.join("\n");
+        println!("{}", script);
+        if dry_run { return Ok(0); }
+        return Box::pin(run_line(
+            &script, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
+        )).await;
+    }
+
+    let tmp = env::temp_dir().join(format!("hsh-fc-{}.hsh-edit", std::process::id()));
+    if let Err(e) = std::fs::write(&tmp, commands.join("\n") + "\n") {
+        eprintln!("fc: {}: {}", tmp.display(), e);
+        return Ok(1);
+    }
+    let words = tokenize::split(&editor).unwrap_or_else(|| vec![editor.clone()]);
+    let status = std::process::Command::new(&words[0]).args(&words[1..]).arg(&tmp).status();
+    let edited = std::fs::read_to_string(&tmp).unwrap_or_default();
+    let _ = std::fs::remove_file(&tmp);
+    match status {
+        Ok(s) if s.success() => {}
+        Ok(s) => {
+            // Jak w bash: edytor zakończony błędem (np. :cq w vim) = anulowanie
+            eprintln!("fc: {} exited with status {} — nothing executed", editor, s.code().unwrap_or(1));
+            return Ok(1);
+        }
+        Err(e) => {
+            eprintln!("fc: {}: {}", words[0], e);
+            return Ok(127);
+        }
+    }
+
+    let mut last_code = 0;
+    for line in edited.lines().map(str::trim).filter(|l| !l.is_empty() && !l.starts_with('#')) {
+        println!("{}", line);
+        if dry_run { continue; }
+        ctx.history.add(line);
+        last_code = Box::pin(run_line(
+            line, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
+        )).await?;
+        if ctx.vars.errexit && last_code != 0 { break; }
+    }
+    Ok(last_code)
 }
 
-fn expand_globs(args: Vec<String>) -> Vec<String> {
-    let mut result = Vec::new();
-    for arg in args {
-        if arg.contains('*') || arg.contains('?') || (arg.contains('{') && arg.contains('}')) {
-            match glob::glob(&arg) {
-                Ok(paths) => {
-                    let exp: Vec<String> = paths
-                        .filter_map(|p| p.ok())
-                        .map(|p| p.to_string_lossy().to_string())
-                        .collect();
-                    if exp.is_empty() { result.push(arg); } else { result.extend(exp); }
-                }
-                Err(_) => result.push(arg),
+/// `repeat N komenda` — uruchom komendę N razy w bieżącej powłoce. Z `set -e`
+/// kończy na pierwszym błędzie; wynik to kod ostatniego uruchomienia, a
+/// nieudane przebiegi są podsumowane na stderr.
+async fn run_repeat(
+    input:         &str,
+    ctx:          &mut ShellCtx,
+    rl:           &mut Editor<ShellHelper, rustyline::history::FileHistory>,
+    prev_dir:     &mut Option<PathBuf>,
+    smart_hints:  &mut SmartHints,
+    path_cache:   &PathCache,
+    dry_run:      bool,
+) -> io::Result<i32> {
+    let rest = input["repeat".len()..].trim_start();
+    let (count, command) = rest.split_once(char::is_whitespace).unwrap_or((rest, ""));
+    let command = command.trim();
+    let Ok(count) = ctx.vars.expand(count).parse::<u64>() else {
+        eprintln!("repeat: {}: invalid count", count);
+        return Ok(2);
+    };
+    if command.is_empty() {
+        eprintln!("repeat: usage: repeat N command");
+        return Ok(2);
+    }
+
+    let (mut last_code, mut failed) = (0, 0u64);
+    for run in 1..=count {
+        last_code = Box::pin(run_line(
+            command, ctx, rl, prev_dir, smart_hints, path_cache, dry_run,
+        )).await?;
+        if last_code != 0 {
+            failed += 1;
+            if ctx.vars.errexit {
+                eprintln!("repeat: stopped at run {} of {} (exit {}), {} succeeded", run, count, last_code, run - 1);
+                return Ok(last_code);
             }
-        } else {
-            result.push(arg);
         }
     }
+    if failed > 0 {
+        eprintln!("repeat: {} of {} runs failed", failed, count);
+    }
+    ctx.vars.last_exit = last_code;
+    Ok(last_code)
+}
+
+// ─────────────────────────────────────────────────────────────────────────────
+// Utilities
+// ─────────────────────────────────────────────────────────────────────────────
+
+/// Usuń komentarze (`# ...` do końca linii) spoza cudzysłowów.
+/// `#` jest komentarzem tylko na początku słowa (po białym znaku lub na
+/// początku linii) — `foo#bar`, `$#` i `${#VAR}` zostają bez zmian.
+/// Treść heredoców nie jest ruszana.
+pub fn strip_comments(input: &str) -> String {
+    let mut result        = String::with_capacity(input.len());
+    let mut heredoc_delim = None::<String>;
+
+    for (n, line) in input.split('\n').enumerate() {
+        if n > 0 { result.push('\n'); }
+
+        if let Some(delim) = &heredoc_delim {
+            if line.trim() == delim { heredoc_delim = None; }
+            result.push_str(line);
+            continue;
+        }
+
+        let stripped = strip_line_comment(line);
+        heredoc_delim = heredoc_delimiter(stripped);
+        result.push_str(stripped);
+    }
     result
 }
 
+fn strip_line_comment(line: &str) -> &str {
+    let mut in_s    = false;
+    let mut in_d    = false;
+    let mut escaped = false;
+    let mut prev_ws = true;
+
+    for (idx, c) in line.char_indices() {
+        if escaped {
+            escaped = false;
+            prev_ws = false;
+            continue;
+        }
+        match c {
+            '\\' if !in_s          => escaped = true,
+            '\'' if !in_d          => in_s = !in_s,
+            '"'  if !in_s          => in_d = !in_d,
+            '#'  if !in_s && !in_d && prev_ws => return line[..idx].trim_end(),
+            _ => {}
+        }
+        prev_ws = c.is_whitespace();
+    }
+    line
+}
+
+/// Delimiter heredoca otwieranego w tej linii (`<<EOF`, `<<-'EOF'`).
+/// `<<` w cudzysłowach lub po `\` nie otwiera heredoca (`echo "a << b"`).
+fn heredoc_delimiter(line: &str) -> Option<String> {
+    let bytes   = line.as_bytes();
+    let mut in_s    = false;
+    let mut in_d    = false;
+    let mut escaped = false;
+    let mut i = 0;
+    let pos = loop {
+        let c = *bytes.get(i)?;
+        if escaped {
+            escaped = false;
+        } else {
+            match c {
+                b'\\' if !in_s => escaped = true,
+                b'\'' if !in_d => in_s = !in_s,
+                b'"'  if !in_s => in_d = !in_d,
+                b'<' if !in_s && !in_d && bytes.get(i + 1) == Some(&b'<') => {
+                    if bytes.get(i + 2) != Some(&b'<') { break i; }
+                    i += 2; // here-string <<<
+                }
+                _ => {}
+            }
+        }
+        i += 1;
+    };
+    let after = line[pos + 2..].trim_start_matches('-').trim_start();
+    let delim: String = after
+        .trim_start_matches(['\'', '"'])
+        .chars()
+        .take_while(|c| !c.is_whitespace() && !"'\";".contains(*c))
+        .collect();
+    if delim.is_empty() { None } else { Some(delim) }
+}
+
 fn expand_for_items(items: &[String], vars: &ShellVars) -> Vec<String> {
     let mut result = Vec::new();
     for item in items {
@@ -947,11 +1481,64 @@ fn expand_for_items(items: &[String], vars: &ShellVars) -> Vec<String> {
             result.extend(vars.positional.clone());
             continue;
         }
+        // Niecytowane `$(…)` / `$VAR` dzielimy na słowa jak w sh
+        if item.contains('$') && !item.starts_with(['"', '\'']) {
+            result.extend(expanded.split_whitespace().map(String::from));
+            continue;
+        }
         result.push(expanded);
     }
     result
 }
 
+/// Czy linia to pojedyncza ścieżka do istniejącego katalogu (auto_cd).
+/// Tylko słowa wyglądające na ścieżkę (`/`, `.`, `~`) i niebędące aliasem,
+/// funkcją, builtinem ani programem z PATH — auto_cd niczego nie przesłania.
+fn is_auto_cd(input: &str, aliases: &HashMap<String, String>, functions: &FunctionTable) -> bool {
+    let tokens = tokenize::tokenize(input);
+    let [tok] = tokens.as_slice() else { return false };
+    if tok.kind != tokenize::TokenKind::Word || tok.unclosed { return false; }
+    let word = tok.value.as_str();
+    let path_like = word.contains('/') || word.starts_with('.') || word.starts_with('~');
+    if !path_like || aliases.contains_key(word) || functions.contains(word)
+        || crate::builtins::is_builtin(word)
+        || (!word.contains('/') && crate::helper::find_in_path(word).is_some())
+    {
+        return false;
+    }
+    Path::new(&expand_tilde(word)).is_dir()
+}
+
+/// Nazwa pierwszego builtinu stanu powłoki użytego jako etap potoku.
+fn pipeline_builtin(input: &str) -> Option<String> {
+    let stages = split_pipeline(input);
+    if stages.len() < 2 { return None; }
+    stages.iter()
+        .filter_map(|s| tokenize::split(s).and_then(|w| w.into_iter().next()))
+        .find(|w| crate::builtins::is_shell_state_builtin(w))
+}
+
+/// Linia po pełnym rozwinięciu (tylda, globy) — dla trybu verbose.
+/// Argumenty ze spacjami lub znakami specjalnymi są cytowane.
+fn expanded_line(stages: &[String]) -> String {
+    let quote = |w: &str| shlex::try_quote(w).map(|q| q.into_owned()).unwrap_or_else(|_| w.to_string());
+    stages
+        .iter()
+        .map(|stage| {
+            let mut out: Vec<String> = Vec::new();
+            for tok in tokenize::tokenize(stage) {
+                if tok.kind == tokenize::TokenKind::Operator {
+                    out.push(tok.value);
+                } else {
+                    out.extend(expand_words(&stage[tok.start..tok.end]).iter().map(|w| quote(w)));
+                }
+            }
+            out.join(" ")
+        })
+        .collect::<Vec<_>>()
+        .join(" | ")
+}
+
 fn split_pipeline(input: &str) -> Vec<String> {
     let mut stages  = Vec::new();
     let mut current = String::new();
@@ -987,6 +1574,9 @@ fn split_compound(input: &str) -> Vec<(String, Option<String>)> {
     let mut current = String::new();
     let mut in_s    = false;
     let mut in_d    = false;
+    // Wnętrze $(…) i `…` należy do podstawianej komendy — nie dziel go
+    let mut depth   = 0usize;
+    let mut in_bt   = false;
     let chars: Vec<char> = input.chars().collect();
     let mut i = 0;
 
@@ -1002,6 +1592,13 @@ fn split_compound(input: &str) -> Vec<(String, Option<String>)> {
         match chars[i] {
             '\'' if !in_d => { in_s = !in_s; current.push('\''); i += 1; }
             '"'  if !in_s => { in_d = !in_d; current.push('"');  i += 1; }
+            '`'  if !in_s => { in_bt = !in_bt; current.push('`'); i += 1; }
+            '$'  if !in_s && chars.get(i + 1) == Some(&'(') => {
+                depth += 1; current.push_str("$("); i += 2;
+            }
+            '('  if !in_s && depth > 0 => { depth += 1; current.push('('); i += 1; }
+            ')'  if !in_s && depth > 0 => { depth -= 1; current.push(')'); i += 1; }
+            _    if depth > 0 || in_bt => { current.push(chars[i]); i += 1; }
             ';'  if !in_s && !in_d => { flush!(Some(";".into())); i += 1; }
             '&'  if !in_s && !in_d && chars.get(i+1) == Some(&'&') => {
                 flush!(Some("&&".into())); i += 2;
@@ -1016,9 +1613,13 @@ fn split_compound(input: &str) -> Vec<(String, Option<String>)> {
     if result.is_empty() { vec![(input.trim().to_string(), None)] } else { result }
 }
 
-fn is_script_construct(input: &str) -> bool {
+fn starts_script_keyword(input: &str) -> bool {
     let first = input.split_whitespace().next().unwrap_or("");
     matches!(first, "if" | "for" | "while" | "until" | "case" | "function")
+}
+
+fn is_script_construct(input: &str) -> bool {
+    starts_script_keyword(input)
         || input.contains("() {")
         || input.contains("(){")
         || (input.contains("()") && input.contains('{'))
@@ -1039,96 +1640,261 @@ fn strip_background_flag(input: &str) -> (bool, String) {
     }
 }
 
-fn expand_alias(input: &str, aliases: &HashMap<String, String>) -> String {
-    let parts: Vec<String> = shlex::split(input).unwrap_or_default();
-    if let Some(first) = parts.first() {
-        if let Some(val) = aliases.get(first.as_str()) {
-            let rest = parts[1..].join(" ");
-            return if rest.is_empty() { val.clone() } else { format!("{} {}", val, rest) };
-        }
-    }
-    input.to_string()
-}
-
-fn check_auto_sudo(input: &str) -> String {
-    if unsafe { libc::getuid() == 0 } { return input.to_string(); }
-    let parts = shlex::split(input).unwrap_or_default();
-    if parts.len() < 2 { return input.to_string(); }
-    if !["vi", "vim", "nano", "emacs"].contains(&parts[0].as_str()) {
+/// Auto-sudo dla edytorów ([sudo] editors) otwierających pliki wymagające roota.
+/// Zwraca `sudo ` + oryginalny napis — bez składania z tokenów, więc cudzysłowy
+/// i escape'y (`vim "/etc/my file.conf"`, `/etc/a\ b`) przechodzą nietknięte.
+fn check_auto_sudo(input: &str, vars: &mut ShellVars) -> String {
+    let files = editor_root_files(input, &vars.sudo);
+    if files.is_empty() {
         return input.to_string();
     }
-    let file = &parts[1];
-    if !file.starts_with("/etc/") && !file.starts_with("/usr/")
-        && !file.starts_with("/var/") && !file.starts_with("/boot/") {
-        return input.to_string();
+    if vars.sudo.always {
+        return format!("sudo {}", input);
     }
-    eprint!("\x1b[1;33m⚠  '{}' requires root. Use sudo? [y/n] \x1b[0m", file);
-    io::stdout().flush().ok();
-    let mut ans = String::new();
-    io::stdin().read_line(&mut ans).ok();
-    if ans.trim().eq_ignore_ascii_case("y") {
+    let question = format!("⚠  '{}' requires root. Use sudo?", files.join("', '"));
+    if ask_confirm("sudo", input, &question, false, &mut vars.confirm_allow, !vars.safe_mode) {
         format!("sudo {}", input)
     } else {
         input.to_string()
     }
 }
 
-/// Nadaj prawa wykonania do .sh i zwaliduj składnię
-fn maybe_chmod_and_validate(cmd: &str) {
-    let first = cmd.split_whitespace().next().unwrap_or("");
-    if first.ends_with(".sh") || first.ends_with(".hsh") {
-        let p = Path::new(first);
-        if let Ok(meta) = p.metadata() {
+/// Pliki z linii edytora ([sudo] editors), które wymagają roota — po zdjęciu
+/// cudzysłowów i escape'ów. Pusto, gdy komenda nie jest edytorem.
+fn editor_root_files(input: &str, sudo: &crate::config::SudoOptions) -> Vec<String> {
+    let parts = tokenize::split(input).unwrap_or_default();
+    if parts.len() < 2 { return Vec::new(); }
+    let editor = parts[0].rsplit('/').next().unwrap_or("");
+    if !sudo.editors.iter().any(|e| e == editor) {
+        return Vec::new();
+    }
+    // Wszystkie argumenty-pliki (`vim -p /etc/hosts /etc/fstab`); flagi i `+N` pomijamy.
+    // Rozwiń ~ przed sprawdzeniem — `vim ~/plik` ma być oceniany po ścieżce docelowej.
+    parts[1..]
+        .iter()
+        .filter(|a| !a.starts_with('-') && !a.starts_with('+'))
+        .map(|a| expand_tilde(a))
+        .filter(|f| needs_root(f, &sudo.protected_paths))
+        .collect()
+}
+
+/// Komenda do ponowienia przez sudo, gdy ostatnia skończyła się odmową dostępu
+/// ([sudo] auto_retry). Nie zgadujemy z góry — decyduje EACCES przy uruchomieniu
+/// lub przekierowaniu albo komunikat na stderr. Przekierowania i potoki otwiera
+/// powłoka, więc takie komendy idą w całości przez `sudo <powłoka> -c '…'`
+/// (ta sama co dla `$(…)`, zob. `ShellVars::delegate_shell`).
+fn sudo_retry(rest: &str, stages: &[String], background: bool, vars: &mut ShellVars) -> Option<String> {
+    if background || vars.posix || !vars.sudo.auto_retry || !vars.permission_denied {
+        return None;
+    }
+    if unsafe { libc::getuid() == 0 || libc::isatty(libc::STDIN_FILENO) != 1 } {
+        return None;
+    }
+    if tokenize::split(rest)?.first().map(|w| w == "sudo").unwrap_or(true) {
+        return None;
+    }
+    let shell_needed = stages.len() > 1 || stages.iter().any(|s| !parse_redirections(s).1.is_empty());
+    let retry = if shell_needed {
+        let shell = shlex::try_join(vars.delegate_shell().iter().map(String::as_str)).ok()?;
+        format!("sudo {} -c {}", shell, shlex::try_quote(rest).ok()?)
+    } else {
+        format!("sudo {}", rest)
+    };
+    let question = "⚠  Permission denied. Retry with sudo?";
+    ask_confirm("sudo", rest, question, false, &mut vars.confirm_allow, !vars.safe_mode).then_some(retry)
+}
+
+/// Czy edycja pliku wymaga roota: leży w katalogu chronionym ([sudo] protected_paths).
+/// Ścieżka względna jest liczona od bieżącego katalogu (`cd /etc; vim hosts`).
+fn needs_root(file: &str, protected: &[String]) -> bool {
+    let path = match env::current_dir() {
+        Ok(cwd) if Path::new(file).is_relative() => cwd.join(file),
+        _ => PathBuf::from(file),
+    };
+    let path = path.to_string_lossy();
+    protected.iter().any(|p| path.starts_with(p.as_str()))
+}
+
+/// Pierwszy wpis z [shell] subshell_commands, od którego zaczyna się komenda
+/// (porównanie słowo po słowie, więc "sudo -s" nie pasuje do "sudo -sh").
+fn subshell_command(cmd: &str, patterns: &[String]) -> Option<String> {
+    let words = tokenize::split(cmd)?;
+    patterns.iter().find_map(|p| {
+        let want: Vec<&str> = p.split_whitespace().collect();
+        let hit = !want.is_empty()
+            && words.len() >= want.len()
+            && want.iter().zip(&words).all(|(w, a)| a == w);
+        if hit { Some(p.clone()) } else { None }
+    })
+}
+
+/// Czy etap potoku zapisuje na dysk: przekierowanie do pliku, komenda
+/// modyfikująca system plików (rm, tee, dd, chmod…) albo `sed -i` / `perl -pi`
+/// (tryb bezpieczny pyta przed uruchomieniem).
+fn writes_to_disk(stage: &str) -> bool {
+    let (clean, redirects) = parse_redirections(stage);
+    let to_file = redirects.iter().any(|r| {
+        matches!(r.kind, RedirectKind::Out | RedirectKind::Append | RedirectKind::OutErr | RedirectKind::AppendErr)
+            && matches!(&r.target, RedirectTarget::File(p) if p != "/dev/null")
+    });
+    let words = tokenize::split(&clean).unwrap_or_default();
+    // sudo rm, env X=1 tee … — liczy się komenda po prefiksie
+    let mut words = words.iter().map(String::as_str).skip_while(|w| {
+        matches!(*w, "sudo" | "doas" | "env" | "command" | "nohup" | "nice" | "time") || w.contains('=')
+    });
+    let first = words.next().unwrap_or("");
+    // -i, -i.bak, -pi, --in-place
+    let in_place = words.filter(|w| w.starts_with('-')).any(|w| {
+        w.starts_with("--in-place")
+            || (!w.starts_with("--") && w[1..].split('.').next().unwrap_or("").contains('i'))
+    });
+    to_file
+        || matches!(
+            first,
+            "mkdir" | "rmdir" | "rm" | "cp" | "mv" | "touch" | "ln" | "tee" | "dd" | "chmod" | "chown"
+                | "chgrp" | "truncate" | "install" | "shred" | "mkfifo" | "mknod" | "rsync" | "unlink"
+        )
+        || (matches!(first, "sed" | "perl") && in_place)
+}
+
+fn confirm_safe_write(cmd: &str) -> bool {
+    eprint!("\x1b[1;33m⚠  safe mode: '{}' writes to disk. Run anyway? [y/N] \x1b[0m", cmd);
+    io::stderr().flush().ok();
+    let mut ans = String::new();
+    io::stdin().read_line(&mut ans).ok();
+    ans.trim().eq_ignore_ascii_case("y")
+}
+
+/// Skrypt uruchamiany bezpośrednio (`./deploy.sh`, `/opt/x.hsh` — pierwsze
+/// słowo ze ścieżką, nie argument jak w `grep foo bar.sh`): nadaj +x, jeśli
+/// plik należy do użytkownika (z komunikatem; nie w trybie bezpiecznym),
+/// i sprawdź składnię
+fn maybe_chmod_and_validate(cmd: &str, safe_mode: bool) {
+    let Some(first) = tokenize::split(cmd).and_then(|w| w.into_iter().next()) else { return };
+    if (first.ends_with(".sh") || first.ends_with(".hsh")) && first.contains('/') {
+        let first = expand_tilde(&first);
+        let p = Path::new(&first);
+        let owned = |m: &std::fs::Metadata| m.uid() == unsafe { libc::geteuid() };
+        if let Some(meta) = p.metadata().ok().filter(|m| !safe_mode && m.is_file() && owned(m)) {
             let mut perms = meta.permissions();
             if perms.mode() & 0o111 == 0 {
                 perms.set_mode(perms.mode() | 0o111);
-                let _ = std::fs::set_permissions(p, perms);
+                if std::fs::set_permissions(p, perms).is_ok() {
+                    eprintln!("\x1b[38;5;244mhsh: chmod +x {}\x1b[0m", first);
+                }
             }
         }
         // Walidacja składni
-        if let Ok(content) = read_to_string(first) {
+        if let Ok(content) = read_to_string(&first) {
             let checks = validate_script(&content);
             if !checks.is_empty() {
-                print_syntax_errors(first, &checks);
+                print_syntax_errors(&first, &checks);
             }
         }
     }
 }
 
+/// `plik.hl [args]` → `hl run plik.hl [args]` — tylko gdy plik .hl jest
+/// komendą (pierwsze słowo, nie argument `cat x.hl`), istnieje, nie jest
+/// programem z PATH, a interpreter `hl` jest dostępny
 fn maybe_hl_run(cmd: String) -> String {
-    let first = cmd.split_whitespace().next().unwrap_or("").to_string();
-    if first.ends_with(".hl") { format!("hl run {}", cmd) } else { cmd }
+    let Some(first) = tokenize::split(&cmd).and_then(|w| w.into_iter().next()) else { return cmd };
+    let runnable = first.ends_with(".hl")
+        && Path::new(&expand_tilde(&first)).is_file()
+        && crate::helper::find_in_path(&first).is_none()
+        && crate::helper::find_in_path("hl").is_some();
+    if runnable { format!("hl run {}", cmd) } else { cmd }
 }
 
-fn glob_match(pattern: &str, word: &str) -> bool {
-    if pattern == "*" { return true; }
-    if !pattern.contains('*') && !pattern.contains('?') {
-        return pattern == word;
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn writes_to_disk_covers_file_modifying_commands() {
+        for cmd in ["rm -rf x", "echo hi | tee out", "dd if=/dev/zero of=x", "ln -s a b", "chmod +x f",
+                    "truncate -s 0 f", "install -m 755 a b", "sed -i s/a/b/ f", "sed -i.bak s/a/b/ f",
+                    "perl -pi -e s/a/b/ f", "sudo rm f", "env X=1 touch f", "echo hi > f", "ls &> log"] {
+            let stage = cmd.rsplit('|').next().unwrap().trim();
+            assert!(writes_to_disk(stage), "{}", cmd);
+        }
+        for cmd in ["ls -la", "sed s/a/b/ f", "sed -n p f", "perl -e 1", "echo hi > /dev/null", "cat f", "grep -i x f"] {
+            assert!(!writes_to_disk(cmd), "{}", cmd);
+        }
     }
-    match_glob(
-        &pattern.chars().collect::<Vec<_>>(), 0,
-        &word.chars().collect::<Vec<_>>(), 0,
-    )
-}
 
-fn match_glob(pat: &[char], pi: usize, s: &[char], si: usize) -> bool {
-    if pi == pat.len() { return si == s.len(); }
-    if pat[pi] == '*' {
-        let next_pi = {
-            let mut np = pi + 1;
-            while np < pat.len() && pat[np] == '*' { np += 1; }
-            np
-        };
-        if next_pi == pat.len() { return true; }
-        for skip in si..=s.len() {
-            if match_glob(pat, next_pi, s, skip) { return true; }
+    #[test]
+    fn strip_line_comment_cuts_at_unquoted_hash() {
+        assert_eq!(strip_line_comment("echo hi # komentarz"), "echo hi");
+        assert_eq!(strip_line_comment("# cała linia"), "");
+        assert_eq!(strip_line_comment("echo hi"), "echo hi");
+    }
+
+    #[test]
+    fn strip_line_comment_keeps_quoted_and_inner_hash() {
+        assert_eq!(strip_line_comment("echo 'a # b'"), "echo 'a # b'");
+        assert_eq!(strip_line_comment("echo \"a # b\" # c"), "echo \"a # b\"");
+        assert_eq!(strip_line_comment("echo a\\#b"), "echo a\\#b");
+        assert_eq!(strip_line_comment("echo ${#x} a#b"), "echo ${#x} a#b");
+    }
+
+    #[test]
+    fn strip_comments_leaves_heredoc_body() {
+        let src = "cat <<EOF # otwarcie\n# nie komentarz\nEOF\necho x # tak";
+        assert_eq!(strip_comments(src), "cat <<EOF\n# nie komentarz\nEOF\necho x");
+    }
+
+    #[test]
+    fn heredoc_delimiter_forms() {
+        assert_eq!(heredoc_delimiter("cat <<EOF").as_deref(), Some("EOF"));
+        assert_eq!(heredoc_delimiter("cat <<-'END' > f").as_deref(), Some("END"));
+        assert_eq!(heredoc_delimiter("cat << \"X\";").as_deref(), Some("X"));
+        assert_eq!(heredoc_delimiter("cat <<< word"), None);
+        assert_eq!(heredoc_delimiter("echo hi"), None);
+    }
+
+    #[test]
+    fn heredoc_delimiter_skips_quoted_text() {
+        assert_eq!(heredoc_delimiter("echo \"a << b\""), None);
+        assert_eq!(heredoc_delimiter("echo 'x <<EOF'"), None);
+        assert_eq!(heredoc_delimiter("echo a \\<<b"), None);
+        assert_eq!(heredoc_delimiter("echo '<<' && cat <<END").as_deref(), Some("END"));
+    }
+
+    /// check_auto_sudo z [sudo] always — bez pytania y/n
+    fn auto_sudo(input: &str) -> String {
+        let mut vars = ShellVars::new();
+        vars.sudo.always = true;
+        check_auto_sudo(input, &mut vars)
+    }
+
+    #[test]
+    fn auto_sudo_keeps_quoted_paths_with_spaces() {
+        let sudo = crate::config::SudoOptions::default();
+        for input in [r#"vim "/etc/my file.conf""#, r"vim /etc/my\ file.conf", "nano '/etc/my file.conf'"] {
+            assert_eq!(editor_root_files(input, &sudo), ["/etc/my file.conf"]);
+            let rewritten = auto_sudo(input);
+            assert_eq!(rewritten, format!("sudo {}", input));
+            let words = tokenize::split(&rewritten).unwrap();
+            assert_eq!(words[0], "sudo");
+            assert_eq!(words[2], "/etc/my file.conf");
         }
-        return false;
     }
-    if si >= s.len() { return false; }
-    if pat[pi] == '?' || pat[pi] == s[si] {
-        match_glob(pat, pi + 1, s, si + 1)
-    } else {
-        false
+
+    #[test]
+    fn auto_sudo_keeps_metacharacters_literal() {
+        let sudo = crate::config::SudoOptions::default();
+        let input = r#"vim '/etc/a;b|c&d' "/etc/$(x) `y`" /etc/e\*f"#;
+        assert_eq!(editor_root_files(input, &sudo), ["/etc/a;b|c&d", "/etc/$(x) `y`", "/etc/e*f"]);
+        let rewritten = auto_sudo(input);
+        assert_eq!(rewritten, format!("sudo {}", input));
+        assert_eq!(tokenize::split(&rewritten).unwrap()[1..], tokenize::split(input).unwrap()[..]);
+    }
+
+    #[test]
+    fn auto_sudo_ignores_other_commands_and_paths() {
+        assert_eq!(auto_sudo("cat /etc/hosts"), "cat /etc/hosts");
+        assert_eq!(auto_sudo("vim /tmp/notes.txt"), "vim /tmp/notes.txt");
+        assert_eq!(auto_sudo("vim +10 -p /etc/hosts"), "sudo vim +10 -p /etc/hosts");
     }
 }
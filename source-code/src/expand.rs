@@ -0,0 +1,196 @@
+// ─────────────────────────────────────────────────────────────────────────────
+// Rozwijanie słów: tylda, globy, aliasy
+//
+// Czyste funkcje bez stanu powłoki — wspólne dla wykonania (execute.rs),
+// builtinów i podświetlania (helper.rs).
+// ─────────────────────────────────────────────────────────────────────────────
+
+use std::collections::HashMap;
+use std::env;
+
+use crate::tokenize;
+
+/// Rozwiń tyldę na początku słowa: `~`, `~/…` (→ $HOME) oraz `~user`,
+/// `~user/…` (katalog domowy z bazy użytkowników). Nieznany użytkownik
+/// lub brak $HOME — słowo bez zmian, jak w bash.
+pub fn expand_tilde(s: &str) -> String {
+    let Some(rest) = s.strip_prefix('~') else { return s.to_string() };
+    let (user, tail) = rest.split_at(rest.find('/').unwrap_or(rest.len()));
+    let home = if user.is_empty() {
+        env::var("HOME").ok()
+    } else {
+        user_home(user)
+    };
+    match home {
+        Some(home) => format!("{}{}", home, tail),
+        None       => s.to_string(),
+    }
+}
+
+/// Katalog domowy użytkownika `name` (getpwnam_r)
+fn user_home(name: &str) -> Option<String> {
+    let c_name = std::ffi::CString::new(name).ok()?;
+    let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+    let mut buf = vec![0 as libc::c_char; 4096];
+    let mut result: *mut libc::passwd = std::ptr::null_mut();
+    let rc = unsafe {
+        libc::getpwnam_r(c_name.as_ptr(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result)
+    };
+    if rc != 0 || result.is_null() || pwd.pw_dir.is_null() { return None; }
+    let dir = unsafe { std::ffi::CStr::from_ptr(pwd.pw_dir) };
+    Some(dir.to_string_lossy().into_owned())
+}
+
+/// Podziel linię na argumenty i rozwiń tyldę oraz globy (`*`, `?`, `[…]`).
+/// Tylko niezacytowane znaki są wzorcem — `"*.txt"` i `\*` zostają dosłownie.
+/// Wzorzec bez dopasowań przechodzi bez zmian (jak domyślnie w bash).
+/// Niezamknięty cudzysłów — pusta lista.
+pub fn expand_words(line: &str) -> Vec<String> {
+    let tokens = tokenize::tokenize(line);
+    if tokens.iter().any(|t| t.unclosed) { return Vec::new(); }
+    tokens.iter().filter(|t| t.kind != tokenize::TokenKind::Comment).flat_map(expand_word).collect()
+}
+
+fn expand_word(tok: &tokenize::Token) -> Vec<String> {
+    // `~` tylko niezacytowana, na początku słowa
+    let tilde = tok.parts.first().map(|p| p.kind == tokenize::PartKind::Plain).unwrap_or(false)
+        && tok.value.starts_with('~');
+    let value = if tilde { expand_tilde(&tok.value) } else { tok.value.clone() };
+    if !tok.has_glob { return vec![value]; }
+
+    let pattern = if tilde { expand_tilde(&tok.pattern) } else { tok.pattern.clone() };
+    let opts = glob::MatchOptions { require_literal_leading_dot: true, ..Default::default() };
+    let matches: Vec<String> = match glob::glob_with(&pattern, opts) {
+        Ok(paths) => paths
+            .filter_map(|p| p.ok())
+            .map(|p| p.to_string_lossy().to_string())
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+    if matches.is_empty() { vec![value] } else { matches }
+}
+
+/// Podmień pierwsze słowo linii na wartość aliasu (jeśli istnieje).
+pub fn expand_alias(input: &str, aliases: &HashMap<String, String>) -> String {
+    let tokens = tokenize::tokenize(input);
+    if let Some(first) = tokens.first() {
+        if let Some(val) = aliases.get(first.value.as_str()) {
+            // Reszta z oryginalnego tekstu — zachowuje cudzysłowy argumentów
+            let rest = input[first.end..].trim();
+            return if rest.is_empty() { val.clone() } else { format!("{} {}", val, rest) };
+        }
+    }
+    input.to_string()
+}
+
+pub fn glob_match(pattern: &str, word: &str) -> bool {
+    if pattern == "*" { return true; }
+    if !pattern.contains('*') && !pattern.contains('?') {
+        return pattern == word;
+    }
+    match_glob(
+        &pattern.chars().collect::<Vec<_>>(), 0,
+        &word.chars().collect::<Vec<_>>(), 0,
+    )
+}
+
+fn match_glob(pat: &[char], pi: usize, s: &[char], si: usize) -> bool {
+    if pi == pat.len() { return si == s.len(); }
+    if pat[pi] == '*' {
+        let next_pi = {
+            let mut np = pi + 1;
+            while np < pat.len() && pat[np] == '*' { np += 1; }
+            np
+        };
+        if next_pi == pat.len() { return true; }
+        for skip in si..=s.len() {
+            if match_glob(pat, next_pi, s, skip) { return true; }
+        }
+        return false;
+    }
+    if si >= s.len() { return false; }
+    if pat[pi] == '?' || pat[pi] == s[si] {
+        match_glob(pat, pi + 1, s, si + 1)
+    } else {
+        false
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::tokenize::{split, tokenize, TokenKind};
+    use crate::vars::ShellVars;
+
+    // Jedyny test zmieniający $HOME — testy biegną równolegle
+    #[test]
+    fn tilde_expands_home_and_users() {
+        env::set_var("HOME", "/home/test");
+        assert_eq!(expand_tilde("~"), "/home/test");
+        assert_eq!(expand_tilde("~/src/hsh"), "/home/test/src/hsh");
+        assert_eq!(expand_tilde("~root/bin"), "/root/bin");
+        assert_eq!(expand_tilde("~no_such_user_hsh/x"), "~no_such_user_hsh/x");
+        assert_eq!(expand_tilde("a/~"), "a/~");
+        assert_eq!(expand_words("~/a '~/b' \\~/c"), ["/home/test/a", "~/b", "~/c"]);
+    }
+
+    #[test]
+    fn variables_and_modifiers() {
+        let mut vars = ShellVars::new();
+        vars.set("NAME", "hsh");
+        assert_eq!(vars.expand("$NAME-${NAME}x"), "hsh-hshx");
+        assert_eq!(vars.expand("\"$NAME\""), "\"hsh\"");
+        assert_eq!(vars.expand("${#NAME}"), "3");
+        assert_eq!(vars.expand("${UNSET_HSH_VAR:-dom}"), "dom");
+        assert_eq!(vars.expand("${NAME:+jest}"), "jest");
+        assert_eq!(vars.expand("koszt 5$"), "koszt 5$");
+    }
+
+    #[test]
+    fn single_quotes_and_escapes_block_expansion() {
+        let mut vars = ShellVars::new();
+        vars.set("NAME", "hsh");
+        assert_eq!(vars.expand("\"$NAME\" '$NAME'"), "\"hsh\" '$NAME'");
+        assert_eq!(vars.expand("\\$NAME"), "\\$NAME");
+    }
+
+    #[test]
+    fn special_and_positional_variables() {
+        let mut vars = ShellVars::new();
+        vars.last_exit  = 3;
+        vars.positional = vec!["a".into(), "b".into()];
+        assert_eq!(vars.expand("$? $# $1 $2 $@"), "3 2 a b a b");
+    }
+
+    #[test]
+    fn tokenizer_splits_words_and_operators() {
+        let tokens = tokenize("echo 'a b' \"c d\"|wc -l && ls>out");
+        let kinds: Vec<(TokenKind, &str)> = tokens.iter().map(|t| (t.kind, t.value.as_str())).collect();
+        assert_eq!(kinds, [
+            (TokenKind::Word, "echo"),
+            (TokenKind::Word, "a b"),
+            (TokenKind::Word, "c d"),
+            (TokenKind::Operator, "|"),
+            (TokenKind::Word, "wc"),
+            (TokenKind::Word, "-l"),
+            (TokenKind::Operator, "&&"),
+            (TokenKind::Word, "ls"),
+            (TokenKind::Operator, ">"),
+            (TokenKind::Word, "out"),
+        ]);
+    }
+
+    #[test]
+    fn tokenizer_quotes_and_escapes() {
+        assert_eq!(split(r#"a\ b $'x\ty' "q\"q""#).unwrap(), ["a b", "x\ty", "q\"q"]);
+        assert_eq!(split("echo 'open"), None);
+    }
+
+    #[test]
+    fn tokenizer_marks_only_unquoted_globs() {
+        let t = &tokenize("\"*\".txt")[0];
+        assert!(!t.has_glob);
+        assert_eq!(t.pattern, "[*].txt");
+        assert!(tokenize("*.rs")[0].has_glob);
+    }
+}
@@ -0,0 +1,121 @@
+// ─────────────────────────────────────────────────────────────────────────────
+// Skakanie po katalogach wg frecency (builtin `j`, jak z/zoxide)
+//
+// Każde udane interaktywne `cd` zapisuje katalog w ~/.hsh_frecency: liczba odwiedzin
+// i czas ostatniej. Ranking = odwiedziny × mnożnik świeżości. Gdy suma
+// odwiedzin przekroczy MAX_TOTAL, wszystkie wpisy są mnożone przez 0.9,
+// a te poniżej 1 odpadają — stare katalogi z czasem wypadają z bazy.
+// ─────────────────────────────────────────────────────────────────────────────
+
+use std::env;
+use std::fs;
+use std::path::Path;
+use std::time::{SystemTime, UNIX_EPOCH};
+
+const MAX_TOTAL: f64 = 1000.0;
+const AGING: f64 = 0.9;
+
+struct Entry {
+    path: String,
+    rank: f64,
+    /// Sekundy od epoki Uniksa
+    last: u64,
+}
+
+impl Entry {
+    /// Odwiedziny × świeżość: ostatnia godzina ×4, dzień ×2, tydzień ×0.5, starsze ×0.25
+    fn score(&self, now: u64) -> f64 {
+        let age = now.saturating_sub(self.last);
+        let weight = match age {
+            0..=3_599        => 4.0,
+            3_600..=86_399   => 2.0,
+            86_400..=604_799 => 0.5,
+            _                => 0.25,
+        };
+        self.rank * weight
+    }
+}
+
+fn db_path() -> String {
+    let home = env::var("HOME").unwrap_or_else(|_| "/root".to_string());
+    format!("{}/.hsh_frecency", home)
+}
+
+fn now() -> u64 {
+    SystemTime::now().duration_since(UNIX_EPOCH).map(|d| d.as_secs()).unwrap_or(0)
+}
+
+/// Wpisy z ~/.hsh_frecency — linie `ścieżka<TAB>odwiedziny<TAB>czas`
+fn load() -> Vec<Entry> {
+    fs::read_to_string(db_path())
+        .unwrap_or_default()
+        .lines()
+        .filter_map(|line| {
+            let mut f = line.split('\t');
+            Some(Entry {
+                path: f.next()?.to_string(),
+                rank: f.next()?.parse().ok()?,
+                last: f.next()?.parse().ok()?,
+            })
+        })
+        .collect()
+}
+
+fn save(entries: &[Entry]) {
+    let body: String = entries
+        .iter()
+        .map(|e| format!("{}\t{:.2}\t{}\n", e.path, e.rank, e.last))
+        .collect();
+    let _ = fs::write(db_path(), body);
+}
+
+/// Zapisz odwiedzenie katalogu (wołane przez `cd`)
+pub fn record(dir: &str) {
+    let home = env::var("HOME").unwrap_or_default();
+    if dir == home || dir == "/" {
+        return;
+    }
+    let mut entries = load();
+    let now = now();
+    match entries.iter_mut().find(|e| e.path == dir) {
+        Some(e) => { e.rank += 1.0; e.last = now; }
+        None    => entries.push(Entry { path: dir.to_string(), rank: 1.0, last: now }),
+    }
+    if entries.iter().map(|e| e.rank).sum::<f64>() > MAX_TOTAL {
+        for e in entries.iter_mut() {
+            e.rank *= AGING;
+        }
+        entries.retain(|e| e.rank >= 1.0);
+    }
+    save(&entries);
+}
+
+/// Istniejące katalogi pasujące do wszystkich `terms` (bez rozróżniania
+/// wielkości liter), od najwyższego wyniku. Ostatni człon musi pasować do
+/// nazwy samego katalogu — `j proj` nie skacze do ~/proj/src/deep.
+/// Usunięte katalogi znikają z bazy przy okazji.
+pub fn matches(terms: &[String]) -> Vec<(f64, String)> {
+    let mut entries = load();
+    let before = entries.len();
+    entries.retain(|e| Path::new(&e.path).is_dir());
+    if entries.len() != before {
+        save(&entries);
+    }
+
+    let terms: Vec<String> = terms.iter().map(|t| t.to_lowercase()).collect();
+    let cwd = env::current_dir().map(|d| d.to_string_lossy().to_string()).unwrap_or_default();
+    let now = now();
+    let mut found: Vec<(f64, String)> = entries
+        .iter()
+        .filter(|e| e.path != cwd)
+        .filter(|e| {
+            let path = e.path.to_lowercase();
+            let name = path.rsplit('/').next().unwrap_or("");
+            terms.iter().all(|t| path.contains(t.as_str()))
+                && terms.last().map(|t| name.contains(t.as_str())).unwrap_or(true)
+        })
+        .map(|e| (e.score(now), e.path.clone()))
+        .collect();
+    found.sort_by(|a, b| b.0.total_cmp(&a.0));
+    found
+}
@@ -1,11 +1,12 @@
 use std::sync::{Arc, Mutex};
 use std::time::{Duration, Instant};
-use tokio::sync::watch;
+use tokio::sync::{watch, Notify};
 
 #[derive(Clone, Default)]
 pub struct GitInfo {
     pub branch: Option<String>,
     pub dirty: bool,       // uncommitted changes
+    pub changed: u32,      // number of changed tracked files
     pub ahead: u32,        // commits ahead of remote
     pub behind: u32,       // commits behind remote
 }
@@ -14,7 +15,7 @@ impl GitInfo {
     pub fn format(&self, git_symbol: &str, git_color: &str) -> String {
         let Some(ref branch) = self.branch else { return String::new() };
 
-        let dirty_marker = if self.dirty { " \x1b[31m✗\x1b[0m" } else { "" };
+        let dirty_marker = if self.dirty { format!(" \x1b[31m±{}\x1b[0m", self.changed) } else { String::new() };
         let sync = match (self.ahead, self.behind) {
             (0, 0) => String::new(),
             (a, 0) => format!(" \x1b[32m↑{}\x1b[0m", a),
@@ -30,28 +31,43 @@ impl GitInfo {
 }
 
 /// Spawns a background task to fetch git info.
-/// Returns a watch receiver that gets updated when ready.
+/// Returns a watch receiver that gets updated when ready, plus a handle that
+/// forces an immediate re-fetch (directory change, `git checkout`).
 /// Call this BEFORE rendering the prompt — result may be "stale" for one frame.
-pub fn spawn_git_watcher() -> watch::Receiver<GitInfo> {
+///
+/// Results are reused for `ttl` before the next background lookup. With
+/// `status` off only the branch is looked up — dirty/ahead/behind need the
+/// slower `git status` and `git rev-list`.
+/// Each lookup is limited to `timeout` (slow NFS repos); a lookup that does
+/// not finish in time is dropped — its git processes are killed — and the
+/// git segment is hidden until a lookup succeeds again.
+pub fn spawn_git_watcher(timeout: Duration, ttl: Duration, status: bool) -> (watch::Receiver<GitInfo>, Arc<Notify>) {
     let (tx, rx) = watch::channel(GitInfo::default());
+    let refresh = Arc::new(Notify::new());
+    let wake = refresh.clone();
 
     tokio::spawn(async move {
         loop {
-            let info = fetch_git_info().await;
+            let info = tokio::time::timeout(timeout, fetch_git_info(status))
+                .await
+                .unwrap_or_default();
             let _ = tx.send(info);
-            // Re-check every 2 seconds
-            tokio::time::sleep(Duration::from_secs(2)).await;
+            tokio::select! {
+                _ = tokio::time::sleep(ttl) => {}
+                _ = wake.notified() => {}
+            }
         }
     });
 
-    rx
+    (rx, refresh)
 }
 
-async fn fetch_git_info() -> GitInfo {
+async fn fetch_git_info(status: bool) -> GitInfo {
     // Branch
     let branch_out = tokio::process::Command::new("git")
     .args(["rev-parse", "--abbrev-ref", "HEAD"])
     .stderr(std::process::Stdio::null())
+    .kill_on_drop(true)
     .output()
     .await;
 
@@ -68,25 +84,31 @@ async fn fetch_git_info() -> GitInfo {
         _ => return GitInfo::default(),
     };
 
+    if !status {
+        return GitInfo { branch, ..GitInfo::default() };
+    }
+
     // Dirty check (fast: only index + worktree)
-    let dirty = tokio::process::Command::new("git")
+    let changed = tokio::process::Command::new("git")
     .args(["status", "--porcelain", "--untracked-files=no"])
     .stderr(std::process::Stdio::null())
+    .kill_on_drop(true)
     .output()
     .await
-    .map(|o| !o.stdout.is_empty())
-    .unwrap_or(false);
+    .map(|o| o.stdout.split(|b| *b == b'\n').filter(|l| !l.is_empty()).count() as u32)
+    .unwrap_or(0);
 
     // Ahead/behind
     let (ahead, behind) = ahead_behind().await;
 
-    GitInfo { branch, dirty, ahead, behind }
+    GitInfo { branch, dirty: changed > 0, changed, ahead, behind }
 }
 
 async fn short_hash() -> Option<String> {
     let out = tokio::process::Command::new("git")
     .args(["rev-parse", "--short", "HEAD"])
     .stderr(std::process::Stdio::null())
+    .kill_on_drop(true)
     .output()
     .await
     .ok()?;
@@ -101,6 +123,7 @@ async fn ahead_behind() -> (u32, u32) {
     let out = tokio::process::Command::new("git")
     .args(["rev-list", "--left-right", "--count", "@{u}...HEAD"])
     .stderr(std::process::Stdio::null())
+    .kill_on_drop(true)
     .output()
     .await;
 
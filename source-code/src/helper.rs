@@ -1,28 +1,29 @@
 use std::borrow::Cow::{self, Borrowed, Owned};
+use std::cell::{Cell, RefCell};
 use std::collections::HashMap;
 use std::env;
 use std::fs::read_dir;
-use std::path::Path;
+use std::path::{Path, PathBuf};
+use std::process::{Command, Stdio};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant, SystemTime};
 
 use rustyline::completion::{Completer, FilenameCompleter, Pair};
 use rustyline::highlight::Highlighter;
-use rustyline::hint::{Hinter, HistoryHinter};
+use rustyline::hint::{Hint, Hinter, HistoryHinter};
 use rustyline::validate::{ValidationContext, ValidationResult, Validator};
-use rustyline::Context;
+use rustyline::{
+    Cmd, ConditionalEventHandler, Context, Event, EventContext, InputMode, KeyCode, KeyEvent,
+    Modifiers, RepeatCount,
+};
 use rustyline_derive::Helper;
 
-use crate::security::highlight_dangerous;
-use crate::smarthints::SmartHints;
+use crate::expand::expand_tilde;
+use crate::security::{highlight_dangerous, DEFAULT_DANGER_STYLE};
+use crate::smarthints::{levenshtein, SmartHints};
 use crate::theme::Theme;
-
-fn expand_tilde(s: &str) -> String {
-    if s.starts_with('~') {
-        if let Ok(home) = env::var("HOME") {
-            return format!("{}{}", home, &s[1..]);
-        }
-    }
-    s.to_string()
-}
+use crate::tokenize::{tokenize, PartKind, TokenKind};
 
 // ─────────────────────────────────────────────────────────────────────────────
 
@@ -36,35 +37,62 @@ pub struct ShellHelper {
     pub hints_snapshot: HashMap<String, Vec<(String, u64)>>,
     /// sequences snapshot: prev_cmd → best_next_cmd
     pub seq_snapshot:   HashMap<String, String>,
+    /// Sekwencja ANSI dla niebezpiecznych linii ([safety] danger_style)
+    pub danger_style:   String,
+    /// Dodatkowe wzorce niebezpiecznych komend ([safety] dangerous_patterns)
+    pub danger_patterns: Vec<String>,
+    /// Aliasy sesji — kopia z main, odświeżana przed każdym promptem
+    pub aliases:        HashMap<String, String>,
+    /// Tryb vi: true, gdy edytor jest w trybie NORMAL (None — tryb emacs)
+    pub vi_normal:      Option<Arc<AtomicBool>>,
+    /// $PATH i czasy modyfikacji jego katalogów z chwili skanu — zmiana
+    /// (nowy program, `export PATH=…`) oznacza nieaktualny commands_cache
+    path_stamp:         PathStamp,
+    /// Statystyki dla `hsh-debug completer`: czas skanu PATH i trafienia cache
+    scan_time:          Duration,
+    lookups:            Cell<u64>,
+    hits:               Cell<u64>,
     hinter:             HistoryHinter,
     completer:          FilenameCompleter,
+    /// Gałęzie i aliasy git dla uzupełniania — krótko trzymane, żeby nie
+    /// uruchamiać gita przy każdym Tab
+    git_cache:          RefCell<Option<GitCompletionCache>>,
+}
+
+/// $PATH + mtime każdego katalogu — jeden stat na katalog, tani przed każdym promptem
+#[derive(PartialEq)]
+struct PathStamp {
+    path:   String,
+    mtimes: Vec<Option<SystemTime>>,
+}
+
+impl PathStamp {
+    fn current() -> Self {
+        let path = env::var("PATH").unwrap_or_default();
+        let mtimes = path
+            .split(':')
+            .map(|dir| std::fs::metadata(dir).and_then(|m| m.modified()).ok())
+            .collect();
+        PathStamp { path, mtimes }
+    }
+}
+
+/// Jak długo trzymać listę gałęzi/aliasów git dla jednego katalogu
+const GIT_CACHE_TTL: Duration = Duration::from_secs(5);
+
+struct GitCompletionCache {
+    dir:      PathBuf,
+    at:       Instant,
+    branches: Vec<String>,
+    aliases:  Vec<String>,
 }
 
 impl ShellHelper {
     pub fn new(theme: Theme) -> Self {
-        let mut commands_cache = vec![
-            "cd", "exit", "history", "which", "type", "jobs",
-            "fg", "export", "source", "hsh-help", "test",
-            "hsh-settings", "hsh-docs", "bg", "stop", "kill", "wait",
-            "alias", "unalias", "set", "pushd", "popd", "dirs",
-        ]
-        .into_iter()
-        .map(String::from)
-        .collect::<Vec<_>>();
-
-        if let Ok(path) = env::var("PATH") {
-            for dir in path.split(':') {
-                if let Ok(entries) = read_dir(dir) {
-                    for entry in entries.flatten() {
-                        commands_cache.push(
-                            entry.file_name().to_string_lossy().to_string(),
-                        );
-                    }
-                }
-            }
-        }
-        commands_cache.sort();
-        commands_cache.dedup();
+        let scan_start = Instant::now();
+        let path_stamp = PathStamp::current();
+        let commands_cache = scan_commands();
+        let scan_time = scan_start.elapsed();
 
         ShellHelper {
             colored_prompt: String::new(),
@@ -73,8 +101,35 @@ impl ShellHelper {
             commands_cache,
             hints_snapshot: HashMap::new(),
             seq_snapshot:   HashMap::new(),
+            danger_style:   DEFAULT_DANGER_STYLE.to_string(),
+            danger_patterns: Vec::new(),
+            aliases:        HashMap::new(),
+            vi_normal:      None,
+            path_stamp,
+            scan_time,
+            lookups:        Cell::new(0),
+            hits:           Cell::new(0),
             hinter:         HistoryHinter {},
             completer:      FilenameCompleter::new(),
+            git_cache:      RefCell::new(None),
+        }
+    }
+
+    /// Przeskanuj PATH od nowa — po zmianie $PATH (np. builtin `path`) i `rehash`
+    pub fn rehash(&mut self) {
+        let scan_start = Instant::now();
+        self.path_stamp = PathStamp::current();
+        self.commands_cache = scan_commands();
+        self.scan_time = scan_start.elapsed();
+    }
+
+    /// Przeskanuj PATH tylko, gdy zmienił się $PATH (`export PATH=…`, `PATH=…`,
+    /// `source`) lub któryś z jego katalogów (zainstalowany/usunięty program) —
+    /// wywoływane przed każdym promptem, więc podświetlanie i Tab widzą zmianę
+    /// od następnej linii
+    pub fn rehash_if_stale(&mut self) {
+        if PathStamp::current() != self.path_stamp {
+            self.rehash();
         }
     }
 
@@ -86,7 +141,7 @@ impl ShellHelper {
             .iter()
             .map(|(k, v)| (k.clone(), *v))
             .collect();
-            v.sort_by(|a, b| b.1.cmp(&a.1));
+            v.sort_by_key(|m| std::cmp::Reverse(m.1));
             self.hints_snapshot.insert(word.clone(), v);
         }
 
@@ -104,17 +159,93 @@ impl ShellHelper {
         if trimmed.is_empty() { return None; }
         let first_word = trimmed.split_whitespace().next()?;
         let entries = self.hints_snapshot.get(first_word)?;
-        for (cmd, _) in entries {
-            if cmd.starts_with(trimmed) && cmd.len() > trimmed.len() {
-                return Some(cmd[trimmed.len()..].to_string());
-            }
+        // strip_prefix zamiast cięcia po bajtach — bezpieczne dla UTF-8
+        entries.iter()
+            .filter_map(|(cmd, _)| cmd.strip_prefix(trimmed))
+            .find(|rest| !rest.is_empty())
+            .map(String::from)
+    }
+
+    /// Gałęzie (`want_branches`) albo aliasy z `git config` dla bieżącego katalogu
+    fn git_names(&self, want_branches: bool) -> Vec<String> {
+        let dir = env::current_dir().unwrap_or_default();
+        let mut cache = self.git_cache.borrow_mut();
+        let fresh = cache.as_ref().map(|c| c.dir == dir && c.at.elapsed() < GIT_CACHE_TTL).unwrap_or(false);
+        if !fresh {
+            let branches = git_lines(&["branch", "--format=%(refname:short)"]);
+            let aliases = git_lines(&["config", "--get-regexp", r"^alias\."])
+                .into_iter()
+                .filter_map(|l| l.split_whitespace().next()?.strip_prefix("alias.").map(String::from))
+                .collect();
+            *cache = Some(GitCompletionCache { dir, at: Instant::now(), branches, aliases });
         }
-        None
+        let cache = cache.as_ref().expect("git cache filled above");
+        if want_branches { cache.branches.clone() } else { cache.aliases.clone() }
     }
 
     fn command_exists(&self, cmd: &str) -> bool {
-        self.commands_cache.iter().any(|c| c == cmd) || Path::new(cmd).exists()
+        // ./deploy, ../bin/x, ~/bin/x — tylko wykonywalne pliki są poprawną komendą
+        if cmd.contains('/') {
+            return is_executable_file(Path::new(&expand_tilde(cmd)));
+        }
+        let hit = self.aliases.contains_key(cmd) || self.commands_cache.iter().any(|c| c == cmd);
+        self.lookups.set(self.lookups.get() + 1);
+        if hit { self.hits.set(self.hits.get() + 1); }
+        // Chybienie cache — program mógł się pojawić po skanie; sprawdź $PATH na żądanie
+        hit || find_in_path(cmd).is_some()
+    }
+
+    /// `[shell] correct`: najbliższa znana komenda dla nieznanego `cmd`
+    /// (`gti` → `git`) — tylko przy odległości edycyjnej ≤ 2
+    pub fn suggest_command(&self, cmd: &str) -> Option<&str> {
+        if cmd.chars().count() < 2
+            || cmd.contains('/')
+            || self.aliases.contains_key(cmd)
+            || self.commands_cache.iter().any(|c| c == cmd)
+        {
+            return None;
+        }
+        self.commands_cache
+            .iter()
+            .map(|c| (c.as_str(), levenshtein(cmd, c)))
+            .filter(|(_, d)| *d <= 2)
+            .min_by_key(|(_, d)| *d)
+            .map(|(c, _)| c)
+    }
+
+    /// Ukryty `hsh-debug completer` — linie klucz=wartość do zgłoszeń wydajności
+    pub fn print_debug_stats(&self) {
+        let lookups = self.lookups.get();
+        let hits    = self.hits.get();
+        let rate    = if lookups > 0 { hits as f64 / lookups as f64 } else { 0.0 };
+        println!("commands_cache.size={}", self.commands_cache.len());
+        println!("path_scan.ms={:.3}", self.scan_time.as_secs_f64() * 1000.0);
+        println!("lookups={}", lookups);
+        println!("hits={}", hits);
+        println!("hit_rate={:.3}", rate);
+        println!("hints_snapshot.size={}", self.hints_snapshot.len());
+    }
+}
+
+/// Builtiny + wszystkie pliki z katalogów $PATH, posortowane bez powtórzeń
+fn scan_commands() -> Vec<String> {
+    let mut commands = crate::builtins::BUILTINS
+        .iter()
+        .map(|(name, _)| name.to_string())
+        .collect::<Vec<_>>();
+
+    if let Ok(path) = env::var("PATH") {
+        for dir in path.split(':') {
+            if let Ok(entries) = read_dir(dir) {
+                for entry in entries.flatten() {
+                    commands.push(entry.file_name().to_string_lossy().to_string());
+                }
+            }
+        }
     }
+    commands.sort();
+    commands.dedup();
+    commands
 }
 
 // ─── Completer ────────────────────────────────────────────────────────────────
@@ -131,27 +262,126 @@ impl Completer for ShellHelper {
         let before_cursor = &line[..pos];
         let trimmed       = before_cursor.trim_start();
 
-        // Pierwsze słowo → complete commands
-        if !trimmed.contains(' ') {
-            let prefix = trimmed;
-            let mut matches: Vec<Pair> = self
+        // Pozycja komendy: początek linii albo słowo po `|`, `;`, `&&`, `||`, `&`
+        let cmd_start = tokenize(before_cursor)
+            .iter()
+            .rev()
+            .find(|t| t.kind == TokenKind::Operator && matches!(t.value.as_str(), "|" | "||" | "&&" | ";" | "&"))
+            .map(|t| t.end)
+            .unwrap_or(0);
+        let cmd_word = before_cursor[cmd_start..].trim_start();
+
+        // Pierwsze słowo jako ścieżka (./skrypt, ../bin/x) → pliki wykonywalne
+        if !cmd_word.contains(char::is_whitespace) && cmd_word.contains('/') {
+            let matches = complete_path_segment(cmd_word, PathKind::Executables);
+            return Ok((pos - cmd_word.len(), matches));
+        }
+
+        // Pierwsze słowo → aliasy, komendy z cache PATH i builtiny,
+        // alfabetycznie i bez powtórzeń
+        if !cmd_word.contains(char::is_whitespace) {
+            let prefix = cmd_word;
+            let mut names: Vec<&String> = self
             .commands_cache
             .iter()
+            .chain(self.aliases.keys())
             .filter(|c| c.starts_with(prefix) && c.as_str() != prefix)
+            .collect();
+            names.sort();
+            names.dedup();
+            let matches: Vec<Pair> = names
+            .into_iter()
             .map(|c| Pair { display: c.clone(), replacement: c.clone() })
             .collect();
-            matches.sort_by(|a, b| {
-                a.display.len().cmp(&b.display.len())
-                .then(a.display.cmp(&b.display))
-            });
             if !matches.is_empty() {
-                let start = line.len() - prefix.len();
-                return Ok((start, matches));
+                return Ok((pos - prefix.len(), matches));
             }
         }
 
-        // Subkomendy dla znanych narzędzi
+        // cd/pushd <Tab> → tylko katalogi (z rozwinięciem ~)
+        if matches!(trimmed.split_whitespace().next(), Some("cd" | "pushd")) {
+            let word_start = before_cursor
+                .rfind(char::is_whitespace)
+                .map(|p| p + 1)
+                .unwrap_or(before_cursor.len());
+            let word = &before_cursor[word_start..];
+            // `cd @<Tab>` → nazwy zakładek
+            if let Some(prefix) = word.strip_prefix('@') {
+                let Some((name, sub)) = prefix.split_once('/') else {
+                    return Ok((word_start, bookmark_completions(prefix, "@")));
+                };
+                // `cd @nazwa/po<Tab>` — podkatalogi zakładki, zapis `@nazwa/` zostaje
+                let Some(base) = crate::config::load_bookmarks().remove(name) else {
+                    return Ok((word_start, Vec::new()));
+                };
+                let matches = complete_path_segment(&format!("{}/{}", base, sub), PathKind::Dirs)
+                    .into_iter()
+                    .map(|p| Pair { replacement: format!("@{}{}", name, &p.replacement[base.len()..]), display: p.display })
+                    .collect();
+                return Ok((word_start, matches));
+            }
+            let matches = complete_path_segment(word, PathKind::Dirs);
+            return Ok((word_start, matches));
+        }
+
+        // bookmark go|rm <Tab> → nazwy zakładek
+        let words: Vec<&str> = trimmed.split_whitespace().collect();
+        if let ["bookmark", "go" | "rm" | "remove", rest @ ..] = words.as_slice() {
+            let word = if before_cursor.ends_with(char::is_whitespace) { "" } else { rest.last().copied().unwrap_or("") };
+            if rest.len() <= usize::from(!word.is_empty()) {
+                return Ok((pos - word.len(), bookmark_completions(word, "")));
+            }
+        }
+
+        // export NAME=<Tab> → ścieżki jako wartość (PATH-y: po każdym ':')
+        if let Some(value_start) = export_value_start(before_cursor) {
+            let value   = &before_cursor[value_start..];
+            let name    = before_cursor[..value_start - 1]
+                .rsplit(char::is_whitespace)
+                .next()
+                .unwrap_or("");
+            let seg_off = if is_path_list_var(name) {
+                value.rfind(':').map(|p| p + 1).unwrap_or(0)
+            } else {
+                0
+            };
+            let segment = &value[seg_off..];
+            let kind    = if is_path_list_var(name) { PathKind::Dirs } else { PathKind::Any };
+            let matches = complete_path_segment(segment, kind);
+            return Ok((value_start + seg_off, matches));
+        }
+
+        // git <Tab> → subkomendy + aliasy z git config; checkout/switch/merge → gałęzie;
+        // pozostałe (git add …) → pliki
         let parts: Vec<&str> = trimmed.split_whitespace().collect();
+        if trimmed.starts_with("git ") {
+            let word_start = before_cursor
+                .rfind(char::is_whitespace)
+                .map(|p| p + 1)
+                .unwrap_or(0);
+            let word  = &before_cursor[word_start..];
+            // Numer słowa pod kursorem (0 — `git`)
+            let index = parts.len() - usize::from(!word.is_empty());
+            let candidates = match index {
+                1 => {
+                    let mut subs = subcommand_completions("git", "").unwrap_or_default();
+                    subs.extend(self.git_names(false));
+                    subs
+                }
+                _ if matches!(parts.get(1), Some(&("checkout" | "switch" | "merge"))) => self.git_names(true),
+                _ => return self.completer.complete(line, pos, ctx),
+            };
+            let mut matches: Vec<Pair> = candidates
+                .into_iter()
+                .filter(|c| c.starts_with(word))
+                .map(|c| Pair { display: c.clone(), replacement: c })
+                .collect();
+            matches.sort_by(|a, b| a.display.cmp(&b.display));
+            matches.dedup_by(|a, b| a.display == b.display);
+            return Ok((word_start, matches));
+        }
+
+        // Subkomendy dla znanych narzędzi
         if let Some(&cmd) = parts.first() {
             let partial = parts.get(1).copied().unwrap_or("");
             if let Some(subs) = subcommand_completions(cmd, partial) {
@@ -179,6 +409,101 @@ impl Completer for ShellHelper {
     }
 }
 
+/// Zakładki z ~/.hsh_bookmarks zaczynające się od `prefix` (z przedrostkiem `sigil`)
+fn bookmark_completions(prefix: &str, sigil: &str) -> Vec<Pair> {
+    crate::config::load_bookmarks()
+        .into_keys()
+        .filter(|name| name.starts_with(prefix))
+        .map(|name| {
+            // `cd @nazwa/` — od razu można uzupełniać podkatalogi
+            let slash = if sigil.is_empty() { "" } else { "/" };
+            Pair { display: format!("{}{}", sigil, name), replacement: format!("{}{}{}", sigil, name, slash) }
+        })
+        .collect()
+}
+
+/// Jeśli kursor stoi w wartości `export NAME=...`, zwróć offset początku wartości.
+fn export_value_start(before_cursor: &str) -> Option<usize> {
+    let trimmed = before_cursor.trim_start();
+    if !trimmed.starts_with("export ") { return None; }
+    let word_start = before_cursor
+        .rfind(char::is_whitespace)
+        .map(|p| p + 1)
+        .unwrap_or(0);
+    let word = &before_cursor[word_start..];
+    let eq   = word.find('=')?;
+    let name = &word[..eq];
+    if name.is_empty() || !name.chars().all(|c| c.is_alphanumeric() || c == '_') {
+        return None;
+    }
+    Some(word_start + eq + 1)
+}
+
+/// Zmienne będące listą katalogów oddzielonych ':' (PATH, MANPATH, LD_LIBRARY_PATH...)
+fn is_path_list_var(name: &str) -> bool {
+    name.ends_with("PATH") || name.ends_with("_DIRS")
+}
+
+#[derive(Clone, Copy, PartialEq)]
+enum PathKind {
+    Any,
+    Dirs,
+    /// Pliki wykonywalne oraz katalogi (żeby dało się do nich zejść)
+    Executables,
+}
+
+fn is_executable_file(path: &Path) -> bool {
+    use std::os::unix::fs::PermissionsExt;
+    path.metadata()
+        .map(|m| m.is_file() && m.permissions().mode() & 0o111 != 0)
+        .unwrap_or(false)
+}
+
+/// Uzupełnij pojedynczą ścieżkę (bez spacji), zachowując zapis użytkownika (np. `~/`).
+fn complete_path_segment(segment: &str, kind: PathKind) -> Vec<Pair> {
+    let split       = segment.rfind('/').map(|p| p + 1).unwrap_or(0);
+    let (dir, file) = segment.split_at(split);
+    let read_from   = if dir.is_empty() { ".".to_string() } else { expand_tilde(dir) };
+
+    let Ok(entries) = read_dir(&read_from) else { return Vec::new() };
+    let mut matches: Vec<Pair> = entries
+        .flatten()
+        .filter_map(|e| {
+            let name = e.file_name().to_string_lossy().to_string();
+            if !name.starts_with(file) { return None; }
+            if name.starts_with('.') && !file.starts_with('.') { return None; }
+            let is_dir = e.path().is_dir();
+            match kind {
+                PathKind::Dirs        if !is_dir => return None,
+                PathKind::Executables if !is_dir && !is_executable_file(&e.path()) => return None,
+                _ => {}
+            }
+            let display = if is_dir { format!("{}/", name) } else { name };
+            Some(Pair { replacement: format!("{}{}", dir, display), display })
+        })
+        .collect();
+    matches.sort_by(|a, b| a.display.cmp(&b.display));
+    matches
+}
+
+/// Niepuste linie wyjścia `git ARGS` (pusto poza repozytorium)
+fn git_lines(args: &[&str]) -> Vec<String> {
+    Command::new("git")
+        .args(args)
+        .stderr(Stdio::null())
+        .output()
+        .ok()
+        .filter(|o| o.status.success())
+        .map(|o| {
+            String::from_utf8_lossy(&o.stdout)
+                .lines()
+                .map(|l| l.trim().to_string())
+                .filter(|l| !l.is_empty())
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
 fn subcommand_completions(cmd: &str, partial: &str) -> Option<Vec<String>> {
     let subs: &[&str] = match cmd {
         "git" => &[
@@ -215,6 +540,7 @@ fn subcommand_completions(cmd: &str, partial: &str) -> Option<Vec<String>> {
             "port-forward", "scale", "rollout", "config",
         ],
         "make" => &["all", "clean", "install", "test", "build", "run"],
+        "bookmark" => &["add", "go", "list", "rm"],
         _ => return None,
     };
     Some(
@@ -227,10 +553,37 @@ fn subcommand_completions(cmd: &str, partial: &str) -> Option<Vec<String>> {
 
 // ─── Hinter ───────────────────────────────────────────────────────────────────
 
+/// Podpowiedź: dopełnienie linii (→ przyjmuje) albo sam podgląd
+/// (lista katalogów dla cd, rozwinięcie aliasu), którego nie da się wstawić
+pub struct ShellHint {
+    text:       String,
+    completion: bool,
+}
+
+impl ShellHint {
+    fn suffix(text: String) -> Self {
+        ShellHint { text, completion: true }
+    }
+
+    fn preview(text: String) -> Self {
+        ShellHint { text, completion: false }
+    }
+}
+
+impl Hint for ShellHint {
+    fn display(&self) -> &str {
+        &self.text
+    }
+
+    fn completion(&self) -> Option<&str> {
+        self.completion.then_some(self.text.as_str())
+    }
+}
+
 impl Hinter for ShellHelper {
-    type Hint = String;
+    type Hint = ShellHint;
 
-    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<String> {
+    fn hint(&self, line: &str, pos: usize, ctx: &Context<'_>) -> Option<ShellHint> {
         // Hint tylko na końcu linii (jak fish)
         if pos < line.len() { return None; }
 
@@ -247,27 +600,34 @@ impl Hinter for ShellHelper {
                 .map(|e| e.file_name().to_string_lossy().to_string())
                 .collect();
                 if !entries.is_empty() {
-                    return Some(format!("\x1b[38;5;236m({})  {}\x1b[0m", entries.len(), entries.join("  ")));
+                    return Some(ShellHint::preview(format!("\x1b[38;5;236m({})  {}\x1b[0m", entries.len(), entries.join("  "))));
                 }
             }
         }
 
         // 1. Historia rustyline — najwyższy priorytet
         if let Some(h) = self.hinter.hint(line, pos, ctx) {
-            return Some(h);
+            return Some(ShellHint::suffix(h));
         }
 
         // 2. Smart inline hint z historii komend (jak fish)
         if !line.trim().is_empty() {
             if let Some(suffix) = self.inline_hint(line) {
-                return Some(suffix);
+                return Some(ShellHint::suffix(suffix));
+            }
+        }
+
+        // 3. Alias jako jedyne słowo — podgląd rozwinięcia, gdy historia nic nie podpowiada
+        if !line.ends_with(char::is_whitespace) {
+            if let Some(expansion) = self.aliases.get(trimmed) {
+                return Some(ShellHint::preview(format!(" → {}", expansion)));
             }
         }
 
-        // 3. Pusta linia → następna komenda
+        // 4. Pusta linia → następna komenda
         if line.trim().is_empty() {
             if let Some(ref nh) = self.next_hint {
-                return Some(nh.clone());
+                return Some(ShellHint::suffix(nh.clone()));
             }
         }
 
@@ -279,202 +639,115 @@ impl Hinter for ShellHelper {
 
 impl Highlighter for ShellHelper {
     fn highlight<'l>(&self, line: &'l str, _pos: usize) -> Cow<'l, str> {
-        if let Some(highlighted) = highlight_dangerous(line) {
+        if let Some(highlighted) = highlight_dangerous(line, &self.danger_style, &self.danger_patterns) {
             return Owned(highlighted);
         }
 
         let t     = &self.theme;
         let reset = "\x1b[0m";
 
-        let mut out    = String::with_capacity(line.len() + 128);
-        let mut is_cmd = true;
-        let mut in_s   = false;
-        let mut in_d   = false;
-        let chars: Vec<char> = line.chars().collect();
-        let mut i = 0;
+        let mut out     = String::with_capacity(line.len() + 128);
+        let mut is_cmd  = true;
+        let mut last    = 0;
+        // Słowo po << / <<< (delimiter heredoca albo treść here-stringa)
+        let mut heredoc = false;
 
-        while i < chars.len() {
-            let c = chars[i];
+        // Ten sam tokenizer co przy wykonaniu — kolory odpowiadają podziałowi na argumenty
+        let tokens   = tokenize(line);
+        let brackets = bracket_depths(line, &tokens);
+        let palette  = [&t.flag_color, &t.var_color, &t.string_color];
+        for tok in &tokens {
+            out.push_str(&line[last..tok.start]);
+            last = tok.end;
 
-            if c.is_whitespace() && !in_s && !in_d {
-                out.push(c);
-                i += 1;
+            if tok.kind == TokenKind::Operator {
+                heredoc = tok.value.contains("<<");
+                let color = if heredoc { &t.string_color }
+                    else if tok.value == "&" { &t.bg_color }
+                    else { &t.op_color };
+                out.push_str(color);
+                out.push_str(&line[tok.start..tok.end]);
+                out.push_str(reset);
+                if matches!(tok.value.as_str(), ";" | "|" | "||" | "&&" | "&") { is_cmd = true; }
                 continue;
             }
 
-            match c {
-                // ── Double quote ──────────────────────────────────────────────
-                '"' if !in_s => {
-                    in_d = !in_d;
-                    if in_d {
-                        let s = format!("{}\"", t.string_color);
-                        out.push_str(&s);
-                    } else {
-                        let s = format!("\"{}", reset);
-                        out.push_str(&s);
-                    }
-                    i += 1;
-                }
+            // ── Komentarz: niezacytowany `#` na początku słowa do końca linii ─
+            if tok.kind == TokenKind::Comment {
+                out.push_str(&t.comment_color);
+                out.push_str(&line[tok.start..tok.end]);
+                out.push_str(reset);
+                continue;
+            }
 
-                // ── Single quote ──────────────────────────────────────────────
-                '\'' if !in_d => {
-                    in_s = !in_s;
-                    if in_s {
-                        let s = format!("{}'", t.string_color);
-                        out.push_str(&s);
-                    } else {
-                        let s = format!("'{}", reset);
-                        out.push_str(&s);
-                    }
-                    i += 1;
-                }
+            // ── Delimiter heredoca / here-string — w całości kolorem napisu ───
+            if std::mem::take(&mut heredoc) {
+                out.push_str(&t.string_color);
+                out.push_str(&line[tok.start..tok.end]);
+                out.push_str(reset);
+                continue;
+            }
 
-                // ── Inside single quotes ──────────────────────────────────────
-                _ if in_s => { out.push(c); i += 1; }
-
-                // ── Inside double quotes ──────────────────────────────────────
-                _ if in_d => {
-                    if c == '$' {
-                        i += 1;
-                        let var_open = format!("{}$", t.var_color);
-                        out.push_str(&var_open);
-                        let start = i;
-                        while i < chars.len()
-                            && (chars[i].is_alphanumeric() || chars[i] == '_')
-                            {
-                                out.push(chars[i]);
-                                i += 1;
-                            }
-                            if i == start {
-                                if let Some(&sc) = chars.get(i) {
-                                    if "?#@$*!0".contains(sc) { out.push(sc); i += 1; }
-                                }
-                            }
-                            out.push_str(&t.string_color);
-                    } else {
-                        out.push(c); i += 1;
-                    }
-                }
+            // ── Słowa: komendy, flagi, ścieżki ────────────────────────────────
+            // Nawiasy grupujące nie należą do nazwy komendy: `(cd /tmp; ls)`
+            let word = tok.value.trim_start_matches('(').trim_end_matches(')');
+            let group_only = matches!(tok.value.as_str(), "{" | "}") || word.is_empty();
+            let word_color: &str = if group_only {
+                reset
+            } else if is_cmd {
+                if self.command_exists(word) { &t.cmd_ok_color }
+                else                         { &t.cmd_err_color }
+            } else if word.starts_with('-') {
+                &t.flag_color
+            } else if word.starts_with('/')
+                || word.starts_with("~/")
+                || word.starts_with("./")
+            {
+                if Path::new(&expand_tilde(word)).exists() { &t.path_color }
+                else { reset }
+            } else {
+                reset
+            };
 
-                // ── $VAR / $() / $(()) ────────────────────────────────────────
-                '$' => {
-                    i += 1;
-                    if chars.get(i) == Some(&'(') {
-                        let open = format!("{}$(", t.var_color);
-                        out.push_str(&open);
-                        i += 1;
-                        let mut depth = 1i32;
-                        while i < chars.len() {
-                            out.push(chars[i]);
-                            if chars[i] == '(' { depth += 1; }
-                            if chars[i] == ')' {
-                                depth -= 1;
-                                if depth == 0 { i += 1; break; }
-                            }
-                            i += 1;
-                        }
-                        out.push_str(reset);
-                    } else if chars.get(i) == Some(&'{') {
-                        let open = format!("{}${{", t.var_color);
-                        out.push_str(&open);
-                        i += 1;
-                        while i < chars.len() && chars[i] != '}' {
-                            out.push(chars[i]); i += 1;
-                        }
-                        if i < chars.len() { out.push('}'); i += 1; }
+            for part in &tok.parts {
+                let color: &str = match part.kind {
+                    PartKind::Plain | PartKind::Escape => word_color,
+                    PartKind::Single | PartKind::Double | PartKind::AnsiC => &t.string_color,
+                    PartKind::Var => &t.var_color,
+                };
+                if part.kind != PartKind::Plain {
+                    out.push_str(color);
+                    out.push_str(&line[part.start..part.end]);
+                    out.push_str(reset);
+                    continue;
+                }
+                // Nawiasy i klamry grupujące — kolor wg głębokości, bez pary na czerwono
+                let mut seg = part.start;
+                for (i, _) in line[part.start..part.end].char_indices() {
+                    let Some(depth) = brackets.get(&(part.start + i)) else { continue };
+                    if seg < part.start + i {
+                        out.push_str(color);
+                        out.push_str(&line[seg..part.start + i]);
                         out.push_str(reset);
-                    } else {
-                        let var_start = i;
-                        if i < chars.len() && "?#@$*!0123456789".contains(chars[i]) {
-                            let s = format!("{}${}{}", t.var_color, chars[i], reset);
-                            out.push_str(&s);
-                            i += 1;
-                        } else {
-                            while i < chars.len()
-                                && (chars[i].is_alphanumeric() || chars[i] == '_')
-                                {
-                                    i += 1;
-                                }
-                                if i == var_start {
-                                    out.push('$');
-                                } else {
-                                    let name: String = chars[var_start..i].iter().collect();
-                                    let s = format!("{}${}{}", t.var_color, name, reset);
-                                    out.push_str(&s);
-                                }
-                        }
                     }
-                    is_cmd = false;
-                }
-
-                // ── Operatory ─────────────────────────────────────────────────
-                ';' => {
-                    let s = format!("{};{}", t.op_color, reset);
-                    out.push_str(&s);
-                    is_cmd = true; i += 1;
-                }
-                '|' if chars.get(i + 1) == Some(&'|') => {
-                    let s = format!("{}||{}", t.op_color, reset);
-                    out.push_str(&s);
-                    is_cmd = true; i += 2;
-                }
-                '&' if chars.get(i + 1) == Some(&'&') => {
-                    let s = format!("{}&&{}", t.op_color, reset);
-                    out.push_str(&s);
-                    is_cmd = true; i += 2;
-                }
-                '|' => {
-                    let s = format!("{}|{}", t.op_color, reset);
-                    out.push_str(&s);
-                    is_cmd = true; i += 1;
-                }
-                '>' | '<' => {
-                    let s = format!("{}{}{}", t.op_color, c, reset);
-                    out.push_str(&s);
-                    i += 1;
-                }
-                '&' => {
-                    let s = format!("{}&{}", t.op_color, reset);
-                    out.push_str(&s);
-                    i += 1;
+                    out.push_str(match depth {
+                        Some(d) => palette[d % palette.len()],
+                        None    => &t.error_color,
+                    });
+                    out.push_str(&line[part.start + i..part.start + i + 1]);
+                    out.push_str(reset);
+                    seg = part.start + i + 1;
                 }
-
-                // ── Słowa: komendy, flagi, ścieżki ────────────────────────────
-                _ => {
-                    let word_start = i;
-                    while i < chars.len() {
-                        let nc = chars[i];
-                        if nc.is_whitespace()
-                            || (!in_s && !in_d && "&|;><\"'$".contains(nc))
-                            {
-                                break;
-                            }
-                            i += 1;
-                    }
-                    let word: String = chars[word_start..i].iter().collect();
-
-                    let color: &str = if is_cmd {
-                        if self.command_exists(&word) { &t.cmd_ok_color }
-                        else                          { &t.cmd_err_color }
-                    } else if word.starts_with('-') {
-                        &t.flag_color
-                    } else if word.starts_with('/')
-                        || word.starts_with("~/")
-                        || word.starts_with("./")
-                        {
-                            if Path::new(&expand_tilde(&word)).exists() { &t.path_color }
-                            else { reset }
-                        } else {
-                            reset
-                        };
-
+                if seg < part.end {
                     out.push_str(color);
-                    out.push_str(&word);
+                    out.push_str(&line[seg..part.end]);
                     out.push_str(reset);
-                    is_cmd = false;
                 }
             }
+            // Po `(` lub `{` dalej pozycja komendy
+            is_cmd = group_only && !tok.value.ends_with(')') && tok.value != "}";
         }
+        out.push_str(&line[last..]);
 
         Owned(out)
     }
@@ -484,7 +757,17 @@ impl Highlighter for ShellHelper {
         prompt: &'p str,
         default: bool,
     ) -> Cow<'b, str> {
-        if default { Borrowed(&self.colored_prompt) } else { Borrowed(prompt) }
+        if !default {
+            return Borrowed(prompt);
+        }
+        match &self.vi_normal {
+            Some(normal) if normal.load(Ordering::Relaxed) => Owned(self.colored_prompt.replacen(
+                &crate::prompt::vi_mode_label(false),
+                &crate::prompt::vi_mode_label(true),
+                1,
+            )),
+            _ => Borrowed(&self.colored_prompt),
+        }
     }
 
     fn highlight_hint<'h>(&self, hint: &'h str) -> Cow<'h, str> {
@@ -496,6 +779,41 @@ impl Highlighter for ShellHelper {
     }
 }
 
+/// Pozycje nawiasów grupujących → głębokość pary (None — bez pary).
+/// Liczą się `(`/`)` w niezacytowanym tekście i `{`/`}` jako osobne słowa;
+/// `$(…)`, `${…}` i cudzysłowy są częściami Var/Double, więc nie wchodzą.
+fn bracket_depths(line: &str, tokens: &[crate::tokenize::Token]) -> HashMap<usize, Option<usize>> {
+    let mut depths = HashMap::new();
+    let mut stack: Vec<(usize, char)> = Vec::new();
+    for tok in tokens.iter().filter(|t| t.kind == TokenKind::Word) {
+        let standalone_brace = matches!(tok.value.as_str(), "{" | "}");
+        for part in tok.parts.iter().filter(|p| p.kind == PartKind::Plain) {
+            for (i, c) in line[part.start..part.end].char_indices() {
+                let pos = part.start + i;
+                match c {
+                    '(' => stack.push((pos, ')')),
+                    '{' if standalone_brace => stack.push((pos, '}')),
+                    ')' | '}' if c == ')' || standalone_brace => {
+                        match stack.last() {
+                            Some(&(open, close)) if close == c => {
+                                stack.pop();
+                                depths.insert(open, Some(stack.len()));
+                                depths.insert(pos, Some(stack.len()));
+                            }
+                            _ => { depths.insert(pos, None); }
+                        }
+                    }
+                    _ => {}
+                }
+            }
+        }
+    }
+    for (open, _) in stack {
+        depths.insert(open, None);
+    }
+    depths
+}
+
 // ─── Validator ────────────────────────────────────────────────────────────────
 
 impl Validator for ShellHelper {
@@ -511,6 +829,12 @@ impl Validator for ShellHelper {
     fn validate_while_typing(&self) -> bool { false }
 }
 
+/// Czy tekst to kompletna komenda (zamknięte bloki i cudzysłowy) —
+/// te same zasady co przy Enter w REPL; używane też przez `source`
+pub fn is_complete_input(input: &str) -> bool {
+    !matches!(check_completeness(input), InputState::Incomplete(_))
+}
+
 #[derive(Debug)]
 enum InputState { Complete, Incomplete(String), Invalid(String) }
 
@@ -519,9 +843,13 @@ fn check_completeness(input: &str) -> InputState {
     let mut wh_d   = 0i32; let mut case_d = 0i32;
     let mut br_d   = 0i32;
     let mut in_s   = false; let mut in_d  = false;
+    // Znak po `\` jest dosłowny (poza '…'); `\` na samym końcu — kontynuacja
+    let mut escaped = false;
 
     for ch in input.chars() {
+        if escaped { escaped = false; continue; }
         match ch {
+            '\\' if !in_s => escaped = true,
             '\'' if !in_d => in_s = !in_s,
             '"'  if !in_s => in_d = !in_d,
             _ => {}
@@ -529,6 +857,15 @@ fn check_completeness(input: &str) -> InputState {
     }
     if in_s { return InputState::Incomplete("unclosed '".into()); }
     if in_d { return InputState::Incomplete("unclosed \"".into()); }
+    if escaped { return InputState::Incomplete("kontynuacja linii".into()); }
+
+    // `cmd |`, `cmd &&`, `cmd ||` — dalszy ciąg potoku / listy w następnej linii
+    let code = crate::execute::strip_comments(input);
+    if let Some(last) = tokenize(&code).last() {
+        if last.kind == TokenKind::Operator && matches!(&code[last.start..last.end], "|" | "&&" | "||") {
+            return InputState::Incomplete(format!("brakuje komendy po '{}'", &code[last.start..last.end]));
+        }
+    }
 
     for tok in input.split_whitespace() {
         match tok {
@@ -553,8 +890,221 @@ fn check_completeness(input: &str) -> InputState {
     if wh_d   > 0 { return InputState::Incomplete(format!("brakuje 'done' ({})", wh_d));   }
     if case_d > 0 { return InputState::Incomplete(format!("brakuje 'esac' ({})", case_d)); }
     if br_d   > 0 { return InputState::Incomplete(format!("brakuje '}}' ({})",   br_d));   }
-    if input.trim_end().ends_with('\\') {
-        return InputState::Incomplete("kontynuacja linii".into());
-    }
     InputState::Complete
 }
+
+/// Sklej linie kontynuowane `\` — usuń niecytowane pary backslash+nowa linia
+/// (w '…' zostają dosłownie, jak w sh)
+pub fn join_continuations(input: &str) -> String {
+    let mut out = String::with_capacity(input.len());
+    let (mut in_s, mut in_d) = (false, false);
+    let mut chars = input.chars().peekable();
+    while let Some(ch) = chars.next() {
+        match ch {
+            '\\' if !in_s => {
+                match chars.next() {
+                    Some('\n') => {}
+                    Some(next)  => { out.push(ch); out.push(next); }
+                    None        => out.push(ch),
+                }
+                continue;
+            }
+            '\'' if !in_d => in_s = !in_s,
+            '"'  if !in_s => in_d = !in_d,
+            _ => {}
+        }
+        out.push(ch);
+    }
+    out
+}
+
+// ─── Fuzzy finder historii (Ctrl+T) ───────────────────────────────────────────
+
+/// Ctrl+T: wybór z historii przez `fzf` (jeśli zainstalowany) albo wbudowany
+/// minimalny selektor. Wybrana komenda jest wstawiana w miejscu kursora.
+/// `entries` — najnowsze unikalne komendy, odświeżane przez pętlę REPL.
+pub struct FuzzyHistoryHandler {
+    pub entries: Arc<Mutex<Vec<String>>>,
+}
+
+impl ConditionalEventHandler for FuzzyHistoryHandler {
+    fn handle(&self, _evt: &Event, _n: RepeatCount, _positive: bool, _ctx: &EventContext) -> Option<Cmd> {
+        let entries = self.entries.lock().map(|e| e.clone()).unwrap_or_default();
+        if entries.is_empty() { return Some(Cmd::Noop); }
+
+        let picked = if command_in_path("fzf") {
+            pick_with_fzf(&entries)
+        } else {
+            pick_builtin(&entries)
+        };
+        match picked {
+            Some(cmd) => Some(Cmd::Insert(1, cmd)),
+            None      => Some(Cmd::Repaint),
+        }
+    }
+}
+
+// ─── Wskaźnik trybu vi ({vi_mode}) ────────────────────────────────────────────
+
+/// Śledzi tryb vi dla {vi_mode}. Rustyline zdradza tryb tylko handlerom
+/// klawiszy, więc handler pod `Event::Any` widzi tryb sprzed klawisza
+/// i przewiduje następny: Esc przechodzi do NORMAL, i/a/I/A/o/O/s/S/C/R
+/// wracają do INSERT. Nic nie zmienia — zawsze oddaje klawisz rustyline.
+/// Prompt odświeża się przy najbliższym przerysowaniu linii.
+pub struct ViModeHandler {
+    pub normal: Arc<AtomicBool>,
+}
+
+impl ConditionalEventHandler for ViModeHandler {
+    fn handle(&self, evt: &Event, _n: RepeatCount, _positive: bool, ctx: &EventContext) -> Option<Cmd> {
+        let key = evt.get(0);
+        let normal = match ctx.input_mode() {
+            InputMode::Command => !matches!(
+                key,
+                Some(KeyEvent(KeyCode::Char('i' | 'a' | 'I' | 'A' | 'o' | 'O' | 's' | 'S' | 'C' | 'R'), Modifiers::NONE))
+            ),
+            _ => matches!(key, Some(KeyEvent(KeyCode::Esc, Modifiers::NONE))),
+        };
+        self.normal.store(normal, Ordering::Relaxed);
+        None
+    }
+}
+
+fn command_in_path(name: &str) -> bool {
+    find_in_path(name).is_some()
+}
+
+/// Pierwszy wykonywalny plik `name` w katalogach $PATH
+pub fn find_in_path(name: &str) -> Option<PathBuf> {
+    env::var("PATH")
+        .ok()?
+        .split(':')
+        .filter(|d| !d.is_empty())
+        .map(|d| Path::new(d).join(name))
+        .find(|p| is_executable_file(p))
+}
+
+/// fzf rysuje na /dev/tty, więc stdin/stdout mogą być potokami
+fn pick_with_fzf(entries: &[String]) -> Option<String> {
+    use std::io::Write;
+    use std::process::{Command, Stdio};
+
+    let mut child = Command::new("fzf")
+        .args(["--height", "40%", "--reverse", "--tiebreak=index", "--prompt", "history> "])
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .spawn()
+        .ok()?;
+    if let Some(mut stdin) = child.stdin.take() {
+        let _ = stdin.write_all(entries.join("\n").as_bytes());
+    }
+    let out = child.wait_with_output().ok()?;
+    let sel = String::from_utf8_lossy(&out.stdout).trim_end_matches('\n').to_string();
+    if out.status.success() && !sel.is_empty() { Some(sel) } else { None }
+}
+
+/// Wbudowany selektor: pisanie filtruje (fuzzy), 1–9 wybiera, Enter — pierwszy
+/// wynik, Esc / Ctrl+C anuluje. Terminal jest już w trybie raw (rustyline).
+fn pick_builtin(entries: &[String]) -> Option<String> {
+    use fuzzy_matcher::skim::SkimMatcherV2;
+    use fuzzy_matcher::FuzzyMatcher;
+    use std::io::{Read, Write};
+
+    let matcher   = SkimMatcherV2::default();
+    let mut query = String::new();
+    let mut err   = std::io::stderr();
+    let clear     = "\x1b7\r\n\x1b[J\x1b8";
+
+    let result = loop {
+        let mut matches: Vec<(i64, &String)> = entries
+            .iter()
+            .filter_map(|e| {
+                if query.is_empty() { Some((0, e)) }
+                else { matcher.fuzzy_match(e, &query).map(|s| (s, e)) }
+            })
+            .collect();
+        // sort stabilny — przy równym wyniku zostaje kolejność od najnowszych
+        matches.sort_by_key(|&(score, _)| std::cmp::Reverse(score));
+        matches.truncate(9);
+
+        let mut screen = String::from("\x1b7\r\n\x1b[J");
+        for (i, (_, cmd)) in matches.iter().enumerate() {
+            screen.push_str(&format!("  \x1b[38;5;244m{}\x1b[0m  {}\r\n", i + 1, cmd));
+        }
+        screen.push_str(&format!("  \x1b[38;5;110mhistory>\x1b[0m {}\x1b8", query));
+        let _ = err.write_all(screen.as_bytes());
+        let _ = err.flush();
+
+        let mut byte = [0u8; 1];
+        if std::io::stdin().read(&mut byte).ok() != Some(1) { break None; }
+        match byte[0] {
+            0x1b | 0x03 | 0x07 => break None,
+            b'\r' | b'\n' => break matches.first().map(|(_, c)| (*c).clone()),
+            0x7f | 0x08 => { query.pop(); }
+            b @ b'1'..=b'9' if !matches.is_empty() => {
+                if let Some((_, c)) = matches.get((b - b'1') as usize) {
+                    break Some((*c).clone());
+                }
+            }
+            b if b.is_ascii_graphic() || b == b' ' => query.push(b as char),
+            _ => {}
+        }
+    };
+
+    let _ = err.write_all(clear.as_bytes());
+    let _ = err.flush();
+    result
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn helper_with(history: &[&str]) -> ShellHelper {
+        let mut helper = ShellHelper::new(Theme::default());
+        for cmd in history {
+            let word = cmd.split_whitespace().next().unwrap().to_string();
+            helper.hints_snapshot.entry(word).or_default().push((cmd.to_string(), 1));
+        }
+        helper
+    }
+
+    #[test]
+    fn inline_hint_after_accented_filename_prefix() {
+        let helper = helper_with(&["cat élan.txt", "cat ćma.md"]);
+        assert_eq!(helper.inline_hint("cat é").as_deref(), Some("lan.txt"));
+        assert_eq!(helper.inline_hint("cat ćm").as_deref(), Some("a.md"));
+        assert_eq!(helper.inline_hint("cat ź"), None);
+        assert_eq!(helper.inline_hint("cat ćma.md"), None);
+    }
+
+    #[test]
+    fn inline_hint_for_non_ascii_command() {
+        let helper = helper_with(&["żółw --pomoc"]);
+        assert_eq!(helper.inline_hint("żółw --p").as_deref(), Some("omoc"));
+    }
+
+    #[test]
+    fn history_hint_wins_over_alias_preview() {
+        let mut helper = helper_with(&["gs --short"]);
+        helper.aliases.insert("gs".into(), "git status".into());
+        let history = rustyline::history::DefaultHistory::new();
+        let ctx = Context::new(&history);
+
+        let hint = helper.hint("gs", 2, &ctx).unwrap();
+        assert_eq!(hint.completion(), Some(" --short"));
+
+        helper.hints_snapshot.clear();
+        let hint = helper.hint("gs", 2, &ctx).unwrap();
+        assert_eq!((hint.display(), hint.completion()), (" → git status", None));
+    }
+
+    #[test]
+    fn command_exists_falls_back_to_path_on_cache_miss() {
+        let mut helper = helper_with(&[]);
+        helper.commands_cache.clear();
+        assert!(helper.command_exists("sh"));
+        assert!(!helper.command_exists("hsh-no-such-command"));
+        assert_eq!(helper.hits.get(), 0);
+    }
+}
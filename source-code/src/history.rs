@@ -3,18 +3,60 @@ use serde::{Deserialize, Serialize};
 use std::collections::HashSet;
 use std::fs;
 use std::io::Write;
+use std::os::unix::io::AsRawFd;
 use std::path::Path;
 
 #[derive(Serialize, Deserialize, Clone, Debug)]
 pub struct HistoryEntry {
     pub command:   String,
-    pub timestamp: DateTime<Local>,
+    /// Brak we wpisach ze starych plików (zwykły tekst, JSON bez czasu)
+    #[serde(default)]
+    pub timestamp: Option<DateTime<Local>>,
+}
+
+impl HistoryEntry {
+    /// Czas wpisu dla `history`; pusty (tej samej szerokości) przy braku czasu
+    pub fn time_text(&self) -> String {
+        match self.timestamp {
+            Some(t) => t.format("%Y-%m-%d %H:%M:%S").to_string(),
+            None    => " ".repeat(19),
+        }
+    }
+}
+
+/// Wpisy z zawartości pliku historii: JSON hsh albo stary format tekstowy
+/// (komenda w linii, bez czasu)
+fn parse_entries(data: &str) -> Vec<HistoryEntry> {
+    serde_json::from_str::<Vec<HistoryEntry>>(data).unwrap_or_else(|_| {
+        data.lines()
+            .filter(|l| !l.trim().is_empty())
+            .map(|l| HistoryEntry { command: l.to_string(), timestamp: None })
+            .collect()
+    })
 }
 
 pub struct ShellHistory {
     pub entries: Vec<HistoryEntry>,
     path:        String,
     dirty:       bool,
+    /// false w trybie bezpiecznym — historia tylko w pamięci
+    pub persist: bool,
+    /// Wspólna historia między sesjami ([history] mode = "shared"): każdy wpis
+    /// trafia do pliku od razu, a przed promptem dociągane są wpisy innych sesji.
+    /// Scalanie odbywa się pod flock, ale plik rustyline (`append_history`)
+    /// ma własną blokadę — przy jednoczesnym Enter w dwóch terminalach kolejność
+    /// wpisów w Ctrl+R może się różnić, żaden wpis nie ginie
+    pub shared:  bool,
+    /// Liczba wpisów zgodnych z plikiem po ostatniej synchronizacji
+    synced_len:  usize,
+    /// Maksymalna liczba wpisów — najstarsze są usuwane (0 — bez limitu)
+    pub max_entries: usize,
+    /// Pomijaj komendę identyczną z poprzednią ([history] ignore_dups)
+    pub ignore_dups: bool,
+    /// Wzorce glob komend, których nie zapisujemy ([history] ignore_patterns)
+    pub ignore_patterns: Vec<String>,
+    /// Kolumna czasu w `history` ([history] timestamps)
+    pub timestamps: bool,
 }
 
 impl ShellHistory {
@@ -24,28 +66,98 @@ impl ShellHistory {
         let canonical = resolve_history_path(path);
         let entries = if Path::new(&canonical).exists() {
             fs::read_to_string(&canonical)
-                .ok()
-                .and_then(|data| serde_json::from_str::<Vec<HistoryEntry>>(&data).ok())
+                .map(|data| parse_entries(&data))
                 .unwrap_or_default()
         } else {
             Vec::new()
         };
+        let synced_len = entries.len();
         ShellHistory {
             entries,
             path: canonical,
             dirty: false,
+            persist: true,
+            shared: false,
+            synced_len,
+            max_entries: 0,
+            ignore_dups: true,
+            ignore_patterns: Vec::new(),
+            timestamps: true,
         }
     }
 
+    /// Historia tylko w pamięci — dla hsh -c i skryptów (bez czytania pliku)
+    pub fn ephemeral() -> Self {
+        ShellHistory {
+            entries: Vec::new(),
+            path: String::new(),
+            dirty: false,
+            persist: false,
+            shared: false,
+            synced_len: 0,
+            max_entries: 0,
+            ignore_dups: true,
+            ignore_patterns: Vec::new(),
+            timestamps: true,
+        }
+    }
+
+    /// Czy linia pasuje do któregoś z `ignore_patterns` — sprawdzana przed
+    /// przycięciem, więc wzorzec `" *"` łapie komendy zaczynające się od spacji
+    pub fn is_ignored(&self, line: &str) -> bool {
+        self.ignore_patterns.iter().any(|p| crate::expand::glob_match(p, line))
+    }
+
+    /// Usuń najstarsze wpisy ponad `max_entries`
+    fn trim(&mut self) {
+        if self.max_entries == 0 || self.entries.len() <= self.max_entries { return; }
+        let excess = self.entries.len() - self.max_entries;
+        self.entries.drain(..excess);
+        self.synced_len = self.synced_len.saturating_sub(excess);
+    }
+
     /// Zapisz historię do pliku JSON.
     /// Tworzy katalog nadrzędny jeśli nie istnieje.
-    pub fn save(&self, _path: &str) {
+    pub fn save(&mut self, _path: &str) {
         // Używamy self.path (canonical), ignorujemy przekazany argument
         // żeby nie zapisywać do błędnej lokalizacji
-        self.save_to(&self.path.clone());
+        if self.shared {
+            self.sync();
+        } else {
+            self.save_to(&self.path.clone());
+        }
+    }
+
+    /// Scal historię z plikiem pod blokadą (tryb shared_history): wpisy
+    /// z dysku (w tym innych sesji) + nowe lokalne, posortowane po czasie.
+    pub fn sync(&mut self) {
+        if !self.shared || !self.persist { return; }
+        let lock_path = format!("{}.lock", self.path);
+        let Ok(lock) = fs::OpenOptions::new().create(true).write(true).truncate(false).open(&lock_path) else { return };
+        // flock zwalniany przy zamknięciu pliku (koniec funkcji)
+        if unsafe { libc::flock(lock.as_raw_fd(), libc::LOCK_EX) } != 0 { return; }
+
+        let on_disk: Vec<HistoryEntry> = fs::read_to_string(&self.path)
+            .map(|data| parse_entries(&data))
+            .unwrap_or_default();
+        let local_new = self.entries.split_off(self.synced_len.min(self.entries.len()));
+        let changed = !local_new.is_empty();
+
+        let mut merged = on_disk;
+        merged.extend(local_new);
+        // sort stabilny — równe znaczniki czasu zachowują kolejność
+        merged.sort_by_key(|e| e.timestamp);
+        self.entries    = merged;
+        self.trim();
+        self.synced_len = self.entries.len();
+        if changed {
+            self.save_to(&self.path.clone());
+        }
+        self.dirty = false;
     }
 
     pub fn save_to(&self, path: &str) {
+        if !self.persist { return; }
         if let Some(parent) = Path::new(path).parent() {
             if !parent.as_os_str().is_empty() {
                 let _ = fs::create_dir_all(parent);
@@ -67,21 +179,25 @@ impl ShellHistory {
         }
     }
 
-    /// Dodaj wpis — pomijaj kolejne duplikaty i komendy zaczynające się od spacji.
+    /// Dodaj wpis — pomijaj kolejne duplikaty (ignore_dups).
     pub fn add(&mut self, cmd: &str) {
         let cmd = cmd.trim();
         if cmd.is_empty() { return; }
         // Pomiń jeśli taka sama jak ostatnia
-        if self.entries.last().map(|e| e.command.as_str()) == Some(cmd) {
+        if self.ignore_dups && self.entries.last().map(|e| e.command.as_str()) == Some(cmd) {
             return;
         }
         self.entries.push(HistoryEntry {
             command:   cmd.to_string(),
-            timestamp: Local::now(),
+            timestamp: Some(Local::now()),
         });
+        self.trim();
         self.dirty = true;
-        // Auto-zapis co 10 wpisów
-        if self.entries.len() % 10 == 0 {
+        if self.shared {
+            // Wspólna historia — dopisz od razu, żeby inne sesje ją widziały
+            self.sync();
+        } else if self.entries.len() % 10 == 0 {
+            // Auto-zapis co 10 wpisów
             self.save_to(&self.path.clone());
         }
     }
@@ -91,19 +207,56 @@ impl ShellHistory {
         self.entries.last().map(|e| e.command.clone())
     }
 
-    /// Wypisz wszystkie wpisy (nowsze na górze, maks 1000).
+    /// Wypisz wpisy chronologicznie (najnowsze na dole, maks 1000 ostatnich).
     pub fn print_all(&self) {
         let start = self.entries.len().saturating_sub(1000);
-        for (i, entry) in self.entries[start..].iter().enumerate().rev() {
+        for (i, entry) in self.entries[start..].iter().enumerate() {
+            self.print_entry(start + i + 1, entry);
+        }
+    }
+
+    /// Wypisz chronologicznie wpisy spełniające warunek (indeks od 0, komenda)
+    /// z ich stałymi numerami — tymi samymi co w `history` i `!N`.
+    pub fn print_matching<F: Fn(usize, &str) -> bool>(&self, pred: F) {
+        for (i, entry) in self.entries.iter().enumerate() {
+            if pred(i, &entry.command) {
+                self.print_entry(i + 1, entry);
+            }
+        }
+    }
+
+    /// `numer  [czas]  komenda` — czas tylko przy [history] timestamps
+    fn print_entry(&self, number: usize, entry: &HistoryEntry) {
+        if self.timestamps {
             println!(
                 "\x1b[38;5;242m{:5}\x1b[0m  \x1b[38;5;238m{}\x1b[0m  {}",
-                start + i + 1,
-                entry.timestamp.format("%Y-%m-%d %H:%M:%S"),
+                number,
+                entry.time_text(),
                 entry.command
             );
+        } else {
+            println!("\x1b[38;5;242m{:5}\x1b[0m  {}", number, entry.command);
         }
     }
 
+    /// Wyczyść historię (history -c) — także w pliku
+    pub fn clear(&mut self) {
+        self.entries.clear();
+        self.synced_len = 0;
+        self.dirty = true;
+        self.save_to(&self.path.clone());
+    }
+
+    /// Wpis o numerze `n` (1 = najstarszy), jak w wyjściu `history`
+    pub fn nth(&self, n: usize) -> Option<String> {
+        n.checked_sub(1).and_then(|i| self.entries.get(i)).map(|e| e.command.clone())
+    }
+
+    /// Najnowsza komenda zaczynająca się od `prefix` (dla `!prefix`)
+    pub fn last_with_prefix(&self, prefix: &str) -> Option<String> {
+        self.entries.iter().rev().find(|e| e.command.starts_with(prefix)).map(|e| e.command.clone())
+    }
+
     /// Fuzzy search — zwraca deduplikowane wyniki posortowane wg score.
     pub fn fuzzy_search(&self, query: &str) -> Vec<&HistoryEntry> {
         use fuzzy_matcher::skim::SkimMatcherV2;
@@ -118,7 +271,7 @@ impl ShellHistory {
                     .map(|score| (score, e))
             })
             .collect();
-        scored.sort_by(|a, b| b.0.cmp(&a.0));
+        scored.sort_by_key(|m| std::cmp::Reverse(m.0));
         let mut seen = HashSet::new();
         scored
             .into_iter()
@@ -148,6 +301,149 @@ impl ShellHistory {
     }
 }
 
+/// Rozwinięcie historii na poprzedniej komendzie:
+/// `!!`, `!!:s/stare/nowe/`, `!!:gs/stare/nowe/` oraz szybka podmiana `^stare^nowe^`.
+/// `!N` / `!-N` wstawiają wpis o numerze N z `history` (lub N-ty od końca),
+/// `!prefix` — najnowszą komendę zaczynającą się od prefix. `!` w środku
+/// słowa (URL, `a!b`) lub przed spacją/`=`/`(` zostaje dosłownie.
+/// Zwraca Ok(None), gdy linia nie zawiera rozwinięcia.
+pub fn expand_history(line: &str, history: &ShellHistory) -> Result<Option<String>, String> {
+    let trimmed = line.trim();
+
+    // ^stare^nowe^ — pierwsze wystąpienie w poprzedniej komendzie
+    if let Some(rest) = trimmed.strip_prefix('^') {
+        let prev = history.last_command().ok_or("^: event not found")?;
+        let mut parts = rest.splitn(3, '^');
+        let old  = parts.next().unwrap_or("");
+        let new  = parts.next().unwrap_or("");
+        let tail = parts.next().unwrap_or("");
+        if old.is_empty() || !prev.contains(old) {
+            return Err(format!("^{}^{}: substitution failed", old, new));
+        }
+        return Ok(Some(format!("{}{}", prev.replacen(old, new, 1), tail)));
+    }
+
+    if !line.contains('!') { return Ok(None); }
+
+    let chars: Vec<char> = line.chars().collect();
+    let mut out      = String::with_capacity(line.len() * 2);
+    let mut in_s     = false;
+    let mut expanded = false;
+    let mut i = 0;
+    while i < chars.len() {
+        let c = chars[i];
+        match c {
+            '\\' if !in_s => {
+                out.push(c);
+                if let Some(&nc) = chars.get(i + 1) { out.push(nc); }
+                i += 2;
+            }
+            '\'' => { in_s = !in_s; out.push(c); i += 1; }
+            '!' if !in_s && chars.get(i + 1) == Some(&'!') => {
+                i += 2;
+                let prev = history.last_command().ok_or("!!: event not found")?;
+                let (text, next) = apply_modifier(&prev, &chars, i)?;
+                out.push_str(&text);
+                i = next;
+                expanded = true;
+            }
+            '!' if !in_s && event_number_len(&chars, i + 1) > 0 => {
+                let len = event_number_len(&chars, i + 1);
+                let spec: String = chars[i + 1..i + 1 + len].iter().collect();
+                let entry = match spec.strip_prefix('-') {
+                    Some(back) => back.parse::<usize>().ok()
+                        .and_then(|n| (history.len() + 1).checked_sub(n))
+                        .and_then(|n| history.nth(n)),
+                    None => spec.parse().ok().and_then(|n| history.nth(n)),
+                };
+                let entry = entry.ok_or_else(|| format!("!{}: event not found", spec))?;
+                i += 1 + len;
+                let (text, next) = apply_modifier(&entry, &chars, i)?;
+                out.push_str(&text);
+                i = next;
+                expanded = true;
+            }
+            '!' if !in_s && event_prefix_len(&chars, i) > 0 => {
+                let len    = event_prefix_len(&chars, i);
+                let prefix: String = chars[i + 1..i + 1 + len].iter().collect();
+                let entry  = history.last_with_prefix(&prefix)
+                    .ok_or_else(|| format!("!{}: event not found", prefix))?;
+                i += 1 + len;
+                let (text, next) = apply_modifier(&entry, &chars, i)?;
+                out.push_str(&text);
+                i = next;
+                expanded = true;
+            }
+            _ => { out.push(c); i += 1; }
+        }
+    }
+    Ok(if expanded { Some(out) } else { None })
+}
+
+/// Długość numeru zdarzenia po `!` (`12`, `-2`); 0 — to nie jest `!N`
+fn event_number_len(chars: &[char], start: usize) -> usize {
+    let sign   = usize::from(chars.get(start) == Some(&'-'));
+    let digits = chars[(start + sign).min(chars.len())..]
+        .iter()
+        .take_while(|c| c.is_ascii_digit())
+        .count();
+    if digits == 0 { 0 } else { sign + digits }
+}
+
+/// Długość prefiksu w `!prefix` (`!` na pozycji `bang`); 0 — to nie jest
+/// rozwinięcie: `!` w środku słowa albo przed spacją, `=`, `(`, cudzysłowem
+fn event_prefix_len(chars: &[char], bang: usize) -> usize {
+    if bang > 0 && !chars[bang - 1].is_whitespace() && !";|&(".contains(chars[bang - 1]) {
+        return 0;
+    }
+    match chars.get(bang + 1) {
+        Some(c) if c.is_whitespace() || "=(\"'".contains(*c) => return 0,
+        None => return 0,
+        _ => {}
+    }
+    chars[bang + 1..]
+        .iter()
+        .take_while(|c| !c.is_whitespace() && !";|&:".contains(**c))
+        .count()
+}
+
+/// Modyfikator po `!!` / `!N` / `!prefix`: `:s/stare/nowe/` (pierwsze) lub `:gs/stare/nowe/` (wszystkie).
+/// Separator jest dowolnym znakiem po `s`; końcowy separator jest opcjonalny.
+fn apply_modifier(prev: &str, chars: &[char], start: usize) -> Result<(String, usize), String> {
+    let rest: String = chars[start..].iter().collect();
+    let (global, skip) = if rest.starts_with(":gs") {
+        (true, 3)
+    } else if rest.starts_with(":s") {
+        (false, 2)
+    } else {
+        return Ok((prev.to_string(), start));
+    };
+
+    let mut i = start + skip;
+    let Some(&delim) = chars.get(i) else {
+        return Err(":s: missing delimiter".to_string());
+    };
+    i += 1;
+    let read_part = |i: &mut usize| {
+        let mut s = String::new();
+        while *i < chars.len() && chars[*i] != delim {
+            s.push(chars[*i]);
+            *i += 1;
+        }
+        if *i < chars.len() { *i += 1; }
+        s
+    };
+    let old = read_part(&mut i);
+    let new = read_part(&mut i);
+
+    if old.is_empty() || !prev.contains(&old) {
+        return Err(format!("!!:{}s{}{}{}: substitution failed",
+                           if global { "g" } else { "" }, delim, old, delim));
+    }
+    let text = if global { prev.replace(&old, &new) } else { prev.replacen(&old, &new, 1) };
+    Ok((text, i))
+}
+
 /// Rozwiąż ścieżkę historii — zawsze do katalogu HOME użytkownika.
 fn resolve_history_path(path: &str) -> String {
     // Jeśli ścieżka zawiera tylko nazwę pliku (bez /), umieść w HOME
@@ -168,7 +464,7 @@ fn resolve_history_path(path: &str) -> String {
 impl Drop for ShellHistory {
     fn drop(&mut self) {
         if self.dirty {
-            self.save_to(&self.path.clone());
+            self.save("");
         }
     }
 }
@@ -1,7 +1,26 @@
 use std::collections::HashMap;
+use std::process::Child;
 use nix::sys::signal::{kill, Signal};
+use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
 use nix::unistd::Pid;
 
+extern "C" fn ignore_signal(_: libc::c_int) {}
+
+/// Interaktywna powłoka przeżywa Ctrl+C i Ctrl+\ w trakcie komendy — sygnał
+/// z terminala trafia do całej grupy pierwszoplanowej, więc dziecko i tak go
+/// dostaje. Pusty handler zamiast SIG_IGN: po exec wraca obsługa domyślna,
+/// a zignorowany sygnał dziecko by odziedziczyło.
+pub fn install_interactive_signals() {
+    unsafe {
+        let mut sa: libc::sigaction = std::mem::zeroed();
+        sa.sa_sigaction = ignore_signal as extern "C" fn(libc::c_int) as libc::sighandler_t;
+        sa.sa_flags = libc::SA_RESTART;
+        libc::sigemptyset(&mut sa.sa_mask);
+        libc::sigaction(libc::SIGINT, &sa, std::ptr::null_mut());
+        libc::sigaction(libc::SIGQUIT, &sa, std::ptr::null_mut());
+    }
+}
+
 #[derive(Debug, Clone, PartialEq)]
 pub enum JobStatus {
     Running,
@@ -12,7 +31,10 @@ pub enum JobStatus {
 #[derive(Debug, Clone)]
 pub struct Job {
     pub id:      usize,
+    /// PID lidera — zarazem id grupy procesów zadania
     pub pid:     u32,
+    /// Wszystkie procesy zadania (etapy potoku), jeszcze nie zebrane
+    pub pids:    Vec<u32>,
     pub command: String,
     pub status:  JobStatus,
 }
@@ -30,14 +52,27 @@ impl JobTable {
         }
     }
 
-    pub fn add(&mut self, pid: u32, command: &str) -> usize {
-        let id = self.next_id;
+    /// Zarejestruj zadanie w tle na podstawie uruchomionych procesów.
+    ///
+    /// Uchwyty `Child` są celowo porzucane: drop w std nie zabija procesu
+    /// ani na niego nie czeka, więc dziecko działa niezależnie od runtime'u
+    /// tokio i od tego, czy prompt jest bezczynny. Zbieraniem (waitpid)
+    /// zajmuje się wyłącznie tablica zadań — `check_finished` przy każdym
+    /// prompcie oraz `fg` / `wait`. Pierwszy proces musi być liderem grupy
+    /// (`process_group(0)`), pozostałe dołączają do jego grupy.
+    pub fn add_children(&mut self, children: Vec<Child>, command: &str) -> usize {
+        let pids: Vec<u32> = children.iter().map(|c| c.id()).collect();
+        drop(children);
+
+        let id  = self.next_id;
+        let pid = pids.first().copied().unwrap_or(0);
         self.next_id += 1;
         self.jobs.insert(
             id,
             Job {
                 id,
                 pid,
+                pids,
                 command: command.to_string(),
                          status: JobStatus::Running,
             },
@@ -46,82 +81,242 @@ impl JobTable {
         id
     }
 
-    pub fn list(&self) {
-        let mut ids: Vec<usize> = self.jobs.keys().cloned().collect();
-        ids.sort();
-        for id in ids {
+    /// Wypisz wybrane zadania; `long` (jobs -l) dodaje PID.
+    pub fn list(&self, ids: &[usize], long: bool) {
+        let (current, previous) = self.current_and_previous();
+        for &id in ids {
             if let Some(job) = self.jobs.get(&id) {
                 let status = match job.status {
                     JobStatus::Running => "Running",
                     JobStatus::Stopped => "Stopped",
                     JobStatus::Done    => "Done",
                 };
-                println!("[{}] {:8}  {}  {}", job.id, status, job.pid, job.command);
+                let marker = if Some(id) == current {
+                    '+'
+                } else if Some(id) == previous {
+                    '-'
+                } else {
+                    ' '
+                };
+                if long {
+                    println!("[{}]{} {:>7} {:8}  {}", job.id, marker, job.pid, status, job.command);
+                } else {
+                    println!("[{}]{} {:8}  {}", job.id, marker, status, job.command);
+                }
             }
         }
     }
 
-    pub fn fg(&mut self, id: usize) -> Option<u32> {
+    pub fn get(&self, id: usize) -> Option<&Job> {
+        self.jobs.get(&id)
+    }
+
+    /// PID lidera zadania (jobs -p)
+    pub fn pid_of(&self, id: usize) -> Option<u32> {
         self.jobs.get(&id).map(|j| j.pid)
     }
 
-    pub fn remove(&mut self, id: usize) {
-        self.jobs.remove(&id);
+    /// Id zadań w kolejności; `status` — tylko w danym stanie (jobs -r / -s)
+    pub fn ids(&self, status: Option<JobStatus>) -> Vec<usize> {
+        self.sorted_ids()
+            .into_iter()
+            .filter(|id| status.as_ref().map(|s| self.jobs.get(id).map(|j| &j.status) == Some(s)).unwrap_or(true))
+            .collect()
     }
 
-    pub fn mark_done(&mut self, id: usize) {
-        if let Some(j) = self.jobs.get_mut(&id) {
-            j.status = JobStatus::Done;
+    fn sorted_ids(&self) -> Vec<usize> {
+        let mut ids: Vec<usize> = self.jobs.keys().cloned().collect();
+        ids.sort();
+        ids
+    }
+
+    /// Bieżące (%+) i poprzednie (%-) zadanie — dwa ostatnio uruchomione.
+    fn current_and_previous(&self) -> (Option<usize>, Option<usize>) {
+        let ids = self.sorted_ids();
+        let mut rev = ids.into_iter().rev();
+        let current  = rev.next();
+        let previous = rev.next();
+        (current, previous)
+    }
+
+    /// Rozwiąż specyfikację zadania na jego id.
+    ///
+    /// Obsługiwane: `%N`/`N`, `%%`/`%+`/puste (bieżące), `%-` (poprzednie),
+    /// `%str` (komenda zaczyna się od str), `%?str` (komenda zawiera str).
+    pub fn resolve_spec(&self, spec: &str) -> Result<usize, String> {
+        let spec = spec.trim();
+        let (current, previous) = self.current_and_previous();
+        let no_such = || format!("{}: no such job", if spec.is_empty() { "current" } else { spec });
+
+        let body = spec.strip_prefix('%').unwrap_or(spec);
+        match body {
+            "" | "%" | "+" => current.ok_or_else(no_such),
+            "-"            => previous.or(current).ok_or_else(no_such),
+            _ if body.chars().all(|c| c.is_ascii_digit()) => {
+                let id: usize = body.parse().map_err(|_| no_such())?;
+                if self.jobs.contains_key(&id) { Ok(id) } else { Err(no_such()) }
+            }
+            _ if spec.starts_with('%') => {
+                let (needle, contains) = match body.strip_prefix('?') {
+                    Some(n) => (n, true),
+                    None    => (body, false),
+                };
+                let matches: Vec<usize> = self
+                    .sorted_ids()
+                    .into_iter()
+                    .filter(|id| {
+                        let cmd = &self.jobs[id].command;
+                        if contains { cmd.contains(needle) } else { cmd.starts_with(needle) }
+                    })
+                    .collect();
+                match matches.as_slice() {
+                    [id] => Ok(*id),
+                    []   => Err(no_such()),
+                    _    => Err(format!("{}: ambiguous job spec", spec)),
+                }
+            }
+            _ => Err(no_such()),
         }
     }
 
+    pub fn remove(&mut self, id: usize) {
+        self.jobs.remove(&id);
+    }
+
+    /// Wyślij sygnał do całej grupy procesów zadania (wszystkie etapy potoku)
     pub fn send_signal(&self, id: usize, sig: Signal) -> bool {
         if let Some(job) = self.jobs.get(&id) {
-            kill(Pid::from_raw(job.pid as i32), sig).is_ok()
+            kill(Pid::from_raw(-(job.pid as i32)), sig).is_ok()
+                || kill(Pid::from_raw(job.pid as i32), sig).is_ok()
         } else {
             false
         }
     }
 
     /// Wznowienie w tle (bg)
-    pub fn bg(&self, id: usize) -> bool {
-        self.send_signal(id, Signal::SIGCONT)
+    pub fn bg(&mut self, id: usize) -> bool {
+        let ok = self.send_signal(id, Signal::SIGCONT);
+        if ok { self.set_status(id, JobStatus::Running); }
+        ok
     }
 
     /// Zatrzymanie (stop)
-    pub fn stop(&self, id: usize) -> bool {
-        self.send_signal(id, Signal::SIGSTOP)
+    pub fn stop(&mut self, id: usize) -> bool {
+        let ok = self.send_signal(id, Signal::SIGSTOP);
+        if ok { self.set_status(id, JobStatus::Stopped); }
+        ok
+    }
+
+    fn set_status(&mut self, id: usize, status: JobStatus) {
+        if let Some(j) = self.jobs.get_mut(&id) {
+            j.status = status;
+        }
+    }
+
+    /// Czekaj (blokująco) na zakończenie zadania i zwróć jego kod wyjścia.
+    ///
+    /// `foreground` (fg) oddaje terminal grupie zadania i wznawia ją SIGCONT;
+    /// jeśli zadanie zostanie ponownie zatrzymane (Ctrl+Z), wraca do tablicy
+    /// jako Stopped, a terminal wraca do powłoki.
+    pub fn wait_job(&mut self, id: usize, foreground: bool) -> Option<i32> {
+        let job  = self.jobs.get(&id)?.clone();
+        let pgid = job.pid as libc::pid_t;
+        let tty  = foreground && unsafe { libc::isatty(libc::STDIN_FILENO) } == 1;
+
+        if tty {
+            unsafe {
+                libc::signal(libc::SIGTTOU, libc::SIG_IGN);
+                libc::tcsetpgrp(libc::STDIN_FILENO, pgid);
+            }
+        }
+        if foreground {
+            self.send_signal(id, Signal::SIGCONT);
+            self.set_status(id, JobStatus::Running);
+        }
+
+        let last    = job.pids.last().copied();
+        let flags   = if foreground { Some(WaitPidFlag::WUNTRACED) } else { None };
+        let mut code    = 0;
+        let mut stopped = false;
+        let mut left    = Vec::new();
+        for pid in job.pids {
+            if stopped { left.push(pid); continue; }
+            match waitpid(Pid::from_raw(pid as i32), flags) {
+                Ok(WaitStatus::Exited(_, c)) if Some(pid) == last => code = c,
+                Ok(WaitStatus::Signaled(_, sig, _)) if Some(pid) == last => code = 128 + sig as i32,
+                Ok(WaitStatus::Stopped(..)) => { stopped = true; left.push(pid); }
+                _ => {}
+            }
+        }
+
+        if tty {
+            unsafe {
+                libc::tcsetpgrp(libc::STDIN_FILENO, libc::getpgrp());
+                libc::signal(libc::SIGTTOU, libc::SIG_DFL);
+            }
+        }
+
+        if stopped {
+            if let Some(j) = self.jobs.get_mut(&id) {
+                j.pids   = left;
+                j.status = JobStatus::Stopped;
+                println!("\n[{}]+ Stopped  {}", j.id, j.command);
+            }
+            return Some(128 + Signal::SIGTSTP as i32);
+        }
+        self.jobs.remove(&id);
+        Some(code)
     }
 
-    /// Sprawdź zakończone zadania (non-blocking)
+    /// Czekaj na wszystkie zadania (wait bez argumentu)
+    pub fn wait_all(&mut self) {
+        for id in self.sorted_ids() {
+            self.wait_job(id, false);
+        }
+    }
+
+    /// Sprawdź zakończone zadania (non-blocking). Zbiera każdy etap potoku;
+    /// zadanie jest skończone, gdy zakończyły się wszystkie jego procesy.
     pub fn check_finished(&mut self) {
-        use nix::sys::wait::{waitpid, WaitPidFlag, WaitStatus};
-
-        let ids: Vec<usize> = self.jobs.keys().cloned().collect();
-        for id in ids {
-            let pid = match self.jobs.get(&id) {
-                Some(j) => Pid::from_raw(j.pid as i32),
-                None    => continue,
-            };
-
-            match waitpid(pid, Some(WaitPidFlag::WNOHANG)) {
-                Ok(WaitStatus::Exited(_, code)) => {
-                    if let Some(job) = self.jobs.remove(&id) {
-                        println!(
-                            "\n\x1b[1;32m[{}] Done ({})\x1b[0m  {}",
-                                 job.id, code, job.command
-                        );
+        for id in self.sorted_ids() {
+            let Some(job) = self.jobs.get_mut(&id) else { continue };
+            let last = job.pids.last().copied();
+            let mut outcome = None;
+            let mut stopped = false;
+
+            job.pids.retain(|&pid| {
+                match waitpid(Pid::from_raw(pid as i32), Some(WaitPidFlag::WNOHANG | WaitPidFlag::WUNTRACED)) {
+                    Ok(WaitStatus::Exited(_, code)) => {
+                        if Some(pid) == last { outcome = Some(Ok(code)); }
+                        false
                     }
-                }
-                Ok(WaitStatus::Signaled(_, sig, _)) => {
-                    if let Some(job) = self.jobs.remove(&id) {
-                        println!(
-                            "\n\x1b[1;31m[{}] Killed ({})\x1b[0m  {}",
-                                 job.id, sig, job.command
-                        );
+                    Ok(WaitStatus::Signaled(_, sig, _)) => {
+                        if Some(pid) == last { outcome = Some(Err(sig)); }
+                        false
                     }
+                    Ok(WaitStatus::Stopped(..)) => { stopped = true; true }
+                    Ok(_)  => true,  // Still running
+                    Err(_) => false, // Już zebrany gdzie indziej
+                }
+            });
+            if stopped { job.status = JobStatus::Stopped; }
+            if !job.pids.is_empty() { continue; }
+
+            if let Some(job) = self.jobs.remove(&id) {
+                match outcome {
+                    Some(Err(sig)) => println!(
+                        "\n\x1b[1;31m[{}] Killed ({})\x1b[0m  {}",
+                             job.id, sig, job.command
+                    ),
+                    Some(Ok(code)) => println!(
+                        "\n\x1b[1;32m[{}] Done ({})\x1b[0m  {}",
+                             job.id, code, job.command
+                    ),
+                    None => println!(
+                        "\n\x1b[1;32m[{}] Done\x1b[0m  {}",
+                             job.id, job.command
+                    ),
                 }
-                _ => {} // Still running
             }
         }
     }
@@ -1,9 +1,12 @@
 mod arithmetic;
+mod battery;
 mod builtins;
 mod builtins_native;
 mod config;
 mod docs;
 mod execute;
+mod expand;
+mod frecency;
 mod git_info;
 mod helper;
 mod history;
@@ -16,23 +19,27 @@ mod security;
 mod settings;
 mod smarthints;
 mod theme;
+mod tokenize;
 mod vars;
 
 use std::env;
-use std::path::PathBuf;
+use std::io::{self, Write};
+use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::{Arc, Mutex};
 
 use rustyline::error::ReadlineError;
-use rustyline::{Cmd, CompletionType, Config, EditMode, Editor, KeyEvent};
+use rustyline::{Cmd, CompletionType, Config, EditMode, Editor, Event, EventHandler, KeyEvent, Movement};
 use sysinfo::{CpuRefreshKind, MemoryRefreshKind, RefreshKind, System};
 use tokio::process::Command as TokioCommand;
 
 use config::{load_shell_config, get_history_path, get_env_vars, get_shell_options};
-use execute::execute_command;
+use execute::{execute_command, ShellCtx};
 use git_info::spawn_git_watcher;
-use helper::ShellHelper;
-use history::ShellHistory;
-use jobs::JobTable;
+use helper::{FuzzyHistoryHandler, ShellHelper, ViModeHandler};
+use history::{expand_history, ShellHistory};
 use path_cache::PathCache;
+use script::FunctionTable;
 use settings::run_settings;
 use smarthints::SmartHints;
 use theme::Theme;
@@ -40,8 +47,14 @@ use vars::ShellVars;
 
 #[tokio::main]
 async fn main() -> rustyline::Result<()> {
-    let args: Vec<String> = env::args().collect();
+    let args = split_short_flags(env::args().collect());
     let dry_run = args.contains(&"--dry-run".to_string());
+    let safe    = args.contains(&"--safe".to_string());
+    let posix   = args.contains(&"--posix".to_string());
+    let no_rc   = args.contains(&"--no-rc".to_string());
+    // Powłoka logowania: --login / -l albo argv[0] zaczynający się od `-`
+    let login   = args.iter().any(|a| a == "--login" || a == "-l")
+        || args.first().map(|a| a.starts_with('-')).unwrap_or(false);
 
     // ── hsh --version ────────────────────────────────────────────────────────
     if args.contains(&"--version".to_string()) || args.contains(&"-V".to_string()) {
@@ -76,18 +89,10 @@ async fn main() -> rustyline::Result<()> {
     }
 
     // ── Wczytaj konfigurację (generuje .hshrc jeśli brak) ───────────────────
-    let hk_config  = load_shell_config();
+    let hk_config  = load_shell_config(!safe);
     let aliases    = config::get_aliases(&hk_config);
     let prompt_cfg = config::get_prompt_config(&hk_config);
 
-    // Zastosuj zmienne środowiskowe z [env]
-    let env_vars = get_env_vars(&hk_config);
-    for (k, v) in &env_vars {
-        if env::var(k).is_err() { // Nie nadpisuj istniejących
-            env::set_var(k, v);
-        }
-    }
-
     // Ścieżka historii z konfiguracji (fallback: ~/.hsh-history)
     let home = env::var("HOME").unwrap_or_else(|_| "/root".to_string());
     let history_ts_path = get_history_path(&hk_config);
@@ -97,103 +102,243 @@ async fn main() -> rustyline::Result<()> {
     let path_cache_path = format!("{}/.hsh-path-cache.json",   home);
 
     // ── hsh -c "command" ────────────────────────────────────────────────────
+    // Z -i/--interactive (jak bash -ic) komenda jest wykonywana w stanie
+    // REPL-a, a potem powłoka przechodzi w tryb interaktywny zamiast wyjść.
+    let interactive = args.iter().any(|a| a == "-i" || a == "--interactive");
+    let mut initial_cmd = None::<String>;
     if let Some(pos) = args.iter().position(|a| a == "-c") {
         match args.get(pos + 1) {
+            Some(cmd) if interactive => initial_cmd = Some(cmd.clone()),
             Some(cmd) => {
-                let mut prev_dir  = None::<PathBuf>;
-                let mut jobs      = JobTable::new();
-                let mut vars      = ShellVars::new();
+                let mut prev_dir = None::<PathBuf>;
+                // Bez historii i podpowiedzi — tylko w pamięci, nic nie zapisujemy
+                let mut ctx      = ShellCtx::new(aliases, ShellHistory::ephemeral());
+                let mut hints    = SmartHints::default();
 
                 // Zastosuj opcje powłoki z konfiguracji
-                apply_shell_options(&mut vars, &hk_config);
+                apply_shell_options(&mut ctx.vars, &hk_config, safe, posix);
+                load_config_functions(&hk_config, &mut ctx.functions);
 
-                let mut hints   = SmartHints::load(&hints_path);
-                let mut history = ShellHistory::load(&history_ts_path);
                 let path_cache  = PathCache::new(&path_cache_path);
                 let mut rl: Editor<ShellHelper, rustyline::history::FileHistory> =
                     Editor::with_config(Config::builder().build())?;
                 rl.set_helper(Some(ShellHelper::new(Theme::load())));
+                if login && !no_rc && !ctx.vars.safe_mode {
+                    for startup in startup_commands(&home, &hk_config) {
+                        let _ = execute_command(
+                            &startup, &mut ctx, &mut rl, &mut prev_dir, &mut hints, &path_cache, dry_run,
+                        ).await;
+                    }
+                }
                 let code = execute_command(
-                    cmd, &aliases, &mut rl, &mut prev_dir,
-                    &mut jobs, &mut vars, &mut hints, &mut history,
-                    &path_cache, dry_run,
+                    cmd, &mut ctx, &mut rl, &mut prev_dir, &mut hints, &path_cache, dry_run,
                 ).await.unwrap_or(1);
-                hints.save(&hints_path);
-                history.save(&history_ts_path);
                 std::process::exit(code);
             }
             None => { eprintln!("hsh: -c requires an argument"); std::process::exit(1); }
         }
     }
 
-    // ── hsh script.sh [args...] ──────────────────────────────────────────────
-    // Jeśli pierwszy argument jest plikiem .sh — wykonaj skrypt
-    if args.len() >= 2 && !args[1].starts_with('-') {
-        let script_path = &args[1];
-        if script_path.ends_with(".sh") || script_path.ends_with(".hsh") {
-            return run_script_file(
-                script_path,
-                &args[2..],
-                &aliases,
-                &hints_path,
-                &history_ts_path,
-                &path_cache_path,
-                dry_run,
-                &hk_config,
-            ).await;
+    // ── hsh script [args...] ─────────────────────────────────────────────────
+    // Pierwszy argument pozycyjny to skrypt — wykonaj go nieinteraktywnie;
+    // opcje przed nim (--login, --posix…) są dozwolone. Brak pliku to błąd
+    // jak w bash (127, katalog — 126), a nie start REPL-a
+    let script_pos = args.iter().skip(1).position(|a| !a.starts_with('-')).map(|p| p + 1);
+    if let (None, Some(pos)) = (&initial_cmd, script_pos) {
+        let script_path = &args[pos];
+        match std::fs::metadata(script_path) {
+            Err(e) => {
+                eprintln!("hsh: {}: {}", script_path, builtins::io_error_text(&e));
+                std::process::exit(127);
+            }
+            Ok(meta) if meta.is_dir() => {
+                eprintln!("hsh: {}: Is a directory", script_path);
+                std::process::exit(126);
+            }
+            Ok(_) => {}
         }
+        // Skrypt nie czyta ani nie zapisuje historii
+        let mut ctx = ShellCtx::new(aliases, ShellHistory::ephemeral());
+        apply_shell_options(&mut ctx.vars, &hk_config, safe, posix);
+        load_config_functions(&hk_config, &mut ctx.functions);
+        let startup = if login && !no_rc && !ctx.vars.safe_mode {
+            startup_commands(&home, &hk_config)
+        } else {
+            Vec::new()
+        };
+        return run_script_file(
+            script_path,
+            &args[pos + 1..],
+            &mut ctx,
+            &startup,
+            &path_cache_path,
+            dry_run,
+        ).await;
     }
 
-    // ── MOTD ─────────────────────────────────────────────────────────────────
-    if let Ok(mut child) = TokioCommand::new("sh")
-        .arg("-c")
-        .arg("/usr/share/HackerOS/Archived/MOTD/hackeros-motd")
-        .stderr(std::process::Stdio::null())
-        .spawn()
-    {
-        let _ = child.wait().await;
+    // ── MOTD ([startup] motd, motd_async) ────────────────────────────────────
+    let (motd, motd_async) = config::get_motd(&hk_config);
+    if let Some(motd) = motd {
+        if let Ok(mut child) = TokioCommand::new(&motd)
+            .stderr(std::process::Stdio::null())
+            .spawn()
+        {
+            if motd_async {
+                tokio::spawn(async move { let _ = child.wait().await; });
+            } else {
+                let _ = child.wait().await;
+            }
+        }
     }
 
+    // Ctrl+C przerywa komendę, nie powłokę (wyjście tylko przez Ctrl+D / exit)
+    jobs::install_interactive_signals();
+
     // ── PathCache ────────────────────────────────────────────────────────────
     let mut path_cache = PathCache::new(&path_cache_path);
 
     // ── Rustyline ────────────────────────────────────────────────────────────
+    let history_opts = config::get_history_options(&hk_config);
+    // [shell] edit_mode = "vi" — tryb vi, domyślnie emacs
+    let vi_mode = get_shell_options(&hk_config).get("edit_mode").map(|v| v == "vi").unwrap_or(false);
+    // [shell] completion = "list" | "circular"; menu_complete = true to samo co "circular"
+    let completion = match get_shell_options(&hk_config).get("completion").map(|v| v.as_str()) {
+        Some("circular") => CompletionType::Circular,
+        _ if get_shell_options(&hk_config).get("menu_complete").map(|v| v == "true").unwrap_or(false) => {
+            CompletionType::Circular
+        }
+        _ => CompletionType::List,
+    };
+    // Historia Ctrl+R: domyślne 100 wpisów rustyline, chyba że [history] max_entries
+    let rl_history_size = if history_opts.max_entries_set { history_opts.max_entries.max(1) } else { 100 };
     let rl_config = Config::builder()
         .history_ignore_space(true)
-        .completion_type(CompletionType::List)
-        .edit_mode(EditMode::Emacs)
+        .max_history_size(rl_history_size)?
+        .history_ignore_dups(history_opts.ignore_dups)?
+        .completion_type(completion)
+        .edit_mode(if vi_mode { EditMode::Vi } else { EditMode::Emacs })
         .build();
 
     let mut rl: Editor<ShellHelper, rustyline::history::FileHistory> =
         Editor::with_config(rl_config)?;
-    rl.set_helper(Some(ShellHelper::new(Theme::load())));
+    let highlight_colors = config::get_highlight_colors(&hk_config);
+    let mut helper = ShellHelper::new(Theme::load().with_overrides(&highlight_colors));
+    helper.danger_style = config::get_danger_style(&hk_config);
+    helper.danger_patterns = config::get_dangerous_patterns(&hk_config);
+    rl.set_helper(Some(helper));
     rl.bind_sequence(KeyEvent::ctrl('l'), Cmd::ClearScreen);
-    rl.bind_sequence(KeyEvent::ctrl('r'), Cmd::HistorySearchForward);
+    // Ctrl+R / Ctrl+S — przyrostowe wyszukiwanie w historii (wstecz / w przód)
+    rl.bind_sequence(KeyEvent::ctrl('r'), Cmd::ReverseSearchHistory);
+    rl.bind_sequence(KeyEvent::ctrl('s'), Cmd::ForwardSearchHistory);
+
+    // Tryb vi: skróty znane z emacsa/readline, których rustyline w vi nie ma,
+    // oraz śledzenie trybu INSERT/NORMAL dla {vi_mode}
+    let vi_normal = Arc::new(AtomicBool::new(false));
+    if vi_mode {
+        rl.bind_sequence(KeyEvent::ctrl('a'), Cmd::Move(Movement::BeginningOfLine));
+        rl.bind_sequence(KeyEvent::ctrl('e'), Cmd::Move(Movement::EndOfLine));
+        rl.bind_sequence(KeyEvent::ctrl('k'), Cmd::Kill(Movement::EndOfLine));
+        rl.bind_sequence(KeyEvent::ctrl('p'), Cmd::PreviousHistory);
+        rl.bind_sequence(KeyEvent::ctrl('n'), Cmd::NextHistory);
+        rl.bind_sequence(
+            Event::Any,
+            EventHandler::Conditional(Box::new(ViModeHandler { normal: vi_normal.clone() })),
+        );
+        if let Some(h) = rl.helper_mut() {
+            h.vi_normal = Some(vi_normal.clone());
+        }
+    }
+
+    // Ctrl+T — fuzzy finder historii (opcjonalnie: [shell] fuzzy_finder = true)
+    let fuzzy_entries = Arc::new(Mutex::new(Vec::<String>::new()));
+    if get_shell_options(&hk_config).get("fuzzy_finder").map(|v| v == "true").unwrap_or(false) {
+        rl.bind_sequence(
+            KeyEvent::ctrl('t'),
+            EventHandler::Conditional(Box::new(FuzzyHistoryHandler { entries: fuzzy_entries.clone() })),
+        );
+    }
     let _ = rl.load_history(&history_rl_path);
 
     // ── State ────────────────────────────────────────────────────────────────
     let mut prev_dir         = None::<PathBuf>;
     let mut last_exit_code   = 0i32;
     let mut last_duration_ms = None::<u128>;
-    let mut jobs             = JobTable::new();
-    let mut vars             = ShellVars::new();
+    // Czas komendy w prompcie: [prompt] show_duration i próg cmd_duration_min_ms
+    let show_duration = prompt_cfg.get("show_duration").map(|v| v != "false").unwrap_or(true);
+    let duration_min_ms: u128 = prompt_cfg
+        .get("cmd_duration_min_ms")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2000);
+    // Funkcje i aliasy żyją przez całą sesję — także te zdefiniowane w plikach z `source`
+    let mut ctx = ShellCtx::new(aliases, ShellHistory::load(&history_ts_path));
+    load_config_functions(&hk_config, &mut ctx.functions);
 
     // Zastosuj opcje powłoki z konfiguracji
-    apply_shell_options(&mut vars, &hk_config);
+    apply_shell_options(&mut ctx.vars, &hk_config, safe, posix);
+
+    // Przywróć katalog z poprzedniej sesji (tylko bez argumentów pozycyjnych)
+    ctx.vars.remember_dir = !ctx.vars.safe_mode
+        && get_shell_options(&hk_config)
+            .get("restore_last_dir")
+            .map(|v| v == "true")
+            .unwrap_or(false);
+    let explicit_target = args[1..].iter().any(|a| !a.starts_with('-'));
+    if ctx.vars.remember_dir && !explicit_target {
+        if let Some(dir) = config::load_last_dir() {
+            let _ = env::set_current_dir(&dir);
+        }
+    }
 
-    // Wczytaj historię z poprawnej ścieżki
-    let mut shell_history = ShellHistory::load(&history_ts_path);
+    // Strefa czasowa promptu (zmieniana w sesji builtinem `tz`)
+    ctx.vars.prompt_tz = prompt_cfg.get("timezone").filter(|s| !s.is_empty()).cloned();
+
+    // Poprawianie literówek w nazwie komendy przed uruchomieniem ([shell] correct)
+    let correct = get_shell_options(&hk_config).get("correct").map(|v| v == "true").unwrap_or(false);
+
+    // Komenda uruchamiana po Enter na pustej linii (domyślnie brak)
+    let on_empty = get_shell_options(&hk_config)
+        .get("on_empty")
+        .map(|c| c.trim().to_string())
+        .filter(|c| !c.is_empty());
+
+    // Opcje historii (wczytanej z poprawnej ścieżki razem z ctx)
+    ctx.history.persist         = !ctx.vars.safe_mode;
+    ctx.history.max_entries     = history_opts.max_entries;
+    ctx.history.ignore_dups     = history_opts.ignore_dups;
+    ctx.history.ignore_patterns = history_opts.ignore_patterns;
+    // Wspólna historia między terminalami (jak SHARE_HISTORY w zsh)
+    ctx.history.shared = history_opts.shared;
+    ctx.history.timestamps = history_opts.timestamps;
     let mut smart_hints   = SmartHints::load(&hints_path);
 
-    vars.set_pwd();
+    ctx.vars.set_pwd();
 
     // Załaduj historię rustyline z wpisów hsh (synchronizacja)
     // To zapewnia działanie Ctrl+R z pełną historią
-    for entry in shell_history.entries.iter().rev().take(500).rev() {
+    for entry in ctx.history.entries.iter().rev().take(500).rev() {
         let _ = rl.add_history_entry(&entry.command);
     }
 
-    let git_rx = spawn_git_watcher();
+    // Limit czasu zapytań git ([prompt] git_timeout_ms) — prompt nie czeka na wolny FS
+    let git_timeout = prompt_cfg
+        .get("git_timeout_ms")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(150);
+    // Jak długo wynik gita jest aktualny ([prompt] git_refresh_ms); zmiana
+    // katalogu i komendy `git …` wymuszają odświeżenie od razu
+    let git_ttl = prompt_cfg
+        .get("git_refresh_ms")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(2000);
+    // Stan repozytorium (±zmiany ↑↓) w tle ([prompt] git_status, domyślnie tak)
+    let git_status = prompt_cfg.get("git_status").map(|v| v != "false").unwrap_or(true);
+    let (mut git_rx, git_refresh) = spawn_git_watcher(
+        std::time::Duration::from_millis(git_timeout),
+        std::time::Duration::from_millis(git_ttl),
+        git_status,
+    );
+    let mut git_dir   = env::current_dir().unwrap_or_default();
+    let mut git_stale = false;
 
     let mut system = System::new_with_specifics(
         RefreshKind::new()
@@ -207,6 +352,34 @@ async fn main() -> rustyline::Result<()> {
         .unwrap_or(0);
     env::set_var("HSH_DEPTH", (shell_depth + 1).to_string());
 
+    // ── Skrypty startowe: ~/.hsh_profile i [startup] commands ────────────────
+    // Wykonywane przez zwykłe `source` — funkcje, aliasy i PATH zostają w sesji.
+    // Błąd w profilu to tylko ostrzeżenie, powłoka startuje dalej.
+    if !no_rc && !ctx.vars.safe_mode {
+        for cmd in startup_commands(&home, &hk_config) {
+            if let Err(e) = execute_command(
+                &cmd, &mut ctx, &mut rl, &mut prev_dir, &mut smart_hints, &path_cache, dry_run,
+            ).await {
+                eprintln!("\x1b[38;5;214mhsh: warning: startup `{}`: {}\x1b[0m", cmd, e);
+            }
+        }
+    }
+
+    // Od teraz `cd` pochodzi od użytkownika — skrypty startowe i `-c` nie
+    // zaśmiecają bazy frecency
+    ctx.vars.interactive = true;
+
+    // ── hsh -ic "command" — wykonaj, potem REPL ──────────────────────────────
+    if let Some(cmd) = initial_cmd {
+        last_exit_code = execute_command(
+            &cmd, &mut ctx, &mut rl, &mut prev_dir, &mut smart_hints, &path_cache, dry_run,
+        )
+        .await
+        .unwrap_or(1);
+        ctx.vars.last_exit = last_exit_code;
+        ctx.jobs.check_finished();
+    }
+
     // ════════════════════════════════════════════════════════════════════════
     // REPL
     // ════════════════════════════════════════════════════════════════════════
@@ -215,23 +388,57 @@ async fn main() -> rustyline::Result<()> {
         system.refresh_cpu_usage();
         path_cache.refresh_if_stale();
 
+        // Nowy katalog albo komenda git — nie pokazuj nieaktualnej gałęzi;
+        // czekamy najwyżej tyle, ile trwa limit jednego zapytania
+        let cwd = env::current_dir().unwrap_or_default();
+        if git_stale || cwd != git_dir {
+            git_rx.borrow_and_update();
+            git_refresh.notify_one();
+            let _ = tokio::time::timeout(
+                std::time::Duration::from_millis(git_timeout + 50),
+                git_rx.changed(),
+            ).await;
+            git_dir   = cwd;
+            git_stale = false;
+        }
         let git_info = git_rx.borrow().clone();
 
-        let prompt = prompt::build_prompt(
-            &prompt_cfg,
+        let prompt_input = prompt::PromptInput {
+            cfg:              &prompt_cfg,
+            theme:            &rl.helper().expect("no helper").theme,
+            timezone:         ctx.vars.prompt_tz.as_deref(),
             last_exit_code,
             last_duration_ms,
-            shell_depth,
-            &system,
-            &git_info,
+            system:           &system,
+            git_info:         &git_info,
+        };
+        let prompt = prompt::build_prompt(&prompt_input, shell_depth, vi_mode);
+        // Prawy prompt ([prompt] rprompt) — rysowany tylko w kolorowej wersji,
+        // rustyline liczy układ linii z samego lewego promptu
+        let rprompt = prompt::build_rprompt(
+            prompt_cfg.get("rprompt").map(|s| s.as_str()).unwrap_or(""),
+            &prompt_input,
         );
 
+        // ── Wspólna historia: dociągnij wpisy innych sesji ───────────────────
+        if ctx.history.shared && !ctx.vars.safe_mode {
+            ctx.history.sync();
+            let _ = rl.clear_history();
+            let _ = rl.load_history(&history_rl_path);
+        }
+
         // ── Helper state ─────────────────────────────────────────────────────
+        if let Ok(mut entries) = fuzzy_entries.lock() {
+            *entries = ctx.history.recent_unique(1000).into_iter().map(String::from).collect();
+        }
         {
             let h = rl.helper_mut().expect("no helper");
-            h.colored_prompt = prompt.clone();
+            h.colored_prompt = prompt::attach_rprompt(&prompt, &rprompt);
             h.sync_hints(&smart_hints);
-            h.next_hint = shell_history
+            h.aliases = ctx.aliases.clone();
+            // Nowe/usunięte programy w PATH — po 127 zawsze, inaczej wg mtime katalogów
+            if last_exit_code == 127 { h.rehash(); } else { h.rehash_if_stale(); }
+            h.next_hint = ctx.history
                 .last_command()
                 .and_then(|last| {
                     smart_hints
@@ -240,15 +447,48 @@ async fn main() -> rustyline::Result<()> {
                 });
         }
 
+        // Każda nowa linia w trybie vi zaczyna się w INSERT
+        vi_normal.store(false, Ordering::Relaxed);
         match rl.readline(&prompt) {
             Ok(line) => {
+                // `polecenie \` + Enter — kolejne linie są dalszym ciągiem tej samej
+                let line = helper::join_continuations(&line);
+                // Rozwinięcie historii (!!, ^stare^nowe^) — wynik jest wypisywany jak w bash
+                let line = match expand_history(&line, &ctx.history) {
+                    Ok(Some(expanded)) => { println!("{}", expanded); expanded }
+                    Ok(None)           => line,
+                    Err(e) => {
+                        eprintln!("hsh: {}", e);
+                        ctx.vars.last_exit = 1;
+                        last_exit_code = 1;
+                        continue;
+                    }
+                };
                 let trimmed = line.trim();
-                if trimmed.is_empty() { continue; }
+                if trimmed.is_empty() {
+                    // Pusta linia: opcjonalna komenda z [shell] on_empty (bez zapisu w historii)
+                    if let Some(cmd) = &on_empty {
+                        last_exit_code = execute_command(
+                            cmd, &mut ctx, &mut rl, &mut prev_dir, &mut smart_hints, &path_cache, dry_run,
+                        )
+                        .await
+                        .unwrap_or(1);
+                        ctx.vars.last_exit = last_exit_code;
+                    }
+                    continue;
+                }
 
                 // ── Specjalne komendy meta ───────────────────────────────────
                 if trimmed == "hsh-settings" {
+                    if ctx.vars.safe_mode {
+                        eprintln!("hsh: safe mode: hsh-settings nie zapisuje motywu — pominięto");
+                        continue;
+                    }
                     run_settings();
-                    let new_theme = Theme::load();
+                    let new_theme = Theme::load().with_overrides(&highlight_colors);
+                    if ctx.vars.color_stderr.is_some() {
+                        ctx.vars.color_stderr = Some(new_theme.error_color.clone());
+                    }
                     rl.helper_mut().expect("no helper").theme = new_theme;
                     continue;
                 }
@@ -260,12 +500,38 @@ async fn main() -> rustyline::Result<()> {
                     continue;
                 }
 
-                // Dodaj do historii rustyline (dla Ctrl+R)
-                rl.add_history_entry(&line);
+                // ── Literówka w komendzie ([shell] correct) ──────────────────
+                let suggestion = if correct && !ctx.vars.posix {
+                    suggest_correction(trimmed, rl.helper().expect("no helper"), &ctx.functions)
+                } else {
+                    None
+                };
+                let offered = suggestion.is_some();
+                let line = match suggestion {
+                    Some((word, fixed)) => {
+                        eprint!("\x1b[1;33mDid you mean '{}'? [Y/n] \x1b[0m", word);
+                        io::stderr().flush().ok();
+                        let mut answer = String::new();
+                        io::stdin().read_line(&mut answer).ok();
+                        if matches!(answer.trim(), "" | "y" | "Y") { fixed } else { line }
+                    }
+                    None => line,
+                };
+                let trimmed = line.trim();
 
-                let prev_cmd = shell_history.last_command().unwrap_or_default();
-                shell_history.add(trimmed);
-                smart_hints.record(&prev_cmd, trimmed);
+                // Komendy pasujące do [history] ignore_patterns nie trafiają nigdzie
+                if !ctx.history.is_ignored(&line) {
+                    // Dodaj do historii rustyline (dla Ctrl+R)
+                    let _ = rl.add_history_entry(&line);
+                    if ctx.history.shared && !ctx.vars.safe_mode {
+                        // append_history blokuje plik i scala z wpisami innych sesji
+                        let _ = rl.append_history(&history_rl_path);
+                    }
+
+                    let prev_cmd = ctx.history.last_command().unwrap_or_default();
+                    ctx.history.add(trimmed);
+                    smart_hints.record(&prev_cmd, trimmed);
+                }
 
                 {
                     let h = rl.helper_mut().expect("no helper");
@@ -275,21 +541,20 @@ async fn main() -> rustyline::Result<()> {
                 let t0 = std::time::Instant::now();
 
                 last_exit_code = execute_command(
-                    &line, &aliases, &mut rl, &mut prev_dir,
-                    &mut jobs, &mut vars, &mut smart_hints,
-                    &mut shell_history, &path_cache, dry_run,
+                    &line, &mut ctx, &mut rl, &mut prev_dir, &mut smart_hints, &path_cache, dry_run,
                 )
                 .await
                 .unwrap_or(1);
 
-                vars.last_exit = last_exit_code;
+                ctx.vars.last_exit = last_exit_code;
+                git_stale = trimmed.split_whitespace().next() == Some("git");
 
                 last_duration_ms = {
                     let ms = t0.elapsed().as_millis();
-                    if ms >= 2000 { Some(ms) } else { None }
+                    (show_duration && ms >= duration_min_ms).then_some(ms)
                 };
 
-                if last_exit_code == 127 {
+                if last_exit_code == 127 && !ctx.vars.posix && !offered {
                     let first_word = trimmed.split_whitespace().next().unwrap_or("");
                     if let Some(suggestion) =
                         smart_hints.spellcheck(first_word, &path_cache.commands)
@@ -302,13 +567,13 @@ async fn main() -> rustyline::Result<()> {
                     }
                 }
 
-                jobs.check_finished();
+                ctx.jobs.check_finished();
             }
 
             Err(ReadlineError::Interrupted) => {
-                vars.last_exit   = 130;
-                last_exit_code   = 130;
-                last_duration_ms = None;
+                ctx.vars.last_exit = 130;
+                last_exit_code     = 130;
+                last_duration_ms   = None;
             }
 
             Err(ReadlineError::Eof) => {
@@ -324,9 +589,20 @@ async fn main() -> rustyline::Result<()> {
     }
 
     // ── Zapis przy wyjściu ───────────────────────────────────────────────────
-    shell_history.save(&history_ts_path);
+    if ctx.vars.safe_mode {
+        return Ok(());
+    }
+    if ctx.vars.remember_dir {
+        config::save_last_dir();
+    }
+    ctx.history.save(&history_ts_path);
     smart_hints.save(&hints_path);
-    rl.save_history(&history_rl_path)?;
+    if ctx.history.shared {
+        // save_history nadpisałby wpisy innych sesji
+        rl.append_history(&history_rl_path)?;
+    } else {
+        rl.save_history(&history_rl_path)?;
+    }
 
     Ok(())
 }
@@ -336,46 +612,60 @@ async fn main() -> rustyline::Result<()> {
 // ─────────────────────────────────────────────────────────────────────────────
 
 async fn run_script_file(
-    script_path:    &str,
-    script_args:    &[String],
-    aliases:        &std::collections::HashMap<String, String>,
-    hints_path:     &str,
-    history_path:   &str,
+    script_path:     &str,
+    script_args:     &[String],
+    ctx:             &mut ShellCtx,
+    startup:         &[String],
     path_cache_path: &str,
-    dry_run:        bool,
-    hk_config:      &hk_parser::HkConfig,
+    dry_run:         bool,
 ) -> rustyline::Result<()> {
-    let mut prev_dir  = None::<PathBuf>;
-    let mut jobs      = JobTable::new();
-    let mut vars      = ShellVars::new();
+    // Shebang innego interpretera (#!/bin/bash, #!/usr/bin/env python3) —
+    // zastąp proces tym interpreterem; #!/usr/bin/hsh lub brak — wykonaj sami.
+    if let Ok(contents) = std::fs::read_to_string(script_path) {
+        if let (Some(interp), _) = execute::split_shebang(&contents) {
+            if !execute::is_hsh_interpreter(&interp) {
+                use std::os::unix::process::CommandExt;
+                let err = std::process::Command::new(&interp[0])
+                    .args(&interp[1..])
+                    .arg(script_path)
+                    .args(script_args)
+                    .exec();
+                eprintln!("hsh: {}: {}: {}", script_path, interp[0], err);
+                std::process::exit(126);
+            }
+        }
+    }
 
-    apply_shell_options(&mut vars, hk_config);
+    let mut prev_dir = None::<PathBuf>;
 
     // Argumenty pozycyjne skryptu ($1, $2, ...)
-    vars.positional = script_args.to_vec();
+    ctx.vars.positional = script_args.to_vec();
     for (i, arg) in script_args.iter().enumerate() {
-        vars.set(&(i + 1).to_string(), arg);
+        ctx.vars.set(&(i + 1).to_string(), arg);
         env::set_var((i + 1).to_string(), arg);
     }
-    vars.set("0", script_path);
+    ctx.vars.set("0", script_path);
 
-    let mut hints   = SmartHints::load(hints_path);
-    let mut history = ShellHistory::load(history_path);
-    let path_cache  = PathCache::new(path_cache_path);
+    let mut hints  = SmartHints::default();
+    let path_cache = PathCache::new(path_cache_path);
 
     let mut rl: Editor<ShellHelper, rustyline::history::FileHistory> =
         Editor::with_config(Config::builder().build())?;
     rl.set_helper(Some(ShellHelper::new(Theme::load())));
 
+    // hsh --login script — najpierw profil
+    for cmd in startup {
+        let _ = execute_command(
+            cmd, ctx, &mut rl, &mut prev_dir, &mut hints, &path_cache, dry_run,
+        ).await;
+    }
+
     let code = execute_command(
         &format!("source {}", script_path),
-        aliases,
+        ctx,
         &mut rl,
         &mut prev_dir,
-        &mut jobs,
-        &mut vars,
         &mut hints,
-        &mut history,
         &path_cache,
         dry_run,
     )
@@ -385,11 +675,78 @@ async fn run_script_file(
     std::process::exit(code);
 }
 
+/// Połączone krótkie flagi jak w bash: `-ic` → `-i -c`, `-lc` → `-l -c`.
+/// Po `-c` reszta (komenda i jej argumenty) zostaje bez zmian.
+fn split_short_flags(raw: Vec<String>) -> Vec<String> {
+    let mut args = Vec::with_capacity(raw.len());
+    let mut rest = raw.into_iter();
+    if let Some(argv0) = rest.next() { args.push(argv0); }
+    for arg in rest.by_ref() {
+        let combined = arg.len() > 2
+            && arg.starts_with('-')
+            && arg[1..].chars().all(|c| matches!(c, 'i' | 'c' | 'l'));
+        if !combined {
+            let is_c = arg == "-c";
+            args.push(arg);
+            if is_c { break; }
+            continue;
+        }
+        args.extend(arg[1..].chars().map(|c| format!("-{}", c)));
+        if arg.contains('c') { break; }
+    }
+    args.extend(rest);
+    args
+}
+
+/// Funkcje z [functions] — wczytywane razem z aliasami, więc są też w `-c`,
+/// skryptach i z --no-rc; profil i [startup] commands mogą już z nich korzystać
+fn load_config_functions(hk_config: &hk_parser::HkConfig, functions: &mut FunctionTable) {
+    for def in config::get_functions(hk_config) {
+        for node in script::Parser::new(&def).parse() {
+            if let script::Node::FunctionDef { name, body } = node {
+                functions.define(&name, body);
+            }
+        }
+    }
+}
+
+/// Komendy startowe: `source ~/.hsh_profile` (jeśli istnieje) i [startup] commands
+fn startup_commands(home: &str, hk_config: &hk_parser::HkConfig) -> Vec<String> {
+    let profile = format!("{}/.hsh_profile", home);
+    let mut startup = Vec::new();
+    if Path::new(&profile).is_file() {
+        startup.push(format!("source {}", shlex::try_quote(&profile).unwrap_or_default()));
+    }
+    startup.extend(config::get_startup_commands(hk_config));
+    startup
+}
+
+/// `[shell] correct`: (podpowiedź, linia z poprawionym pierwszym słowem), gdy
+/// pierwsze słowo nie jest znaną komendą, aliasem, builtinem ani funkcją.
+/// Słowa kluczowe, przypisania i słowa z cudzysłowem/$ zostają bez zmian.
+fn suggest_correction(line: &str, helper: &ShellHelper, functions: &FunctionTable) -> Option<(String, String)> {
+    let start = line.len() - line.trim_start().len();
+    let word  = line[start..].split_whitespace().next()?;
+    if word.contains(|c: char| "\"'$=(){}[]!*?\\`;&|<>".contains(c))
+        || functions.contains(word)
+        || matches!(
+            word,
+            "if" | "then" | "else" | "elif" | "fi" | "for" | "while" | "until" | "do" | "done"
+                | "case" | "esac" | "function" | "in"
+        )
+    {
+        return None;
+    }
+    let fixed = helper.suggest_command(word)?;
+    let rest  = &line[start + word.len()..];
+    Some((fixed.to_string(), format!("{}{}{}", &line[..start], fixed, rest)))
+}
+
 // ─────────────────────────────────────────────────────────────────────────────
 // Pomocnicze: zastosuj opcje z [shell] do ShellVars
 // ─────────────────────────────────────────────────────────────────────────────
 
-fn apply_shell_options(vars: &mut ShellVars, config: &hk_parser::HkConfig) {
+fn apply_shell_options(vars: &mut ShellVars, config: &hk_parser::HkConfig, safe: bool, posix: bool) {
     let opts = get_shell_options(config);
     if opts.get("errexit").map(|v| v == "true").unwrap_or(false) {
         vars.set_option("e", true);
@@ -397,7 +754,42 @@ fn apply_shell_options(vars: &mut ShellVars, config: &hk_parser::HkConfig) {
     if opts.get("xtrace").map(|v| v == "true").unwrap_or(false) {
         vars.set_option("x", true);
     }
+    if opts.get("verbose").map(|v| v == "true").unwrap_or(false) {
+        vars.set_option("v", true);
+    }
     if opts.get("nounset").map(|v| v == "true").unwrap_or(false) {
         vars.set_option("u", true);
     }
+    vars.cd_mkdir = opts.get("cd_mkdir").map(|v| v == "ask").unwrap_or(false);
+    vars.auto_cd = config::get_auto_cd(config);
+    vars.color_stderr = opts
+        .get("color_stderr")
+        .filter(|v| *v == "true")
+        .map(|_| Theme::load().error_color);
+    vars.subshell_cmds = opts
+        .get("subshell_commands")
+        .map(|list| list.split(',').map(|s| s.trim().to_string()).filter(|s| !s.is_empty()).collect())
+        .unwrap_or_default();
+    vars.fallback_shell = opts.get("fallback_shell").filter(|s| !s.is_empty()).cloned();
+    vars.editor = opts.get("editor").filter(|s| !s.is_empty()).cloned();
+    vars.danger_patterns = config::get_dangerous_patterns(config);
+    vars.confirm_dangerous = config::get_confirm_dangerous(config);
+    vars.sudo = config::get_sudo_options(config);
+    vars.safe_mode = safe || opts.get("safe_mode").map(|v| v == "true").unwrap_or(false);
+    if vars.safe_mode {
+        // cd nie tworzy katalogów w trybie bezpiecznym
+        vars.cd_mkdir = false;
+    } else {
+        vars.confirm_allow = config::load_allowlist();
+        // Zmienne środowiskowe z [env] — w trybie bezpiecznym środowisko
+        // zostaje takie, z jakim powłoka wystartowała
+        for (k, v) in get_env_vars(config) {
+            if env::var(&k).is_err() { // Nie nadpisuj istniejących
+                env::set_var(k, v);
+            }
+        }
+    }
+    if posix || opts.get("posix").map(|v| v == "true").unwrap_or(false) {
+        vars.set_option("posix", true);
+    }
 }
@@ -1,9 +1,13 @@
 use std::collections::HashMap;
 use std::env;
 use std::path::PathBuf;
+use std::sync::{Mutex, OnceLock};
 
-use chrono::Local;
+use ansi_str::AnsiStr;
+use chrono::{FixedOffset, Local, Offset, Utc};
+use chrono_tz::Tz;
 use sysinfo::System;
+use terminal_size::{terminal_size, Width};
 
 use crate::git_info::GitInfo;
 use crate::theme::Theme;
@@ -12,6 +16,40 @@ fn is_root() -> bool {
     unsafe { libc::getuid() == 0 }
 }
 
+/// Nazwa użytkownika: $USER, a bez niego wpis z passwd
+fn user_name() -> &'static str {
+    static USER: OnceLock<String> = OnceLock::new();
+    USER.get_or_init(|| {
+        env::var("USER").ok().filter(|u| !u.is_empty()).unwrap_or_else(|| {
+            let mut pwd: libc::passwd = unsafe { std::mem::zeroed() };
+            let mut buf = vec![0 as libc::c_char; 4096];
+            let mut result: *mut libc::passwd = std::ptr::null_mut();
+            let rc = unsafe {
+                libc::getpwuid_r(libc::getuid(), &mut pwd, buf.as_mut_ptr(), buf.len(), &mut result)
+            };
+            if rc != 0 || result.is_null() || pwd.pw_name.is_null() { return String::new(); }
+            unsafe { std::ffi::CStr::from_ptr(pwd.pw_name) }.to_string_lossy().into_owned()
+        })
+    })
+}
+
+/// Nazwa hosta — pobierana raz (gethostname, potem /etc/hostname)
+fn host_name() -> &'static str {
+    static HOST: OnceLock<String> = OnceLock::new();
+    HOST.get_or_init(|| {
+        Some(crate::config::hostname())
+            .filter(|h| !h.is_empty())
+            .or_else(|| std::fs::read_to_string("/etc/hostname").ok().map(|h| h.trim().to_string()))
+            .unwrap_or_default()
+    })
+}
+
+/// Host do promptu: przy [prompt] show_host = true tylko w sesji SSH
+fn prompt_host(prompt_cfg: &HashMap<String, String>) -> &'static str {
+    let ssh_only = prompt_cfg.get("show_host").map(|v| v == "true").unwrap_or(false);
+    if ssh_only && env::var_os("SSH_CONNECTION").is_none() { "" } else { host_name() }
+}
+
 fn shorten_path(path: &PathBuf) -> String {
     if let Ok(home) = env::var("HOME") {
         let s = path.to_string_lossy();
@@ -22,6 +60,95 @@ fn shorten_path(path: &PathBuf) -> String {
     path.to_string_lossy().to_string()
 }
 
+/// Katalog do promptu wg [prompt] path_style: "full" (domyślnie), "basename"
+/// (tylko ostatni składnik) lub "truncate" (rodzice skróceni do pierwszej
+/// litery: `~/p/hsh`). Prefiks $HOME zawsze zamieniany na `~`.
+fn display_dir(prompt_cfg: &HashMap<String, String>) -> String {
+    let cwd  = env::current_dir().unwrap_or_else(|_| PathBuf::from("/"));
+    let full = shorten_path(&cwd);
+    match prompt_cfg.get("path_style").map(|s| s.as_str()) {
+        Some("basename") => {
+            if full == "~" || full == "/" { return full; }
+            cwd.file_name().map(|n| n.to_string_lossy().to_string()).unwrap_or(full)
+        }
+        Some("truncate") => {
+            let parts: Vec<&str> = full.split('/').collect();
+            let last = parts.len() - 1;
+            parts.iter().enumerate().map(|(i, p)| {
+                if i == last || p.is_empty() || *p == "~" {
+                    p.to_string()
+                } else {
+                    // .config → .c
+                    let skip = if p.starts_with('.') { 2 } else { 1 };
+                    p.chars().take(skip).collect()
+                }
+            }).collect::<Vec<_>>().join("/")
+        }
+        _ => full,
+    }
+}
+
+/// {battery} — pusty bez baterii; czerwony poniżej [prompt] battery_warn_below
+fn battery_segment(prompt_cfg: &HashMap<String, String>) -> String {
+    let warn_below = prompt_cfg
+        .get("battery_warn_below")
+        .and_then(|v| v.parse().ok())
+        .unwrap_or(20);
+    crate::battery::battery_info()
+        .map(|b| b.format(warn_below))
+        .unwrap_or_default()
+}
+
+/// {venv} — `(nazwa)` z $VIRTUAL_ENV; przy [prompt] venv_versions = true
+/// także wersja z `.nvmrc` / `.python-version` w bieżącym katalogu
+fn venv_segment(prompt_cfg: &HashMap<String, String>) -> String {
+    let mut parts = Vec::new();
+    if let Ok(venv) = env::var("VIRTUAL_ENV") {
+        if let Some(name) = std::path::Path::new(&venv).file_name() {
+            parts.push(format!("({})", name.to_string_lossy()));
+        }
+    }
+    if prompt_cfg.get("venv_versions").map(|v| v == "true").unwrap_or(false) {
+        let versions = lang_versions();
+        if !versions.is_empty() { parts.push(versions); }
+    }
+    if parts.is_empty() {
+        return String::new();
+    }
+    format!("\x1b[38;5;179m{}\x1b[0m", parts.join(" "))
+}
+
+/// `node 18.17 py 3.11` z plików wersji w bieżącym katalogu — czytane raz
+/// na katalog, przy kolejnych promptach w tym samym miejscu z pamięci
+fn lang_versions() -> String {
+    static CACHE: Mutex<Option<(PathBuf, String)>> = Mutex::new(None);
+    let cwd = env::current_dir().unwrap_or_default();
+    let Ok(mut cache) = CACHE.lock() else { return String::new() };
+    if let Some((dir, text)) = cache.as_ref() {
+        if *dir == cwd { return text.clone(); }
+    }
+    let read = |file: &str| {
+        std::fs::read_to_string(cwd.join(file))
+            .ok()
+            .and_then(|s| s.lines().next().map(|l| l.trim().trim_start_matches('v').to_string()))
+            .filter(|v| !v.is_empty())
+    };
+    let text = [("node", read(".nvmrc")), ("py", read(".python-version"))]
+        .into_iter()
+        .filter_map(|(lang, v)| v.map(|v| format!("{} {}", lang, v)))
+        .collect::<Vec<_>>()
+        .join(" ");
+    *cache = Some((cwd, text.clone()));
+    text
+}
+
+/// `took 3.4s` dla {cmd_duration} — puste, gdy komenda była krótsza od progu
+fn cmd_duration(t: &Theme, last_duration_ms: Option<u128>) -> String {
+    last_duration_ms
+        .map(|ms| format!("{}took {}\x1b[0m", t.duration_color, format_duration(ms)))
+        .unwrap_or_default()
+}
+
 fn format_duration(ms: u128) -> String {
     if ms >= 60_000 {
         format!("{}m {}s", ms / 60_000, (ms % 60_000) / 1000)
@@ -32,20 +159,102 @@ fn format_duration(ms: u128) -> String {
     }
 }
 
-pub fn build_prompt(
-    _prompt_cfg: &HashMap<String, String>,
-    last_exit_code: i32,
-    last_duration_ms: Option<u128>,
-    shell_depth: usize,
-    system: &System,
-    git_info: &GitInfo,
-) -> String {
-    let t   = Theme::load();
+/// Kolor z konfiguracji: numer palety 256 ("244") albo gotowa sekwencja ANSI
+fn color_escape(value: &str) -> String {
+    match value.parse::<u8>() {
+        Ok(n)  => format!("\x1b[38;5;{}m", n),
+        Err(_) => value.to_string(),
+    }
+}
+
+/// Przesunięcie strefy czasowej dla `tz` / [prompt] timezone.
+///
+/// Obsługiwane: `UTC`/`GMT`, stałe przesunięcia (`UTC+2`, `+05:30`, `-3`)
+/// oraz nazwy stref IANA (`Europe/Warsaw`) — liczone na bieżącą chwilę,
+/// więc uwzględniają czas letni.
+pub fn zone_offset(name: &str) -> Option<FixedOffset> {
+    let name = name.trim();
+    let upper = name.to_ascii_uppercase();
+    if matches!(upper.as_str(), "UTC" | "GMT" | "Z") {
+        return FixedOffset::east_opt(0);
+    }
+    let fixed = upper.strip_prefix("UTC").or_else(|| upper.strip_prefix("GMT")).unwrap_or(&upper);
+    if let Some(off) = parse_fixed_offset(fixed) {
+        return Some(off);
+    }
+
+    // Nazwy stref rozwiązywane raz na nazwę; przesunięcie liczone na bieżącą
+    // chwilę, bo zależy od czasu letniego
+    static ZONES: OnceLock<Mutex<HashMap<String, Option<Tz>>>> = OnceLock::new();
+    let tz = *ZONES
+        .get_or_init(|| Mutex::new(HashMap::new()))
+        .lock()
+        .ok()?
+        .entry(name.to_string())
+        .or_insert_with(|| name.parse::<Tz>().ok());
+    Some(Utc::now().with_timezone(&tz?).offset().fix())
+}
+
+/// `+2`, `-05:30`, `+0530` → FixedOffset
+fn parse_fixed_offset(s: &str) -> Option<FixedOffset> {
+    let sign = match s.chars().next()? {
+        '+' => 1,
+        '-' => -1,
+        _   => return None,
+    };
+    let digits = s[1..].replace(':', "");
+    if digits.is_empty() || digits.len() > 4 || !digits.chars().all(|c| c.is_ascii_digit()) {
+        return None;
+    }
+    let (h, m) = if digits.len() <= 2 {
+        (digits.parse::<i32>().ok()?, 0)
+    } else {
+        let split = digits.len() - 2;
+        (digits[..split].parse::<i32>().ok()?, digits[split..].parse::<i32>().ok()?)
+    };
+    if h > 14 || m > 59 { return None; }
+    FixedOffset::east_opt(sign * (h * 3600 + m * 60))
+}
+
+/// Czas do promptu — lokalny albo w strefie ustawionej przez `tz`
+fn format_time(prompt_cfg: &HashMap<String, String>, timezone: Option<&str>) -> String {
+    let offset = timezone.and_then(zone_offset);
+    let time = match offset {
+        Some(off) => Utc::now().with_timezone(&off).format("%H:%M:%S").to_string(),
+        None      => Local::now().format("%H:%M:%S").to_string(),
+    };
+    // [prompt] show_tz: "offset" (+02:00) | "name" (Europe/Warsaw) | brak
+    match prompt_cfg.get("show_tz").map(|s| s.as_str()) {
+        Some("offset") => {
+            let off = offset.unwrap_or_else(|| *Local::now().offset());
+            format!("{} {}", time, Utc::now().with_timezone(&off).format("%:z"))
+        }
+        Some("name") => format!("{} {}", time, timezone.unwrap_or("local")),
+        _ => time,
+    }
+}
+
+/// Wspólne dane lewego i prawego promptu — zbierane raz przed każdym promptem
+#[derive(Clone, Copy)]
+pub struct PromptInput<'a> {
+    pub cfg:              &'a HashMap<String, String>,
+    pub theme:            &'a Theme,
+    pub timezone:         Option<&'a str>,
+    pub last_exit_code:   i32,
+    pub last_duration_ms: Option<u128>,
+    pub system:           &'a System,
+    pub git_info:         &'a GitInfo,
+}
+
+pub fn build_prompt(input: &PromptInput, shell_depth: usize, vi_mode: bool) -> String {
+    let PromptInput {
+        cfg: prompt_cfg, theme: t, timezone, last_exit_code, last_duration_ms, system, git_info,
+    } = *input;
     let rst = "\x1b[0m";
     let dim = "\x1b[38;5;240m";
 
-    let dir  = shorten_path(&env::current_dir().unwrap_or_else(|_| PathBuf::from("/")));
-    let time = Local::now().format("%H:%M:%S").to_string();
+    let dir  = display_dir(prompt_cfg);
+    let time = format_time(prompt_cfg, timezone);
 
     // ── Git segment ───────────────────────────────────────────────────────────
     let git_seg = {
@@ -66,18 +275,33 @@ pub fn build_prompt(
                 format!("  {}{}  ", t.sep, gs)
             }
         } else {
-            String::new()
+            // Poza repozytorium: opcjonalny placeholder (no_git_symbol, domyślnie pusty)
+            match prompt_cfg.get("no_git_symbol").filter(|s| !s.is_empty()) {
+                Some(sym) => format!("  {}{}{}{}  ", t.sep, dim, sym, rst),
+                None      => String::new(),
+            }
         }
     };
 
+    // ── Kolor katalogu — inny poza repozytorium, jeśli ustawiono no_git_dir_color ─
+    let dir_color = match prompt_cfg.get("no_git_dir_color") {
+        Some(c) if git_info.branch.is_none() && !c.is_empty() => color_escape(c),
+        _ => t.dir_color.clone(),
+    };
+
     // ── Duration ──────────────────────────────────────────────────────────────
     let dur_seg = last_duration_ms
     .map(|ms| format!("  {}{}{}  ", t.duration_color, format_duration(ms), rst))
     .unwrap_or_default();
 
     // ── Exit code ─────────────────────────────────────────────────────────────
+    // Zabity sygnałem — kod i nazwa sygnału: ✗ [139 SIGSEGV]
     let exit_seg = if last_exit_code != 0 {
-        format!("  {}✗ [{}]{}  ", t.error_color, last_exit_code, rst)
+        let label = match crate::execute::signal_name(last_exit_code) {
+            Some(sig) => format!("{} {}", last_exit_code, sig),
+            None      => last_exit_code.to_string(),
+        };
+        format!("  {}✗ [{}]{}  ", t.error_color, label, rst)
     } else {
         String::new()
     };
@@ -115,13 +339,41 @@ pub fn build_prompt(
     let pc_color = if last_exit_code == 0 { &t.prompt_ok } else { &t.prompt_err };
     let pc       = format!("{}{}{} ", pc_color, t.prompt_char, rst);
 
+    // ── [prompt] format — własny układ z placeholderów ───────────────────────
+    if let Some(fmt) = prompt_cfg.get("format").filter(|f| !f.is_empty()) {
+        let values: HashMap<&str, String> = [
+            ("time",        format!("{}{}{}", t.time_color, time, rst)),
+            ("cwd",         format!("{}{}{}", dir_color, dir, rst)),
+            ("git",         git_seg.trim().to_string()),
+            ("sys",         sys_seg.trim().to_string()),
+            ("depth",       depth_seg.trim().to_string()),
+            ("root_symbol", root_seg.trim().to_string()),
+            ("exit_symbol", exit_seg.trim().to_string()),
+            ("exit_code",   last_exit_code.to_string()),
+            ("duration",    dur_seg.trim().to_string()),
+            ("cmd_duration", cmd_duration(&t, last_duration_ms)),
+            ("battery",     battery_segment(prompt_cfg)),
+            ("prompt_char", pc.trim_end().to_string()),
+            ("user",        user_name().to_string()),
+            ("host",        prompt_host(prompt_cfg).to_string()),
+            ("venv",        venv_segment(prompt_cfg)),
+            ("vi_mode",     if vi_mode { vi_mode_label(false) } else { String::new() }),
+        ].into_iter().collect();
+        return render_format(fmt, &values);
+    }
+
     // ── Assemble — wszystkie segmenty już zawierają swoje kolory ─────────────
     let mut prompt = String::new();
     prompt.push_str(&t.time_color);
     prompt.push_str(&time);
     prompt.push_str(rst);
     prompt.push_str("  ");
-    prompt.push_str(&t.dir_color);
+    // user@host w domyślnym układzie tylko przez SSH przy show_host = true
+    let host = prompt_host(prompt_cfg);
+    if !host.is_empty() && prompt_cfg.get("show_host").map(|v| v == "true").unwrap_or(false) {
+        prompt.push_str(&format!("{}{}@{}{}  ", dim, user_name(), host, rst));
+    }
+    prompt.push_str(&dir_color);
     prompt.push_str(&dir);
     prompt.push_str(rst);
     prompt.push_str(&git_seg);
@@ -133,3 +385,91 @@ pub fn build_prompt(
     prompt.push_str(&pc);
     prompt
 }
+
+/// {vi_mode} — `INSERT` / `NORMAL`. Obie etykiety mają tę samą szerokość,
+/// więc helper podmienia je w gotowym prompcie bez przeliczania układu linii.
+pub fn vi_mode_label(normal: bool) -> String {
+    if normal {
+        "\x1b[1;38;5;214mNORMAL\x1b[0m".to_string()
+    } else {
+        "\x1b[1;38;5;114mINSERT\x1b[0m".to_string()
+    }
+}
+
+/// Wypełnij szablon [prompt] format: `{nazwa}` → wartość (nieznane — puste),
+/// dosłowne `\n` → nowa linia
+fn render_format(fmt: &str, values: &HashMap<&str, String>) -> String {
+    let fmt = fmt.replace("\\n", "\n");
+    let mut out  = String::new();
+    let mut rest = fmt.as_str();
+    while let Some(open) = rest.find('{') {
+        out.push_str(&rest[..open]);
+        let after = &rest[open + 1..];
+        match after.find('}') {
+            Some(close) if after[..close].chars().all(|c| c.is_ascii_alphanumeric() || c == '_') => {
+                if let Some(v) = values.get(&after[..close]) { out.push_str(v); }
+                rest = &after[close + 1..];
+            }
+            _ => {
+                out.push('{');
+                rest = after;
+            }
+        }
+    }
+    out.push_str(rest);
+    out
+}
+
+/// Prawy prompt z szablonu [prompt] rprompt. Placeholdery:
+/// `{mem}` (zajęta pamięć w GB), `{mem_pct}`, `{cpu}`, `{time}`, `{dir}`,
+/// `{branch}`, `{exit_code}`, `{duration}`, `{cmd_duration}`, `{battery}`, `{venv}`.
+/// Pusty szablon — brak prawego promptu.
+pub fn build_rprompt(template: &str, input: &PromptInput) -> String {
+    if template.is_empty() {
+        return String::new();
+    }
+    let PromptInput {
+        cfg: prompt_cfg, theme: t, timezone, last_exit_code, last_duration_ms, system, git_info,
+    } = *input;
+    let rst = "\x1b[0m";
+
+    let used_gb  = system.used_memory() as f64 / 1_073_741_824.0;
+    let total_gb = system.total_memory() as f64 / 1_073_741_824.0;
+    let cpu      = system.cpus().first().map(|c| c.cpu_usage()).unwrap_or(0.0);
+    let mem_pct  = if total_gb > 0.0 { used_gb / total_gb * 100.0 } else { 0.0 };
+
+    let text = template
+        .replace("{mem}", &format!("{:.1}GB", used_gb))
+        .replace("{mem_pct}", &format!("{:.0}%", mem_pct))
+        .replace("{cpu}", &format!("{:.0}%", cpu))
+        .replace("{time}", &format_time(prompt_cfg, timezone))
+        .replace("{dir}", &display_dir(prompt_cfg))
+        .replace("{branch}", git_info.branch.as_deref().unwrap_or(""))
+        .replace("{exit_code}", &last_exit_code.to_string())
+        .replace("{duration}", &last_duration_ms.map(format_duration).unwrap_or_default())
+        .replace("{battery}", &battery_segment(prompt_cfg))
+        .replace("{venv}", &venv_segment(prompt_cfg))
+        .replace("{cmd_duration}", &last_duration_ms.map(|ms| format!("took {}", format_duration(ms))).unwrap_or_default());
+    format!("{}{}{}", t.duration_color, text, rst)
+}
+
+/// Dołącz prawy prompt do lewego: zapisz kursor, przejdź do kolumny
+/// `szerokość terminala − szerokość rprompt`, wypisz i wróć. Szerokości liczone
+/// po usunięciu sekwencji ANSI; gdy obie części się nie mieszczą — sam lewy prompt.
+/// W prompcie wieloliniowym prawy prompt trafia do ostatniej linii.
+pub fn attach_rprompt(prompt: &str, rprompt: &str) -> String {
+    let Some((Width(cols), _)) = terminal_size() else {
+        return prompt.to_string();
+    };
+    let (head, last) = match prompt.rfind('\n') {
+        Some(nl) => prompt.split_at(nl + 1),
+        None     => ("", prompt),
+    };
+    let left  = last.ansi_strip().chars().count();
+    let right = rprompt.ansi_strip().chars().count();
+    if right == 0 || left + right + 1 >= cols as usize {
+        return prompt.to_string();
+    }
+    let col = cols as usize - right + 1;
+    format!("{}\x1b7\x1b[{}G{}\x1b8{}", head, col, rprompt, last)
+}
@@ -1,7 +1,10 @@
 use std::collections::HashMap;
+use std::ffi::CString;
 use std::fs::{File, OpenOptions};
-use std::io;
+use std::io::{self, Write};
+use std::os::unix::ffi::OsStrExt;
 use std::os::unix::io::{IntoRawFd, RawFd};
+use std::path::Path;
 
 use libc::{dup2, close};
 
@@ -18,7 +21,10 @@ pub enum RedirectKind {
     Append,
     In,
     HereDoc,
+    /// `&>` — stdout i stderr do jednego pliku (obcięcie)
     OutErr,
+    /// `&>>` — stdout i stderr dopisywane do jednego pliku
+    AppendErr,
     DupFd,
 }
 
@@ -130,12 +136,17 @@ pub fn parse_redirections(input: &str) -> (String, Vec<Redirect>) {
                 }
             }
 
-            // &>  stdout+stderr to file
+            // &>  &>>  stdout+stderr to one file (like `>file 2>&1` / `>>file 2>&1`)
             '&' if !in_single && !in_double && chars.get(i + 1) == Some(&'>') => {
                 i += 2;
+                let kind = if chars.get(i) == Some(&'>') {
+                    i += 1;
+                    RedirectKind::AppendErr
+                } else {
+                    RedirectKind::OutErr
+                };
                 let path = read_word(&chars, &mut i);
-                redirects.push(Redirect { kind: RedirectKind::OutErr, fd: 1, target: RedirectTarget::File(path.clone()) });
-                redirects.push(Redirect { kind: RedirectKind::OutErr, fd: 2, target: RedirectTarget::File(path) });
+                redirects.push(Redirect { kind, fd: 1, target: RedirectTarget::File(path) });
             }
 
             // >>
@@ -174,35 +185,75 @@ pub fn parse_redirections(input: &str) -> (String, Vec<Redirect>) {
     (clean.trim().to_string(), redirects)
 }
 
+/// Does the command explicitly redirect stderr (`2>file`, `2>&1`, `&>file`)?
+pub fn redirects_stderr(redirects: &[Redirect]) -> bool {
+    redirects.iter().any(|r| r.fd == 2 || matches!(r.kind, RedirectKind::OutErr | RedirectKind::AppendErr))
+}
+
+/// Pierwszy plik przekierowania, którego nie da się otworzyć — sprawdzane
+/// w rodzicu bez efektów ubocznych (nic nie jest tworzone ani obcinane).
+/// Nieudany `open()` w pre_exec dociera do rodzica jako samo errno, więc
+/// tu ustalamy nazwę pliku i odróżniamy błąd przekierowania od braku programu.
+pub fn failing_target(redirects: &[Redirect]) -> Option<String> {
+    redirects.iter().find_map(|r| {
+        let RedirectTarget::File(path) = &r.target else { return None };
+        let ok = match r.kind {
+            RedirectKind::In => File::open(path).is_ok(),
+            _                => is_writable_target(Path::new(path)),
+        };
+        (!ok).then(|| path.clone())
+    })
+}
+
+/// Istniejący plik musi dać się otworzyć do zapisu, nowy — katalog z prawem zapisu
+fn is_writable_target(path: &Path) -> bool {
+    if path.exists() {
+        return OpenOptions::new().write(true).open(path).is_ok();
+    }
+    let dir = path.parent().filter(|d| !d.as_os_str().is_empty()).unwrap_or(Path::new("."));
+    let Ok(c_dir) = CString::new(dir.as_os_str().as_bytes()) else { return false };
+    dir.is_dir() && unsafe { libc::access(c_dir.as_ptr(), libc::W_OK | libc::X_OK) } == 0
+}
+
 /// Apply redirections in the child process (after fork, before exec).
 pub fn apply_redirections(
     redirects: &[Redirect],
     heredoc_bodies: &HashMap<String, String>,
 ) -> io::Result<()> {
+    // Działa w pre_exec: do rodzica dociera tylko errno, więc błędy open()
+    // zwracamy bez opakowywania (inaczej std zamienia je na EINVAL)
     for r in redirects {
         match (&r.kind, &r.target) {
             (RedirectKind::Out, RedirectTarget::File(path)) => {
-                let f = File::create(path)
-                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+                let f = File::create(path)?;
                 safe_dup2(f.into_raw_fd(), r.fd)?;
             }
             (RedirectKind::Append, RedirectTarget::File(path)) => {
-                let f = OpenOptions::new().append(true).create(true).open(path)
-                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+                let f = OpenOptions::new().append(true).create(true).open(path)?;
                 safe_dup2(f.into_raw_fd(), r.fd)?;
             }
             (RedirectKind::In, RedirectTarget::File(path)) => {
-                let f = File::open(path)
-                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
+                let f = File::open(path)?;
                 safe_dup2(f.into_raw_fd(), r.fd)?;
             }
             (RedirectKind::DupFd, RedirectTarget::Fd(dst)) => {
-                safe_dup2(*dst, r.fd)?;
+                // `2>&1` — źródło zostaje otwarte (safe_dup2 zamknąłby stdout)
+                let ret = unsafe { dup2(*dst, r.fd) };
+                if ret == -1 {
+                    return Err(io::Error::last_os_error());
+                }
             }
-            (RedirectKind::OutErr, RedirectTarget::File(path)) => {
-                let f = File::create(path)
-                .map_err(|e| io::Error::new(e.kind(), format!("{}: {}", path, e)))?;
-                safe_dup2(f.into_raw_fd(), r.fd)?;
+            (RedirectKind::OutErr | RedirectKind::AppendErr, RedirectTarget::File(path)) => {
+                // Jeden deskryptor dla obu strumieni — wspólny offset, więc
+                // stdout i stderr nie nadpisują się nawzajem
+                let append = matches!(r.kind, RedirectKind::AppendErr);
+                let f = OpenOptions::new().write(true).create(true)
+                .append(append).truncate(!append).open(path)?;
+                let fd = f.into_raw_fd();
+                if unsafe { dup2(fd, 1) } == -1 {
+                    return Err(io::Error::last_os_error());
+                }
+                safe_dup2(fd, 2)?;
             }
             (RedirectKind::HereDoc, RedirectTarget::HereDoc(delim)) => {
                 if let Some(body) = heredoc_bodies.get(delim) {
@@ -232,6 +283,53 @@ pub fn apply_redirections(
     Ok(())
 }
 
+/// Deskryptory powłoki sprzed przekierowania natywnej komendy — drop
+/// opróżnia bufory stdout/stderr i przywraca oryginały
+pub struct SavedFds(Vec<(RawFd, RawFd)>);
+
+impl Drop for SavedFds {
+    fn drop(&mut self) {
+        let _ = io::stdout().flush();
+        let _ = io::stderr().flush();
+        for &(fd, saved) in self.0.iter().rev() {
+            unsafe {
+                dup2(saved, fd);
+                close(saved);
+            }
+        }
+    }
+}
+
+/// Przekierowania natywnej komendy (echo, cat…) w procesie samej powłoki —
+/// działa do końca życia zwróconego strażnika. Błąd zawiera nazwę pliku.
+pub fn apply_in_shell(
+    redirects: &[Redirect],
+    heredoc_bodies: &HashMap<String, String>,
+) -> io::Result<SavedFds> {
+    let _ = io::stdout().flush();
+    let _ = io::stderr().flush();
+    let mut saved = SavedFds(Vec::new());
+    for r in redirects {
+        let fds = match r.kind {
+            RedirectKind::OutErr | RedirectKind::AppendErr => vec![1, 2],
+            _ => vec![r.fd],
+        };
+        for fd in fds {
+            if saved.0.iter().any(|&(f, _)| f == fd) { continue; }
+            // Kopia poza zakresem 0–9 i z CLOEXEC — nie wycieknie do dzieci
+            let copy = unsafe { libc::fcntl(fd, libc::F_DUPFD_CLOEXEC, 10) };
+            if copy >= 0 { saved.0.push((fd, copy)); }
+        }
+        apply_redirections(std::slice::from_ref(r), heredoc_bodies).map_err(|e| match &r.target {
+            RedirectTarget::File(path) => {
+                io::Error::new(e.kind(), format!("{}: {}", path, crate::builtins::io_error_text(&e)))
+            }
+            _ => e,
+        })?;
+    }
+    Ok(saved)
+}
+
 /// Safe wrapper around libc::dup2
 fn safe_dup2(old: RawFd, new: RawFd) -> io::Result<()> {
     let ret = unsafe { dup2(old, new) };
@@ -263,3 +361,108 @@ fn read_word(chars: &[char], i: &mut usize) -> String {
     }
     word
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::os::unix::process::CommandExt;
+    use std::process::Command;
+
+    fn temp_file(name: &str) -> String {
+        let path = std::env::temp_dir().join(format!("hsh-test-{}-{}", name, std::process::id()));
+        let _ = std::fs::remove_file(&path);
+        path.to_string_lossy().to_string()
+    }
+
+    /// `sh -c 'echo out; echo err >&2'` z przekierowaniami założonymi jak w execute.rs
+    fn run_with(redirects: Vec<Redirect>) {
+        let mut cmd = Command::new("/bin/sh");
+        cmd.args(["-c", "echo out; echo err >&2"]);
+        unsafe { cmd.pre_exec(move || apply_redirections(&redirects, &HashMap::new())); }
+        assert!(cmd.status().unwrap().success());
+    }
+
+    #[test]
+    fn parses_both_stream_shorthands() {
+        let (clean, r) = parse_redirections("make &> build.log");
+        assert_eq!(clean.trim(), "make");
+        assert!(matches!(r[..], [Redirect { kind: RedirectKind::OutErr, target: RedirectTarget::File(ref f), .. }] if f == "build.log"));
+
+        let (clean, r) = parse_redirections("make &>> build.log");
+        assert_eq!(clean.trim(), "make");
+        assert!(matches!(r[..], [Redirect { kind: RedirectKind::AppendErr, target: RedirectTarget::File(ref f), .. }] if f == "build.log"));
+    }
+
+    #[test]
+    fn out_err_creates_and_truncates() {
+        let path = temp_file("outerr");
+        let (_, r) = parse_redirections(&format!("x &> {}", path));
+        run_with(r.clone());
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out\nerr\n");
+
+        std::fs::write(&path, "stara, dłuższa zawartość pliku\n").unwrap();
+        run_with(r);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out\nerr\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn append_err_appends_both_streams() {
+        let path = temp_file("appenderr");
+        std::fs::write(&path, "start\n").unwrap();
+        let (_, r) = parse_redirections(&format!("x &>> {}", path));
+        run_with(r.clone());
+        run_with(r);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "start\nout\nerr\nout\nerr\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    #[test]
+    fn out_then_dup_matches_out_err() {
+        let path = temp_file("dup");
+        let (_, r) = parse_redirections(&format!("x > {} 2>&1", path));
+        run_with(r);
+        assert_eq!(std::fs::read_to_string(&path).unwrap(), "out\nerr\n");
+        let _ = std::fs::remove_file(&path);
+    }
+
+    /// Zapis przez surowy deskryptor — jak natywna komenda pisząca na fd
+    fn write_fd(fd: RawFd, text: &str) {
+        let n = unsafe { libc::write(fd, text.as_ptr() as *const libc::c_void, text.len()) };
+        assert_eq!(n, text.len() as isize);
+    }
+
+    #[test]
+    fn apply_in_shell_restores_fd_on_drop() {
+        // Wysoki numer — nie koliduje z deskryptorami równoległych testów
+        let fd = 57;
+        let (before, inside) = (temp_file("saved-before"), temp_file("saved-inside"));
+        let orig = File::create(&before).unwrap().into_raw_fd();
+        assert_eq!(unsafe { dup2(orig, fd) }, fd);
+        unsafe { close(orig); }
+
+        let r = [Redirect { kind: RedirectKind::Out, fd, target: RedirectTarget::File(inside.clone()) }];
+        {
+            let _saved = apply_in_shell(&r, &HashMap::new()).unwrap();
+            write_fd(fd, "w środku\n");
+        }
+        write_fd(fd, "po\n");
+        unsafe { close(fd); }
+
+        assert_eq!(std::fs::read_to_string(&inside).unwrap(), "w środku\n");
+        assert_eq!(std::fs::read_to_string(&before).unwrap(), "po\n");
+        let _ = std::fs::remove_file(&before);
+        let _ = std::fs::remove_file(&inside);
+    }
+
+    #[test]
+    fn apply_in_shell_error_names_file() {
+        let missing = std::env::temp_dir()
+            .join(format!("hsh-test-nodir-{}", std::process::id()))
+            .join("f");
+        let target = RedirectTarget::File(missing.display().to_string());
+        let r = [Redirect { kind: RedirectKind::Out, fd: 58, target }];
+        let err = apply_in_shell(&r, &HashMap::new()).err().expect("brak katalogu");
+        assert!(err.to_string().starts_with(&missing.display().to_string()), "{}", err);
+    }
+}
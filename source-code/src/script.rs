@@ -493,6 +493,21 @@ fn tokenize_script(input: &str) -> Vec<String> {
                 i += 1;
             }
 
+            // $(( … )) i $( … ) należą do słowa — bez rozbijania na (( / )) i ;
+            '$' if !in_single && chars.get(i + 1) == Some(&'(') => {
+                let mut depth = 0usize;
+                while i < chars.len() {
+                    let ch = chars[i];
+                    current.push(ch);
+                    i += 1;
+                    match ch {
+                        '(' => depth += 1,
+                        ')' => { depth -= 1; if depth == 0 { break; } }
+                        _   => {}
+                    }
+                }
+            }
+
             // (( jako jeden token
             '(' if !in_single && !in_double && chars.get(i + 1) == Some(&'(') => {
                 if !current.is_empty() { tokens.push(std::mem::take(&mut current)); }
@@ -1,5 +1,8 @@
+use std::collections::HashSet;
 use std::io::{self, Write};
 
+use crate::config;
+
 /// Extended list of dangerous patterns
 static DANGEROUS_PATTERNS: &[(&str, &str)] = &[
     ("rm -rf /", "This will delete ALL files on your system!"),
@@ -16,36 +19,103 @@ static DANGEROUS_PATTERNS: &[(&str, &str)] = &[
     ("wget -O- | bash", "Executing remote code is dangerous!"),
 ];
 
-/// Highlight pattern for terminal (red bold blinking)
-pub fn highlight_dangerous(line: &str) -> Option<String> {
-    for (pattern, _) in DANGEROUS_PATTERNS {
-        if line.contains(pattern) {
-            return Some(format!("\x1b[5;41m{}\x1b[0m", line));
-        }
+/// Default danger highlight: blinking text on red background
+pub const DEFAULT_DANGER_STYLE: &str = "\x1b[5;41m";
+
+/// Map a `danger_style` config value to its escape sequence
+pub fn danger_style_escape(name: &str) -> &'static str {
+    match name.trim() {
+        "bg_red"   => "\x1b[41m",
+        "bold_red" => "\x1b[1;31m",
+        _          => DEFAULT_DANGER_STYLE,
     }
-    None
+}
+
+/// Warning shown for user patterns from `[safety] dangerous_patterns`
+const USER_PATTERN_WARNING: &str = "Matches a dangerous pattern from ~/.hshrc!";
+
+/// Highlight the whole line with `style` if it matches a built-in pattern
+/// or one of the `extra` patterns from the config
+pub fn highlight_dangerous(line: &str, style: &str, extra: &[String]) -> Option<String> {
+    check_dangerous(line, extra).map(|_| format!("{}{}\x1b[0m", style, line))
 }
 
 /// Returns warning message if dangerous, None otherwise
-pub fn check_dangerous(input: &str) -> Option<&'static str> {
+pub fn check_dangerous(input: &str, extra: &[String]) -> Option<&'static str> {
     for (pattern, warning) in DANGEROUS_PATTERNS {
         if input.contains(pattern) {
             return Some(warning);
         }
     }
-    None
+    extra
+        .iter()
+        .any(|p| !p.is_empty() && input.contains(p.as_str()))
+        .then_some(USER_PATTERN_WARNING)
 }
 
 /// Ask user to confirm dangerous command. Returns true if confirmed.
-pub fn confirm_dangerous(input: &str) -> bool {
-    if let Some(warning) = check_dangerous(input) {
+/// With `confirm` off only the warning is printed.
+pub fn confirm_dangerous(
+    input:   &str,
+    extra:   &[String],
+    confirm: bool,
+    allow:   &mut HashSet<String>,
+    persist: bool,
+) -> bool {
+    if let Some(warning) = check_dangerous(input, extra) {
+        if allow.contains(&allow_key("danger", input)) {
+            return true;
+        }
         eprintln!("\x1b[1;31m⚠  DANGER: {}\x1b[0m", warning);
-        eprint!("\x1b[1;33mAre you sure? Type 'yes' to confirm: \x1b[0m");
-        io::stdout().flush().ok();
-        let mut answer = String::new();
-        io::stdin().read_line(&mut answer).ok();
-        answer.trim() == "yes"
+        !confirm || ask_confirm("danger", input, "Are you sure?", true, allow, persist)
     } else {
         true
     }
 }
+
+/// Shared confirmation prompt with a "don't ask again" allowlist.
+///
+/// Answers: `y` — allow once; `a` — allow this exact command for the
+/// session; `A` — allow it permanently (~/.hsh-allow, unless `persist` is
+/// false). In `strict` mode (dangerous commands) single keystrokes are not
+/// enough: only `yes` allows once and `yes-always` remembers the command.
+/// Anything else declines. Remembered commands are confirmed without asking.
+pub fn ask_confirm(
+    kind:     &str,
+    command:  &str,
+    question: &str,
+    strict:   bool,
+    allow:    &mut HashSet<String>,
+    persist:  bool,
+) -> bool {
+    let key = allow_key(kind, command);
+    if allow.contains(&key) {
+        return true;
+    }
+    let choices = match (strict, persist) {
+        (true, _)      => "yes/n/yes-always",
+        (false, true)  => "y/n/a/A",
+        (false, false) => "y/n/a",
+    };
+    eprint!("\x1b[1;33m{} [{}] \x1b[0m", question, choices);
+    io::stderr().flush().ok();
+    let mut answer = String::new();
+    io::stdin().read_line(&mut answer).ok();
+    let remember = |allow: &mut HashSet<String>, permanent: bool| {
+        if permanent && persist { config::append_allowlist(&key); }
+        allow.insert(key.clone());
+        true
+    };
+    match answer.trim() {
+        "yes"                  => true,
+        "yes-always" if strict => remember(allow, true),
+        "y" | "Y" if !strict   => true,
+        "a" if !strict         => remember(allow, false),
+        "A" if !strict         => remember(allow, true),
+        _ => false,
+    }
+}
+
+fn allow_key(kind: &str, command: &str) -> String {
+    format!("{}\t{}", kind, command.trim())
+}
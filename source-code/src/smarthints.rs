@@ -64,7 +64,7 @@ impl SmartHints {
         .iter()
         .map(|(k, &v)| (k.as_str(), v))
         .collect();
-        v.sort_by(|a, b| b.1.cmp(&a.1));
+        v.sort_by_key(|m| std::cmp::Reverse(m.1));
         v.into_iter().take(n).map(|(k, _)| k).collect()
     }
 
@@ -90,12 +90,15 @@ impl SmartHints {
     }
 }
 
+/// Odległość edycyjna z przestawieniem sąsiednich znaków jako jedną zmianą
+/// (`gti` → `git` = 1), inaczej literówki typu `ehco` remisują z obcymi komendami
 pub fn levenshtein(a: &str, b: &str) -> usize {
     let a: Vec<char> = a.chars().collect();
     let b: Vec<char> = b.chars().collect();
     let (m, n) = (a.len(), b.len());
     if m == 0 { return n; }
     if n == 0 { return m; }
+    let mut before: Vec<usize> = vec![0usize; n + 1];
     let mut prev: Vec<usize> = (0..=n).collect();
     let mut curr = vec![0usize; n + 1];
     for i in 1..=m {
@@ -103,7 +106,11 @@ pub fn levenshtein(a: &str, b: &str) -> usize {
         for j in 1..=n {
             let cost = usize::from(a[i - 1] != b[j - 1]);
             curr[j] = (prev[j] + 1).min(curr[j - 1] + 1).min(prev[j - 1] + cost);
+            if i > 1 && j > 1 && a[i - 1] == b[j - 2] && a[i - 2] == b[j - 1] {
+                curr[j] = curr[j].min(before[j - 2] + 1);
+            }
         }
+        std::mem::swap(&mut before, &mut prev);
         std::mem::swap(&mut prev, &mut curr);
     }
     prev[n]
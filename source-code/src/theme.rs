@@ -1,4 +1,5 @@
 use serde::{Deserialize, Serialize};
+use std::collections::HashMap;
 use std::fs;
 use std::path::PathBuf;
 
@@ -22,8 +23,50 @@ pub struct Theme {
     pub sep:            String,
     pub duration_color: String,
     pub error_color:    String,
+    /// Komentarze `# …` w linii poleceń (starsze pliki motywu — domyślny szary)
+    #[serde(default = "default_comment_color")]
+    pub comment_color:  String,
+    /// Operator `&` (praca w tle)
+    #[serde(default = "default_bg_color")]
+    pub bg_color:       String,
 }
 
+/// Kolor z konfiguracji jako sekwencja ANSI: nazwa ("green", "bright_red",
+/// "gray"), numer palety 256 ("110"), parametry SGR ("1;32") albo gotowa
+/// sekwencja z `\x1b[`
+pub fn color_value(value: &str) -> Option<String> {
+    let value = value.trim();
+    if value.starts_with('\x1b') {
+        return Some(value.to_string());
+    }
+    if let Ok(n) = value.parse::<u8>() {
+        return Some(format!("\x1b[38;5;{}m", n));
+    }
+    if !value.is_empty() && value.chars().all(|c| c.is_ascii_digit() || c == ';') {
+        return Some(format!("\x1b[{}m", value));
+    }
+    let (bright, name) = match value.to_ascii_lowercase().strip_prefix("bright_") {
+        Some(rest) => (true, rest.to_string()),
+        None       => (false, value.to_ascii_lowercase()),
+    };
+    let code = match name.as_str() {
+        "black"   => 0,
+        "red"     => 1,
+        "green"   => 2,
+        "yellow"  => 3,
+        "blue"    => 4,
+        "magenta" => 5,
+        "cyan"    => 6,
+        "white"   => 7,
+        "gray" | "grey" => return Some("\x1b[90m".into()),
+        _ => return None,
+    };
+    Some(format!("\x1b[{}m", if bright { 90 + code } else { 30 + code }))
+}
+
+fn default_comment_color() -> String { "\x1b[38;5;242m".into() }
+fn default_bg_color() -> String { "\x1b[38;5;80m".into() }
+
 impl Default for Theme {
     fn default() -> Self { Theme::default_theme() }
 }
@@ -50,6 +93,8 @@ impl Theme {
             sep:            "\x1b[38;5;238m\x1b[0m".into(),
             duration_color: "\x1b[38;5;242m".into(),
             error_color:    "\x1b[38;5;203m".into(),
+            comment_color:  "\x1b[38;5;242m".into(),
+            bg_color:       "\x1b[38;5;73m".into(),
         }
     }
 
@@ -74,6 +119,8 @@ impl Theme {
             sep:            "\x1b[38;5;57m\x1b[0m".into(),
             duration_color: "\x1b[38;5;105m".into(),
             error_color:    "\x1b[38;5;213m".into(),
+            comment_color:  "\x1b[38;5;105m".into(),
+            bg_color:       "\x1b[38;5;87m".into(),
         }
     }
 
@@ -98,6 +145,8 @@ impl Theme {
             sep:            "\x1b[38;5;238m\x1b[0m".into(),
             duration_color: "\x1b[38;5;103m".into(),
             error_color:    "\x1b[38;5;167m".into(),
+            comment_color:  "\x1b[38;5;103m".into(),
+            bg_color:       "\x1b[38;5;116m".into(),
         }
     }
 
@@ -122,6 +171,8 @@ impl Theme {
             sep:            "\x1b[38;5;239m\x1b[0m".into(),
             duration_color: "\x1b[38;5;243m".into(),
             error_color:    "\x1b[38;5;167m".into(),
+            comment_color:  "\x1b[38;5;243m".into(),
+            bg_color:       "\x1b[38;5;108m".into(),
         }
     }
 
@@ -146,6 +197,8 @@ impl Theme {
             sep:            "\x1b[38;5;61m\x1b[0m".into(),
             duration_color: "\x1b[38;5;102m".into(),
             error_color:    "\x1b[38;5;203m".into(),
+            comment_color:  "\x1b[38;5;102m".into(),
+            bg_color:       "\x1b[38;5;159m".into(),
         }
     }
 
@@ -170,6 +223,8 @@ impl Theme {
             sep:            "\x1b[38;5;22m\x1b[0m".into(),
             duration_color: "\x1b[38;5;28m".into(),
             error_color:    "\x1b[38;5;196m".into(),
+            comment_color:  "\x1b[38;5;28m".into(),
+            bg_color:       "\x1b[38;5;76m".into(),
         }
     }
 
@@ -194,6 +249,8 @@ impl Theme {
             sep:            "\x1b[38;5;248m\x1b[0m".into(),
             duration_color: "\x1b[38;5;102m".into(),
             error_color:    "\x1b[38;5;160m".into(),
+            comment_color:  "\x1b[38;5;102m".into(),
+            bg_color:       "\x1b[38;5;66m".into(),
         }
     }
 
@@ -218,6 +275,8 @@ impl Theme {
             sep:            "\x1b[38;5;236m\x1b[0m".into(),
             duration_color: "\x1b[38;5;102m".into(),
             error_color:    "\x1b[38;5;160m".into(),
+            comment_color:  "\x1b[38;5;102m".into(),
+            bg_color:       "\x1b[38;5;66m".into(),
         }
     }
 
@@ -242,6 +301,8 @@ impl Theme {
             sep:            "\x1b[38;5;238m\x1b[0m".into(),
             duration_color: "\x1b[38;5;242m".into(),
             error_color:    "\x1b[38;5;203m".into(),
+            comment_color:  "\x1b[38;5;244m".into(),
+            bg_color:       "\x1b[38;5;110m".into(),
         }
     }
 
@@ -266,6 +327,8 @@ impl Theme {
             sep:            "\x1b[38;5;237m\x1b[0m".into(),
             duration_color: "\x1b[38;5;245m".into(),
             error_color:    "\x1b[38;5;197m".into(),
+            comment_color:  "\x1b[38;5;245m".into(),
+            bg_color:       "\x1b[38;5;141m".into(),
         }
     }
 
@@ -290,6 +353,8 @@ impl Theme {
             sep:            "\x1b[38;5;238m\x1b[0m".into(),
             duration_color: "\x1b[38;5;243m".into(),
             error_color:    "\x1b[38;5;210m".into(),
+            comment_color:  "\x1b[38;5;243m".into(),
+            bg_color:       "\x1b[38;5;146m".into(),
         }
     }
 
@@ -314,6 +379,8 @@ impl Theme {
             sep:            "\x1b[38;5;250m\x1b[0m".into(),
             duration_color: "\x1b[38;5;102m".into(),
             error_color:    "\x1b[38;5;160m".into(),
+            comment_color:  "\x1b[38;5;102m".into(),
+            bg_color:       "\x1b[38;5;66m".into(),
         }
     }
 
@@ -338,6 +405,8 @@ impl Theme {
             sep:            "\x1b[38;5;238m\x1b[0m".into(),
             duration_color: "\x1b[38;5;242m".into(),
             error_color:    "\x1b[38;5;203m".into(),
+            comment_color:  "\x1b[38;5;242m".into(),
+            bg_color:       "\x1b[38;5;73m".into(),
         }
     }
 
@@ -362,6 +431,8 @@ impl Theme {
             sep:            "\x1b[38;5;236m\x1b[0m".into(),
             duration_color: "\x1b[38;5;245m".into(),
             error_color:    "\x1b[38;5;210m".into(),
+            comment_color:  "\x1b[38;5;245m".into(),
+            bg_color:       "\x1b[38;5;147m".into(),
         }
     }
 
@@ -386,6 +457,8 @@ impl Theme {
             sep:            "\x1b[38;5;236m\x1b[0m".into(),
             duration_color: "\x1b[38;5;245m".into(),
             error_color:    "\x1b[38;5;167m".into(),
+            comment_color:  "\x1b[38;5;245m".into(),
+            bg_color:       "\x1b[38;5;109m".into(),
         }
     }
 
@@ -410,6 +483,8 @@ impl Theme {
             sep:            "\x1b[38;5;236m\x1b[0m".into(),
             duration_color: "\x1b[38;5;245m".into(),
             error_color:    "\x1b[38;5;203m".into(),
+            comment_color:  "\x1b[38;5;245m".into(),
+            bg_color:       "\x1b[38;5;146m".into(),
         }
     }
 
@@ -434,6 +509,8 @@ impl Theme {
             sep:            "\x1b[38;5;236m\x1b[0m".into(),
             duration_color: "\x1b[38;5;245m".into(),
             error_color:    "\x1b[38;5;210m".into(),
+            comment_color:  "\x1b[38;5;245m".into(),
+            bg_color:       "\x1b[38;5;146m".into(),
         }
     }
 
@@ -458,6 +535,8 @@ impl Theme {
             sep:            "\x1b[38;5;236m\x1b[0m".into(),
             duration_color: "\x1b[38;5;247m".into(),
             error_color:    "\x1b[38;5;203m".into(),
+            comment_color:  "\x1b[38;5;247m".into(),
+            bg_color:       "\x1b[38;5;147m".into(),
         }
     }
 
@@ -482,6 +561,8 @@ impl Theme {
             sep:            "\x1b[38;5;57m\x1b[0m".into(),
             duration_color: "\x1b[38;5;129m".into(),
             error_color:    "\x1b[38;5;198m".into(),
+            comment_color:  "\x1b[38;5;129m".into(),
+            bg_color:       "\x1b[38;5;87m".into(),
         }
     }
 
@@ -506,10 +587,35 @@ impl Theme {
             sep:            "\x1b[38;5;250m\x1b[0m".into(),
             duration_color: "\x1b[38;5;102m".into(),
             error_color:    "\x1b[38;5;167m".into(),
+            comment_color:  "\x1b[38;5;102m".into(),
+            bg_color:       "\x1b[38;5;66m".into(),
         }
     }
 
     // ── Metody zarządzania ─────────────────────────────────────────────────────
+    /// Nadpisz kolory podświetlania rolami z [colors] w .hshrc.
+    /// Nieznane role i niepoprawne wartości są pomijane.
+    pub fn with_overrides(mut self, colors: &HashMap<String, String>) -> Self {
+        for (role, value) in colors {
+            let Some(esc) = color_value(value) else { continue };
+            let field = match role.as_str() {
+                "command_ok"      => &mut self.cmd_ok_color,
+                "command_missing" => &mut self.cmd_err_color,
+                "string"          => &mut self.string_color,
+                "variable"        => &mut self.var_color,
+                "operator"        => &mut self.op_color,
+                "option"          => &mut self.flag_color,
+                "path"            => &mut self.path_color,
+                "comment"         => &mut self.comment_color,
+                "background"      => &mut self.bg_color,
+                "hint"            => &mut self.hint_color,
+                _ => continue,
+            };
+            *field = esc;
+        }
+        self
+    }
+
     pub fn load() -> Self {
         let path = theme_path();
         if path.exists() {
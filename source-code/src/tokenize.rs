@@ -0,0 +1,452 @@
+// ─────────────────────────────────────────────────────────────────────────────
+// Wspólny tokenizer linii poleceń
+//
+// Używany zarówno przez podświetlanie składni (helper.rs), jak i przez
+// wykonanie (execute.rs / vars.rs), dzięki czemu kolory zawsze odpowiadają
+// temu, jak linia zostanie faktycznie podzielona na argumenty.
+// ─────────────────────────────────────────────────────────────────────────────
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum TokenKind {
+    /// Zwykłe słowo (komenda, argument, ścieżka)
+    Word,
+    /// Operator: ; | || && & oraz przekierowania (> >> < << <<< 2> 2>&1 &>)
+    Operator,
+    /// Komentarz: niezacytowany `#` na początku słowa aż do końca linii
+    Comment,
+}
+
+/// Rodzaj fragmentu surowego tekstu słowa — na potrzeby kolorowania
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum PartKind {
+    Plain,
+    Escape,
+    Single,
+    Double,
+    AnsiC,
+    Var,
+}
+
+#[derive(Debug, Clone)]
+pub struct Part {
+    pub kind:  PartKind,
+    /// Zakres bajtowy w oryginalnej linii
+    pub start: usize,
+    pub end:   usize,
+}
+
+#[derive(Debug, Clone)]
+pub struct Token {
+    pub kind:     TokenKind,
+    /// Zakres bajtowy w oryginalnej linii
+    pub start:    usize,
+    pub end:      usize,
+    /// Wartość po usunięciu cudzysłowów i rozwinięciu sekwencji $'…'
+    pub value:    String,
+    /// Kolejne fragmenty surowego tekstu (puste dla operatorów)
+    pub parts:    Vec<Part>,
+    /// Słowo kończy się w otwartym cudzysłowie lub samotnym `\`
+    pub unclosed: bool,
+    /// Wartość jako wzorzec glob — znaki z cudzysłowów/escape'ów są
+    /// zacytowane (`[*]`), więc pasują tylko dosłownie
+    pub pattern:  String,
+    /// Słowo zawiera niezacytowany `*`, `?` lub `[`
+    pub has_glob: bool,
+}
+
+impl Token {
+    /// Dopisz znak pochodzący z cudzysłowu lub escape'u (bez znaczenia glob)
+    fn push_literal(&mut self, c: char) {
+        self.value.push(c);
+        if matches!(c, '*' | '?' | '[' | ']') {
+            self.pattern.push('[');
+            self.pattern.push(c);
+            self.pattern.push(']');
+        } else {
+            self.pattern.push(c);
+        }
+    }
+
+    fn push_literal_str(&mut self, s: &str) {
+        for c in s.chars() { self.push_literal(c); }
+    }
+}
+
+/// Podziel linię na tokeny ze spanami i rodzajami.
+pub fn tokenize(line: &str) -> Vec<Token> {
+    let chars: Vec<(usize, char)> = line.char_indices().collect();
+    let byte_at = |i: usize| chars.get(i).map(|c| c.0).unwrap_or(line.len());
+    let mut tokens = Vec::new();
+    let mut i = 0;
+
+    while i < chars.len() {
+        if chars[i].1.is_whitespace() { i += 1; continue; }
+
+        let start = i;
+        if chars[i].1 == '#' {
+            while i < chars.len() && chars[i].1 != '\n' { i += 1; }
+            tokens.push(Token {
+                kind:     TokenKind::Comment,
+                start:    byte_at(start),
+                end:      byte_at(i),
+                value:    line[byte_at(start)..byte_at(i)].to_string(),
+                parts:    Vec::new(),
+                unclosed: false,
+                pattern:  String::new(),
+                has_glob: false,
+            });
+            continue;
+        }
+
+        let is_op = operator_len(&chars, i);
+        if is_op > 0 {
+            i += is_op;
+            tokens.push(Token {
+                kind:     TokenKind::Operator,
+                start:    byte_at(start),
+                end:      byte_at(i),
+                value:    line[byte_at(start)..byte_at(i)].to_string(),
+                parts:    Vec::new(),
+                unclosed: false,
+                pattern:  String::new(),
+                has_glob: false,
+            });
+            continue;
+        }
+
+        let mut tok = Token {
+            kind:     TokenKind::Word,
+            start:    byte_at(start),
+            end:      0,
+            value:    String::new(),
+            parts:    Vec::new(),
+            unclosed: false,
+            pattern:  String::new(),
+            has_glob: false,
+        };
+
+        while i < chars.len() {
+            let c = chars[i].1;
+            if c.is_whitespace() || operator_len(&chars, i) > 0 { break; }
+            let part_start = i;
+
+            match c {
+                '\\' => {
+                    match chars.get(i + 1).map(|c| c.1) {
+                        Some('\n') => {}
+                        Some(nc)   => tok.push_literal(nc),
+                        None       => tok.unclosed = true,
+                    }
+                    i = (i + 2).min(chars.len());
+                    push_part(&mut tok.parts, PartKind::Escape, byte_at(part_start), byte_at(i));
+                }
+
+                '\'' => {
+                    i += 1;
+                    while i < chars.len() && chars[i].1 != '\'' {
+                        tok.push_literal(chars[i].1);
+                        i += 1;
+                    }
+                    if i < chars.len() { i += 1; } else { tok.unclosed = true; }
+                    push_part(&mut tok.parts, PartKind::Single, byte_at(part_start), byte_at(i));
+                }
+
+                '$' if chars.get(i + 1).map(|c| c.1) == Some('\'') => {
+                    i += 2;
+                    let mut closed = false;
+                    while i < chars.len() {
+                        match chars[i].1 {
+                            '\'' => { i += 1; closed = true; break; }
+                            '\\' => {
+                                let mut esc = String::new();
+                                i = ansi_c_escape(&chars, i + 1, &mut esc);
+                                tok.push_literal_str(&esc);
+                            }
+                            ch   => { tok.push_literal(ch); i += 1; }
+                        }
+                    }
+                    if !closed { tok.unclosed = true; }
+                    push_part(&mut tok.parts, PartKind::AnsiC, byte_at(part_start), byte_at(i));
+                }
+
+                '"' => {
+                    i += 1;
+                    let mut seg = part_start;
+                    let mut closed = false;
+                    while i < chars.len() {
+                        match chars[i].1 {
+                            '"' => { i += 1; closed = true; break; }
+                            '\\' => {
+                                match chars.get(i + 1).map(|c| c.1) {
+                                    Some('\n') => {}
+                                    Some(nc) if "$`\"\\".contains(nc) => tok.push_literal(nc),
+                                    Some(nc) => { tok.push_literal('\\'); tok.push_literal(nc); }
+                                    None => tok.push_literal('\\'),
+                                }
+                                i = (i + 2).min(chars.len());
+                            }
+                            '$' if var_len(&chars, i + 1) > 0 => {
+                                push_part(&mut tok.parts, PartKind::Double, byte_at(seg), byte_at(i));
+                                let end = i + 1 + var_len(&chars, i + 1);
+                                tok.push_literal_str(&line[byte_at(i)..byte_at(end)]);
+                                push_part(&mut tok.parts, PartKind::Var, byte_at(i), byte_at(end));
+                                i = end;
+                                seg = i;
+                            }
+                            ch => { tok.push_literal(ch); i += 1; }
+                        }
+                    }
+                    if !closed { tok.unclosed = true; }
+                    push_part(&mut tok.parts, PartKind::Double, byte_at(seg), byte_at(i));
+                }
+
+                '$' if var_len(&chars, i + 1) > 0 => {
+                    let end = i + 1 + var_len(&chars, i + 1);
+                    tok.value.push_str(&line[byte_at(i)..byte_at(end)]);
+                    tok.pattern.push_str(&line[byte_at(i)..byte_at(end)]);
+                    i = end;
+                    push_part(&mut tok.parts, PartKind::Var, byte_at(part_start), byte_at(i));
+                }
+
+                _ => {
+                    tok.value.push(c);
+                    tok.pattern.push(c);
+                    if matches!(c, '*' | '?' | '[') { tok.has_glob = true; }
+                    i += 1;
+                    push_part(&mut tok.parts, PartKind::Plain, byte_at(part_start), byte_at(i));
+                }
+            }
+        }
+
+        tok.end = byte_at(i);
+        tokens.push(tok);
+    }
+
+    tokens
+}
+
+/// Podziel linię na argumenty (jak `shlex::split`, ale wspólną logiką
+/// z podświetlaniem). Zwraca None przy niezamkniętym cudzysłowie.
+/// Komentarze są pomijane.
+pub fn split(line: &str) -> Option<Vec<String>> {
+    let tokens = tokenize(line);
+    if tokens.iter().any(|t| t.unclosed) { return None; }
+    Some(tokens.into_iter().filter(|t| t.kind != TokenKind::Comment).map(|t| t.value).collect())
+}
+
+/// Błąd podziału argumentów builtinu
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum SplitError {
+    /// Niezamknięty cudzysłów lub samotny `\` na końcu
+    Unclosed,
+    /// Operator (`|`, `;`, `&&`, `>`…) tam, gdzie oczekiwane są same słowa
+    Operator(String),
+}
+
+impl std::fmt::Display for SplitError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            SplitError::Unclosed     => write!(f, "unterminated quote"),
+            SplitError::Operator(op) => write!(f, "unexpected `{}`", op),
+        }
+    }
+}
+
+/// Jak `split`, ale dla argumentów builtinów: operator nie jest słowem,
+/// tylko błędem — `bookmark go x | cat` nie dostaje `|` jako argumentu.
+pub fn split_args(line: &str) -> Result<Vec<String>, SplitError> {
+    let tokens = tokenize(line);
+    if tokens.iter().any(|t| t.unclosed) { return Err(SplitError::Unclosed); }
+    if let Some(op) = tokens.iter().find(|t| t.kind == TokenKind::Operator) {
+        return Err(SplitError::Operator(op.value.clone()));
+    }
+    Ok(tokens.into_iter().filter(|t| t.kind == TokenKind::Word).map(|t| t.value).collect())
+}
+
+fn push_part(parts: &mut Vec<Part>, kind: PartKind, start: usize, end: usize) {
+    if start >= end { return; }
+    if let Some(last) = parts.last_mut() {
+        if last.kind == kind && last.end == start {
+            last.end = end;
+            return;
+        }
+    }
+    parts.push(Part { kind, start, end });
+}
+
+/// Długość operatora zaczynającego się na pozycji `i` (0 — brak operatora).
+/// Przekierowania z numerem deskryptora (`2>`, `2>&1`) są jednym tokenem.
+fn operator_len(chars: &[(usize, char)], i: usize) -> usize {
+    let at = |k: usize| chars.get(k).map(|c| c.1);
+
+    // N>  N>>  N>&M  N<  — tylko na początku słowa
+    let mut j = i;
+    if at(i).map(|c| c.is_ascii_digit()).unwrap_or(false)
+        && (i == 0 || chars[i - 1].1.is_whitespace() || operator_len(chars, i - 1) > 0)
+    {
+        while at(j).map(|c| c.is_ascii_digit()).unwrap_or(false) { j += 1; }
+        if !matches!(at(j), Some('>') | Some('<')) { return 0; }
+    }
+
+    match at(j) {
+        Some('>') => {
+            j += 1;
+            match at(j) {
+                Some('>') => j += 1,
+                Some('&') => {
+                    j += 1;
+                    while at(j).map(|c| c.is_ascii_digit() || c == '-').unwrap_or(false) { j += 1; }
+                }
+                _ => {}
+            }
+            j - i
+        }
+        Some('<') => {
+            j += 1;
+            if at(j) == Some('<') {
+                j += 1;
+                // <<- (heredoc z wcięciem) albo <<< (here-string)
+                if matches!(at(j), Some('-') | Some('<')) { j += 1; }
+            }
+            j - i
+        }
+        Some('&') if j == i => match at(i + 1) {
+            Some('&') => 2,
+            Some('>') => if at(i + 2) == Some('>') { 3 } else { 2 },
+            _ => 1,
+        },
+        Some('|') if j == i => if at(i + 1) == Some('|') { 2 } else { 1 },
+        Some(';') if j == i => 1,
+        _ => 0,
+    }
+}
+
+/// Długość odwołania do zmiennej po `$` (0 — samotny `$`):
+/// `$NAME`, `${…}`, `$(…)`, `$((…))` oraz zmienne specjalne.
+fn var_len(chars: &[(usize, char)], i: usize) -> usize {
+    let at = |k: usize| chars.get(k).map(|c| c.1);
+    match at(i) {
+        Some('(') | Some('{') => {
+            let (open, close) = if at(i) == Some('(') { ('(', ')') } else { ('{', '}') };
+            let mut depth = 0i32;
+            let mut j = i;
+            while j < chars.len() {
+                let c = chars[j].1;
+                if c == open { depth += 1; }
+                if c == close {
+                    depth -= 1;
+                    if depth == 0 { return j + 1 - i; }
+                }
+                j += 1;
+            }
+            j - i
+        }
+        Some(c) if "?#@$*!-0123456789".contains(c) => 1,
+        Some(c) if c.is_alphabetic() || c == '_' => {
+            let mut j = i;
+            while at(j).map(|c| c.is_alphanumeric() || c == '_').unwrap_or(false) { j += 1; }
+            j - i
+        }
+        _ => 0,
+    }
+}
+
+/// Rozwiń sekwencję `\x` wewnątrz `$'…'`; `i` wskazuje znak po `\`.
+/// Zwraca pozycję za sekwencją.
+fn ansi_c_escape(chars: &[(usize, char)], i: usize, out: &mut String) -> usize {
+    let Some(&(_, c)) = chars.get(i) else {
+        out.push('\\');
+        return i;
+    };
+    let simple = match c {
+        'n'  => Some('\n'),
+        't'  => Some('\t'),
+        'r'  => Some('\r'),
+        'a'  => Some('\x07'),
+        'b'  => Some('\x08'),
+        'e' | 'E' => Some('\x1b'),
+        'f'  => Some('\x0c'),
+        'v'  => Some('\x0b'),
+        '\\' => Some('\\'),
+        '\'' => Some('\''),
+        '"'  => Some('"'),
+        _    => None,
+    };
+    if let Some(ch) = simple {
+        out.push(ch);
+        return i + 1;
+    }
+
+    let (radix, max, skip) = match c {
+        'x'            => (16, 2, 1),
+        '0'..='7'      => (8, 3, 0),
+        _              => {
+            out.push('\\');
+            out.push(c);
+            return i + 1;
+        }
+    };
+    let mut j = i + skip;
+    let mut digits = String::new();
+    while digits.len() < max {
+        match chars.get(j) {
+            Some(&(_, d)) if d.is_digit(radix) => { digits.push(d); j += 1; }
+            _ => break,
+        }
+    }
+    match u32::from_str_radix(&digits, radix).ok().and_then(char::from_u32) {
+        Some(ch) => out.push(ch),
+        None     => { out.push('\\'); out.push(c); return i + 1; }
+    }
+    j
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    fn words(line: &str) -> Vec<String> {
+        split(line).expect("zamknięte cudzysłowy")
+    }
+
+    #[test]
+    fn hash_at_word_start_starts_comment() {
+        assert_eq!(words("echo a # b c"), ["echo", "a"]);
+        assert_eq!(words("# cała linia"), Vec::<String>::new());
+        assert_eq!(words("ls;# po operatorze"), ["ls", ";"]);
+    }
+
+    #[test]
+    fn hash_inside_word_or_quotes_is_literal() {
+        assert_eq!(words("echo a#b"), ["echo", "a#b"]);
+        assert_eq!(words("echo '#' \"#x\" \\#y"), ["echo", "#", "#x", "#y"]);
+        assert_eq!(words("echo $# ${#x}"), ["echo", "$#", "${#x}"]);
+    }
+
+    #[test]
+    fn comment_ends_at_newline() {
+        let toks = tokenize("a # x\nb");
+        let kinds: Vec<_> = toks.iter().map(|t| (t.kind, t.value.as_str())).collect();
+        assert_eq!(kinds, [
+            (TokenKind::Word, "a"),
+            (TokenKind::Comment, "# x"),
+            (TokenKind::Word, "b"),
+        ]);
+    }
+
+    #[test]
+    fn split_args_rejects_operators() {
+        assert_eq!(split_args("go 'a b' # x"), Ok(vec!["go".into(), "a b".into()]));
+        assert_eq!(split_args("go a | cat"), Err(SplitError::Operator("|".into())));
+        assert_eq!(split_args("a && b"), Err(SplitError::Operator("&&".into())));
+        assert_eq!(split_args("a > out"), Err(SplitError::Operator(">".into())));
+        assert_eq!(split_args("a ';' '>'"), Ok(vec!["a".into(), ";".into(), ">".into()]));
+        assert_eq!(split_args("a 'b"), Err(SplitError::Unclosed));
+        assert_eq!(SplitError::Operator(";".into()).to_string(), "unexpected `;`");
+    }
+
+    #[test]
+    fn quote_inside_comment_is_not_unclosed() {
+        assert_eq!(split("echo hi # don't"), Some(vec!["echo".into(), "hi".into()]));
+    }
+}
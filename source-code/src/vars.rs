@@ -1,19 +1,65 @@
-use std::collections::HashMap;
+use std::cell::Cell;
+use std::collections::{HashMap, HashSet};
 use std::env;
-use std::process::Command;
+use std::process::{Command, Stdio};
 use std::time::Instant;
 use rand::Rng;
 
+use crate::tokenize::{tokenize, TokenKind};
+
 pub struct ShellVars {
     pub local:      HashMap<String, String>,
     pub last_exit:  i32,
     pub positional: Vec<String>,
     pub errexit:    bool,
     pub xtrace:     bool,
+    /// Wypisz na stderr w pełni rozwiniętą komendę przed uruchomieniem (set -v)
+    pub verbose:    bool,
+    /// Tryb zgodności POSIX (--posix / [shell] posix / set -o posix): bez
+    /// auto-sudo, potwierdzeń, obsługi .sh/.hl i z komunikatami jak w sh
+    pub posix:      bool,
     pub nounset:    bool,
     pub start_time: Instant,
     pub line_no:    usize,
     pub dir_stack:  Vec<String>,
+    /// cd do nieistniejącego katalogu pyta o jego utworzenie ([shell] cd_mkdir = "ask")
+    pub cd_mkdir:   bool,
+    /// Sama ścieżka do katalogu działa jak `cd` ([options] / [shell] auto_cd = true)
+    pub auto_cd:    bool,
+    /// Kolor stderr komend zewnętrznych ([shell] color_stderr = true) —
+    /// error_color motywu wczytany raz; None — bez kolorowania
+    pub color_stderr: Option<String>,
+    /// Tryb bezpieczny (--safe / [shell] safe_mode): bez auto-chmod, bez
+    /// zapisu stanu i z potwierdzeniem przed komendami piszącymi na dysk
+    pub safe_mode:  bool,
+    /// Powłoka dla `$(…)` i innych delegacji do `-c` ([shell] fallback_shell)
+    pub fallback_shell: Option<String>,
+    /// Kod wyjścia ostatniego podstawienia `$(…)` z bieżącej ekspansji
+    /// (Cell — `expand` działa na `&self`)
+    pub subst_status: Cell<Option<i32>>,
+    /// Dodatkowe wzorce niebezpiecznych komend ([safety] dangerous_patterns)
+    pub danger_patterns: Vec<String>,
+    /// Pytaj przed niebezpieczną komendą ([safety] confirm_dangerous)
+    pub confirm_dangerous: bool,
+    /// Komendy potwierdzone odpowiedzią "a"/"A" (auto-sudo, niebezpieczne)
+    pub confirm_allow: HashSet<String>,
+    /// Auto-sudo: edytory, chronione katalogi i sudo bez pytania ([sudo])
+    pub sudo:       crate::config::SudoOptions,
+    /// Ostatnia komenda zewnętrzna dostała odmowę dostępu (EACCES przy
+    /// uruchomieniu lub przekierowaniu, "Permission denied" na stderr)
+    pub permission_denied: bool,
+    /// Edytor dla `fc`, gdy brak $FCEDIT/$EDITOR ([shell] editor)
+    pub editor:     Option<String>,
+    /// Strefa czasowa czasu w prompcie (`tz`, [prompt] timezone); None — lokalna
+    pub prompt_tz:  Option<String>,
+    /// Komendy uruchamiające interaktywną podpowłokę ([shell] subshell_commands)
+    pub subshell_cmds: Vec<String>,
+    /// Kody wyjścia poszczególnych etapów ostatniego pipeline'u ($PIPESTATUS)
+    pub pipestatus: Vec<i32>,
+    /// Zapisuj katalog przy wyjściu ([shell] restore_last_dir)
+    pub remember_dir: bool,
+    /// Sesja interaktywna — tylko wtedy `cd` zasila bazę frecency dla `j`
+    pub interactive: bool,
 }
 
 impl ShellVars {
@@ -24,10 +70,29 @@ impl ShellVars {
             positional: Vec::new(),
             errexit:    false,
             xtrace:     false,
+            verbose:    false,
+            posix:      false,
             nounset:    false,
             start_time: Instant::now(),
             line_no:    0,
             dir_stack:  Vec::new(),
+            cd_mkdir:   false,
+            auto_cd:    false,
+            pipestatus: vec![0],
+            remember_dir: false,
+            interactive: false,
+            color_stderr: None,
+            safe_mode:  false,
+            fallback_shell: None,
+            editor:     None,
+            confirm_allow: HashSet::new(),
+            sudo:       crate::config::SudoOptions::default(),
+            permission_denied: false,
+            danger_patterns: Vec::new(),
+            confirm_dangerous: true,
+            subst_status: Cell::new(None),
+            prompt_tz:  None,
+            subshell_cmds: Vec::new(),
         };
         // Domyślne IFS
         s.local.insert("IFS".to_string(), " \t\n".to_string());
@@ -47,9 +112,19 @@ impl ShellVars {
             "@" | "*" => return Some(self.positional.join(" ")),
             "RANDOM"  => return Some(rand::thread_rng().gen_range(0u32..=32767).to_string()),
             "SECONDS" => return Some(self.start_time.elapsed().as_secs().to_string()),
-            "PWD"     => return Some(
-                env::current_dir().unwrap_or_default().to_string_lossy().to_string()
-            ),
+            "PWD"     => {
+                // Logiczne PWD (po cd przez dowiązanie), o ile nadal jest aktualne
+                let cwd = env::current_dir().unwrap_or_default();
+                let same = |p: &String| {
+                    std::fs::canonicalize(p).ok() == std::fs::canonicalize(&cwd).ok()
+                };
+                // Na starcie sesji — $PWD odziedziczone po rodzicu, jak w bash
+                return Some(
+                    self.local.get("PWD").cloned().or_else(|| env::var("PWD").ok())
+                        .filter(|p| p.starts_with('/') && same(p))
+                        .unwrap_or_else(|| cwd.to_string_lossy().to_string()),
+                );
+            }
             "OLDPWD"  => return self.local.get("OLDPWD").cloned(),
             "LINENO"  => return Some(self.line_no.to_string()),
             "PPID"    => return Some(unsafe { libc::getppid() }.to_string()),
@@ -64,6 +139,14 @@ impl ShellVars {
             _ => {}
         }
 
+        // $PIPESTATUS, ${PIPESTATUS[n]}, ${PIPESTATUS[@]}
+        if let Some(rest) = key
+            .strip_prefix("PIPESTATUS")
+            .filter(|r| r.is_empty() || r.starts_with('['))
+        {
+            return self.pipestatus_element(rest);
+        }
+
         // Argumenty pozycyjne $1 $2 ...
         if let Ok(n) = key.parse::<usize>() {
             if n >= 1 {
@@ -94,6 +177,7 @@ impl ShellVars {
             map.insert("OLDPWD".to_string(), old.clone());
         }
         map.insert("LINENO".to_string(), self.line_no.to_string());
+        map.insert("PIPESTATUS".to_string(), self.pipestatus_element("").unwrap_or_default());
         map
     }
 
@@ -108,8 +192,13 @@ impl ShellVars {
         let mut result = String::new();
         let chars: Vec<char> = input.chars().collect();
         let mut i = 0;
+        let mut in_double = false;
 
         while i < chars.len() {
+            if let Some(next) = copy_quoted(&chars, i, &mut in_double, &mut result) {
+                i = next;
+                continue;
+            }
             // $( ... ) — nie mylić z $(( ... ))
             if chars[i] == '$'
                 && chars.get(i + 1) == Some(&'(')
@@ -128,7 +217,7 @@ impl ShellVars {
                 }
                 let cmd: String = chars[start..i].iter().collect();
                 if i < chars.len() { i += 1; }
-                result.push_str(&run_substitution(&cmd));
+                result.push_str(&self.run_substitution(&cmd));
             }
             // `command`
             else if chars[i] == '`' {
@@ -137,7 +226,7 @@ impl ShellVars {
                 while i < chars.len() && chars[i] != '`' { i += 1; }
                 let cmd: String = chars[start..i].iter().collect();
                 if i < chars.len() { i += 1; }
-                result.push_str(&run_substitution(&cmd));
+                result.push_str(&self.run_substitution(&cmd));
             }
             else {
                 result.push(chars[i]);
@@ -147,13 +236,63 @@ impl ShellVars {
         result
     }
 
+    /// Powłoka (program i opcje przed `-c`) dla komend delegowanych: `$(…)`
+    /// i ponowienia przez `sudo`. [shell] fallback_shell, domyślnie samo hsh
+    /// z bieżącym --safe/--posix, a gdy nie da się go znaleźć — sh.
+    pub fn delegate_shell(&self) -> Vec<String> {
+        match (&self.fallback_shell, env::current_exe()) {
+            (Some(shell), _) => vec![shell.clone()],
+            (None, Ok(hsh))  => {
+                let mut argv = vec![hsh.to_string_lossy().to_string()];
+                if self.safe_mode { argv.push("--safe".to_string()); }
+                if self.posix { argv.push("--posix".to_string()); }
+                argv
+            }
+            (None, Err(_))   => vec!["sh".to_string()],
+        }
+    }
+
+    /// Wykonaj `cmd` i zwróć jego stdout bez końcowych newline'ów.
+    /// Przez `delegate_shell` — zmienne powłoki trafiają do środowiska
+    /// podpowłoki, stderr idzie na terminal, a kod wyjścia do `subst_status`.
+    fn run_substitution(&self, cmd: &str) -> String {
+        let shell = self.delegate_shell();
+        let out = Command::new(&shell[0])
+            .args(&shell[1..])
+            .arg("-c")
+            .arg(cmd)
+            .envs(self.local.iter().filter(|(k, _)| is_env_name(k)))
+            .stdin(Stdio::inherit())
+            .stderr(Stdio::inherit())
+            .output();
+
+        match out {
+            Ok(o) => {
+                self.subst_status.set(Some(crate::execute::exit_code(&o.status)));
+                let s = String::from_utf8_lossy(&o.stdout).to_string();
+                // POSIX: usuń końcowe newlines
+                s.trim_end_matches('\n').to_string()
+            }
+            Err(e) => {
+                eprintln!("hsh: $(…): {}", e);
+                self.subst_status.set(Some(127));
+                String::new()
+            }
+        }
+    }
+
     /// Ekspanduj $VAR i ${VAR} (po ekspansji komend).
     fn expand_vars(&self, input: &str) -> String {
         let mut result = String::new();
         let chars: Vec<char> = input.chars().collect();
         let mut i = 0;
+        let mut in_double = false;
 
         while i < chars.len() {
+            if let Some(next) = copy_quoted(&chars, i, &mut in_double, &mut result) {
+                i = next;
+                continue;
+            }
             if chars[i] != '$' {
                 result.push(chars[i]);
                 i += 1;
@@ -292,10 +431,29 @@ impl ShellVars {
         result
     }
 
+    /// Element $PIPESTATUS dla sufiksu `""`, `"[n]"`, `"[@]"` lub `"[*]"`.
+    fn pipestatus_element(&self, subscript: &str) -> Option<String> {
+        let index = match subscript {
+            ""            => "0",
+            s if s.starts_with('[') && s.ends_with(']') => &s[1..s.len() - 1],
+            _             => return None,
+        };
+        match index {
+            "@" | "*" => Some(
+                self.pipestatus.iter().map(|c| c.to_string()).collect::<Vec<_>>().join(" "),
+            ),
+            n => n.parse::<usize>().ok()
+                .and_then(|i| self.pipestatus.get(i))
+                .map(|c| c.to_string()),
+        }
+    }
+
     pub fn set_option(&mut self, name: &str, value: bool) {
         match name {
             "errexit" | "e" => self.errexit = value,
             "xtrace"  | "x" => self.xtrace  = value,
+            "verbose" | "v" => self.verbose = value,
+            "posix"         => self.posix   = value,
             "nounset" | "u" => self.nounset  = value,
             _ => {}
         }
@@ -313,6 +471,44 @@ impl ShellVars {
         env::set_var("PWD", &pwd);
     }
 
+    /// Ścieżka logiczna `target` względem $PWD: `..` zdejmuje ostatni
+    /// składnik tekstowo (rodzic dowiązania, nie katalogu docelowego).
+    pub fn logical_path(&self, target: &str) -> String {
+        let base = self.get("PWD").unwrap_or_default();
+        let mut comps: Vec<&str> = if target.starts_with('/') {
+            Vec::new()
+        } else {
+            base.split('/').filter(|c| !c.is_empty()).collect()
+        };
+        for c in target.split('/') {
+            match c {
+                "" | "." => {}
+                ".."     => { comps.pop(); }
+                c        => comps.push(c),
+            }
+        }
+        format!("/{}", comps.join("/"))
+    }
+
+    /// Ustaw PWD logicznie (z zachowaniem dowiązań symbolicznych) po `cd` —
+    /// `logical` z `logical_path` policzonego przed zmianą katalogu.
+    /// Jeśli nie wskazuje na bieżący katalog — jak set_pwd (fizycznie).
+    pub fn set_pwd_logical(&mut self, logical: &str) {
+        let same = match (std::fs::canonicalize(logical), env::current_dir()) {
+            (Ok(a), Ok(b)) => std::fs::canonicalize(b).map(|b| a == b).unwrap_or(false),
+            _              => false,
+        };
+        if !same {
+            self.set_pwd();
+            return;
+        }
+        if let Some(old) = self.local.get("PWD").cloned() {
+            self.local.insert("OLDPWD".to_string(), old);
+        }
+        self.local.insert("PWD".to_string(), logical.to_string());
+        env::set_var("PWD", logical);
+    }
+
     /// Prosta ekspansja zmiennych w heredoc (bez podstawiania komend).
     pub fn expand_in_heredoc(&self, s: &str) -> String {
         let mut result = s.to_string();
@@ -328,33 +524,48 @@ impl ShellVars {
     }
 }
 
-/// Uruchom podstawianie komendy, zwróć przycięty stdout.
-fn run_substitution(cmd: &str) -> String {
-    // Użyj hsh -c jeśli dostępny, fallback do sh
-    let shell = env::var("SHELL").unwrap_or_else(|_| "sh".to_string());
-    let out = Command::new(&shell)
-        .arg("-c")
-        .arg(cmd)
-        .output();
-
-    match out {
-        Ok(o) => {
-            let s = String::from_utf8_lossy(&o.stdout).to_string();
-            // POSIX: usuń końcowe newlines
-            s.trim_end_matches('\n').to_string()
+/// Przepisz bez ekspansji fragment, który jej nie podlega: `'…'` (poza
+/// podwójnymi cudzysłowami) i znak po `\`. Śledzi `"…"` w `in_double`.
+/// Zwraca pozycję za fragmentem; None — znak do normalnej obsługi.
+fn copy_quoted(chars: &[char], i: usize, in_double: &mut bool, out: &mut String) -> Option<usize> {
+    match chars[i] {
+        '\\' => {
+            let end = (i + 2).min(chars.len());
+            out.extend(&chars[i..end]);
+            Some(end)
         }
-        Err(_) => String::new(),
+        '"' => {
+            *in_double = !*in_double;
+            out.push('"');
+            Some(i + 1)
+        }
+        '\'' if !(*in_double || (i > 0 && chars[i - 1] == '$')) => {
+            let end = chars[i + 1..].iter().position(|&c| c == '\'')
+                .map(|p| i + 1 + p + 1)
+                .unwrap_or(chars.len());
+            out.extend(&chars[i..end]);
+            Some(end)
+        }
+        _ => None,
     }
 }
 
+/// Nazwa, którą można przekazać do środowiska procesu (bez `1`, `?` itp.)
+fn is_env_name(name: &str) -> bool {
+    name.chars().next().map(|c| c.is_ascii_alphabetic() || c == '_').unwrap_or(false)
+        && name.chars().all(|c| c.is_ascii_alphanumeric() || c == '_')
+}
+
 /// Parsuj inline przypisania zmiennych przed komendą.
 /// Zwraca (lista_par, reszta_komendy).
 pub fn parse_inline_env(input: &str) -> (Vec<(String, String)>, String) {
-    let parts = shlex::split(input).unwrap_or_default();
+    let tokens = tokenize(input);
     let mut pairs      = Vec::new();
     let mut rest_start = 0;
 
-    for (idx, part) in parts.iter().enumerate() {
+    for (idx, tok) in tokens.iter().enumerate() {
+        if tok.kind != TokenKind::Word { break; }
+        let part = &tok.value;
         if let Some(eq) = part.find('=') {
             let key = &part[..eq];
             // Klucz musi być poprawną nazwą zmiennej
@@ -370,6 +581,40 @@ pub fn parse_inline_env(input: &str) -> (Vec<(String, String)>, String) {
         break;
     }
 
-    let rest = parts[rest_start..].join(" ");
+    // Reszta brana z oryginalnego tekstu — ponowne łączenie części
+    // gubiłoby cudzysłowy (np. "a # b" stałoby się komentarzem).
+    let rest = match tokens.get(rest_start) {
+        Some(tok) => input[tok.start..].trim().to_string(),
+        None      => String::new(),
+    };
     (pairs, rest)
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn delegate_shell_follows_fallback_shell() {
+        let mut vars = ShellVars::new();
+        vars.fallback_shell = Some("/bin/bash".to_string());
+        assert_eq!(vars.delegate_shell(), ["/bin/bash"]);
+    }
+
+    #[test]
+    fn delegate_shell_defaults_to_hsh_with_current_modes() {
+        let mut vars = ShellVars::new();
+        vars.safe_mode = true;
+        vars.set_option("posix", true);
+        let shell = vars.delegate_shell();
+        assert_eq!(shell[0], env::current_exe().unwrap().to_string_lossy());
+        assert_eq!(&shell[1..], ["--safe", "--posix"]);
+    }
+
+    #[test]
+    fn substitution_uses_fallback_shell() {
+        let mut vars = ShellVars::new();
+        vars.fallback_shell = Some("sh".to_string());
+        assert_eq!(vars.expand("$(echo $((2 + 3)))"), "5");
+    }
+}
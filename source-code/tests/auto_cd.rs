@@ -0,0 +1,40 @@
+// auto_cd: sama ścieżka katalogu działa jak `cd` — z [options] albo [shell],
+// nigdy w trybie POSIX
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::Command;
+
+fn home(name: &str, hshrc: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hsh-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    let dir = fs::canonicalize(&dir).unwrap();
+    fs::write(dir.join(".hshrc"), hshrc).unwrap();
+    dir
+}
+
+fn pwd_after(home: &PathBuf, extra: &[&str]) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_hsh"))
+        .args(extra)
+        .args(["-c", "./sub; pwd"])
+        .env("HOME", home)
+        .current_dir(home)
+        .output()
+        .unwrap();
+    String::from_utf8_lossy(&out.stdout).trim_end().to_string()
+}
+
+#[test]
+fn options_and_shell_sections_enable_auto_cd() {
+    for section in ["options", "shell"] {
+        let dir = home(&format!("auto-cd-{}", section), &format!("[{}]\n-> auto_cd => true\n", section));
+        assert_eq!(pwd_after(&dir, &[]), format!("{}/sub", dir.display()));
+    }
+}
+
+#[test]
+fn posix_mode_disables_auto_cd() {
+    let dir = home("auto-cd-posix", "[options]\n-> auto_cd => true\n");
+    assert_eq!(pwd_after(&dir, &["--posix"]), dir.display().to_string());
+}
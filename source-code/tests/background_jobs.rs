@@ -0,0 +1,53 @@
+// Zadanie w tle (`cmd &`) dostaje własną grupę procesów — Ctrl+C, który
+// terminal wysyła do całej grupy pierwszoplanowej, go nie dotyczy
+
+use std::fs;
+use std::io::{BufRead, BufReader};
+use std::os::unix::process::CommandExt;
+use std::process::{Command, Stdio};
+use std::time::Duration;
+
+/// Proces istnieje i nie jest zombie (osierocone dziecko czeka na init)
+fn alive(pid: i32) -> bool {
+    fs::read_to_string(format!("/proc/{}/stat", pid))
+        .ok()
+        .and_then(|stat| stat.rsplit(')').next().and_then(|s| s.split_whitespace().next()).map(|s| s != "Z"))
+        .unwrap_or(false)
+}
+
+#[test]
+fn background_job_survives_sigint_to_foreground_group() {
+    let home = std::env::temp_dir().join(format!("hsh-test-bg-{}", std::process::id()));
+    let _ = fs::remove_dir_all(&home);
+    fs::create_dir_all(&home).unwrap();
+    fs::write(home.join(".hshrc"), "").unwrap();
+    fs::write(home.join("bg.hsh"), "sleep 30 &\nsleep 30\n").unwrap();
+
+    // hsh jako lider własnej grupy — jak powłoka na pierwszym planie terminala
+    let mut shell = Command::new(env!("CARGO_BIN_EXE_hsh"))
+        .args(["--no-rc", "bg.hsh"])
+        .env("HOME", &home)
+        .current_dir(&home)
+        .stdout(Stdio::piped())
+        .process_group(0)
+        .spawn()
+        .unwrap();
+
+    // "[1] PID" — ogłoszenie zadania w tle
+    let mut line = String::new();
+    BufReader::new(shell.stdout.take().unwrap()).read_line(&mut line).unwrap();
+    let pid: i32 = line.split_whitespace().nth(1).and_then(|p| p.parse().ok())
+        .unwrap_or_else(|| panic!("no job announcement: {:?}", line));
+    assert!(alive(pid));
+
+    // Ctrl+C: SIGINT do całej grupy pierwszoplanowej
+    std::thread::sleep(Duration::from_millis(200));
+    unsafe { libc::kill(-(shell.id() as i32), libc::SIGINT); }
+    std::thread::sleep(Duration::from_millis(200));
+    unsafe { libc::kill(-(shell.id() as i32), libc::SIGKILL); }
+    let _ = shell.wait();
+
+    let survived = alive(pid);
+    unsafe { libc::kill(pid, libc::SIGKILL); }
+    assert!(survived, "background job {} died with the foreground group", pid);
+}
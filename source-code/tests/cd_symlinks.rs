@@ -0,0 +1,52 @@
+// `cd` przez dowiązania: -L (domyślnie) liczy `..` od logicznego $PWD,
+// -P od fizycznego katalogu
+
+use std::fs;
+use std::os::unix::fs::symlink;
+use std::path::PathBuf;
+use std::process::Command;
+
+/// base/real/a/b oraz base/link → real/a/b (ścieżki kanoniczne)
+fn tree(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hsh-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("real/a/b")).unwrap();
+    let dir = fs::canonicalize(&dir).unwrap();
+    symlink(dir.join("real/a/b"), dir.join("link")).unwrap();
+    fs::write(dir.join(".hshrc"), "").unwrap();
+    dir
+}
+
+/// Wynik `pwd` po wykonaniu `cmd` w katalogu `base`
+fn pwd_after(base: &PathBuf, cmd: &str) -> String {
+    let out = Command::new(env!("CARGO_BIN_EXE_hsh"))
+        .args(["--no-rc", "-c", &format!("{}; pwd", cmd)])
+        .env("HOME", base)
+        .current_dir(base)
+        .output()
+        .unwrap();
+    assert!(out.status.success(), "{}", String::from_utf8_lossy(&out.stderr));
+    String::from_utf8_lossy(&out.stdout).trim_end().to_string()
+}
+
+#[test]
+fn logical_parent_of_symlink() {
+    let base = tree("cd-logical");
+    assert_eq!(pwd_after(&base, "cd link"), format!("{}/link", base.display()));
+    assert_eq!(pwd_after(&base, "cd link; cd .."), base.display().to_string());
+    assert_eq!(pwd_after(&base, "cd -L link/.."), base.display().to_string());
+}
+
+#[test]
+fn physical_parent_of_symlink() {
+    let base = tree("cd-physical");
+    assert_eq!(pwd_after(&base, "cd link; cd -P .."), format!("{}/real/a", base.display()));
+    assert_eq!(pwd_after(&base, "cd -P link"), format!("{}/real/a/b", base.display()));
+}
+
+#[test]
+fn trailing_slash_and_dotdot_in_path() {
+    let base = tree("cd-slash");
+    assert_eq!(pwd_after(&base, "cd link/"), format!("{}/link", base.display()));
+    assert_eq!(pwd_after(&base, "cd link/../real/a/"), format!("{}/real/a", base.display()));
+}
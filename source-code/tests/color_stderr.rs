@@ -0,0 +1,54 @@
+// [shell] color_stderr — jawne przekierowanie stderr wygrywa z kolorowaniem
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output};
+
+/// Świeży HOME z .hshrc włączającym color_stderr
+fn home(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hsh-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(".hshrc"), "[shell]\n-> color_stderr => \"true\"\n").unwrap();
+    dir
+}
+
+fn hsh(home: &PathBuf, cmd: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hsh"))
+        .args(["--no-rc", "-c", cmd])
+        .env("HOME", home)
+        .current_dir(home)
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn plain_stderr_is_painted() {
+    let home = home("paint");
+    let out = hsh(&home, "/bin/sh -c 'echo err >&2'");
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("\x1b[") && err.contains("err"), "{:?}", err);
+}
+
+#[test]
+fn dup_to_stdout_keeps_both_streams_unpainted() {
+    let home = home("dup");
+    let out = hsh(&home, "/bin/sh -c 'echo out; echo err >&2' 2>&1");
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "out\nerr\n");
+    assert!(out.stderr.is_empty());
+}
+
+#[test]
+fn dup_to_stdout_in_pipeline() {
+    let home = home("duppipe");
+    let out = hsh(&home, "/bin/sh -c 'echo out; echo err >&2' 2>&1 | /bin/cat");
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "out\nerr\n");
+}
+
+#[test]
+fn stderr_to_file_is_not_painted() {
+    let home = home("file");
+    let out = hsh(&home, "/bin/sh -c 'echo err >&2' 2>err.txt");
+    assert!(out.stderr.is_empty());
+    assert_eq!(fs::read_to_string(home.join("err.txt")).unwrap(), "err\n");
+}
@@ -0,0 +1,29 @@
+// `repeat N komenda` rozwija komendę tylko raz na przebieg: wartość zmiennej
+// nie może zostać wykonana jako $(…)
+
+use std::fs;
+use std::process::Command;
+
+fn hsh(name: &str, cmd: &str) -> String {
+    let home = std::env::temp_dir().join(format!("hsh-test-{}-{}", name, std::process::id()));
+    fs::create_dir_all(&home).unwrap();
+    fs::write(home.join(".hshrc"), "").unwrap();
+    let out = Command::new(env!("CARGO_BIN_EXE_hsh"))
+        .args(["--no-rc", "-c", cmd])
+        .env("HOME", &home)
+        .current_dir(&home)
+        .output()
+        .unwrap();
+    String::from_utf8_lossy(&out.stdout).to_string()
+}
+
+#[test]
+fn variable_value_is_not_expanded_again() {
+    let out = hsh("repeat-inject", "x='$(echo INJECTED)'; repeat 2 echo $x");
+    assert_eq!(out, "$(echo INJECTED)\n$(echo INJECTED)\n");
+}
+
+#[test]
+fn count_may_come_from_a_variable() {
+    assert_eq!(hsh("repeat-count", "n=3; repeat $n echo hi"), "hi\nhi\nhi\n");
+}
@@ -0,0 +1,54 @@
+// Tryb bezpieczny (--safe): pytanie przed zapisem na dysk, bez [env] z
+// konfiguracji, aliasy tylko w pamięci z komunikatem
+
+use std::fs;
+use std::io::Write;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn home(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hsh-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(&dir).unwrap();
+    fs::write(dir.join(".hshrc"), "[env]\n-> HSH_SAFE_TEST => \"z-konfiguracji\"\n").unwrap();
+    dir
+}
+
+fn hsh(home: &PathBuf, args: &[&str], stdin: &str) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_hsh"))
+        .args(args)
+        .env("HOME", home)
+        .env_remove("HSH_SAFE_TEST")
+        .current_dir(home)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin.as_bytes()).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+#[test]
+fn declined_write_is_not_run() {
+    let dir = home("safe-write");
+    let out = hsh(&dir, &["--safe", "-c", "echo hi | tee out"], "n\n");
+    assert!(String::from_utf8_lossy(&out.stdout).contains("Command aborted."));
+    assert!(!dir.join("out").exists());
+}
+
+#[test]
+fn config_env_is_skipped() {
+    let dir = home("safe-env");
+    let out = hsh(&dir, &["--safe", "-c", "echo \"[$HSH_SAFE_TEST]\""], "");
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "[]\n");
+    let out = hsh(&dir, &["-c", "echo \"[$HSH_SAFE_TEST]\""], "");
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "[z-konfiguracji]\n");
+}
+
+#[test]
+fn alias_reports_it_is_not_persisted() {
+    let dir = home("safe-alias");
+    let out = hsh(&dir, &["--safe", "-c", "alias ll='ls -l'"], "");
+    assert!(String::from_utf8_lossy(&out.stdout).contains("~/.hshrc not written"));
+}
@@ -0,0 +1,49 @@
+// `hsh plik` — skrypt jako pierwszy argument; brak pliku to błąd, nie REPL
+
+use std::fs;
+use std::path::PathBuf;
+use std::process::{Command, Output, Stdio};
+
+fn home(name: &str) -> PathBuf {
+    let dir = std::env::temp_dir().join(format!("hsh-test-{}-{}", name, std::process::id()));
+    let _ = fs::remove_dir_all(&dir);
+    fs::create_dir_all(dir.join("sub")).unwrap();
+    fs::write(dir.join(".hshrc"), "").unwrap();
+    dir
+}
+
+fn hsh(home: &PathBuf, script: &str) -> Output {
+    Command::new(env!("CARGO_BIN_EXE_hsh"))
+        .arg(script)
+        .env("HOME", home)
+        .current_dir(home)
+        .stdin(Stdio::null())
+        .output()
+        .unwrap()
+}
+
+#[test]
+fn missing_script_exits_127() {
+    let home = home("noscript");
+    let out = hsh(&home, "nosuchfile");
+    assert_eq!(out.status.code(), Some(127));
+    let err = String::from_utf8_lossy(&out.stderr);
+    assert!(err.contains("nosuchfile: No such file or directory"), "{:?}", err);
+}
+
+#[test]
+fn directory_as_script_exits_126() {
+    let home = home("dirscript");
+    let out = hsh(&home, "sub");
+    assert_eq!(out.status.code(), Some(126));
+    assert!(String::from_utf8_lossy(&out.stderr).contains("sub: Is a directory"));
+}
+
+#[test]
+fn existing_script_runs() {
+    let home = home("script");
+    fs::write(home.join("run"), "echo z-pliku\n").unwrap();
+    let out = hsh(&home, "run");
+    assert_eq!(out.status.code(), Some(0));
+    assert_eq!(String::from_utf8_lossy(&out.stdout), "z-pliku\n");
+}